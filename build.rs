@@ -0,0 +1,32 @@
+//! Lists the family definitions of `data/families` for `assets.rs` to embed,
+//! a new file ships with the tool without touching the code.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let dir = Path::new("data/families");
+    println!("cargo::rerun-if-changed={}", dir.display());
+
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .expect("data/families is readable")
+        .map(|entry| entry.expect("data/families is readable").file_name())
+        .filter_map(|name| name.into_string().ok())
+        .filter(|name| name.ends_with(".toml"))
+        .collect();
+    names.sort();
+
+    let entries: String = names
+        .iter()
+        .map(|name| {
+            format!(
+                "    ({name:?}, include_str!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \
+                 \"/data/families/{name}\"))),\n"
+            )
+        })
+        .collect();
+    let out = Path::new(&env::var("OUT_DIR").expect("cargo sets OUT_DIR")).join("families.rs");
+
+    fs::write(out, format!("[\n{entries}]\n")).expect("OUT_DIR is writable");
+}
@@ -1,19 +1,256 @@
+use std::fs;
 use std::path::PathBuf;
+use std::process::ExitCode;
 
-use clap::Parser;
-use sawfish::slaspec::builder::SLASpecBuilder;
+use clap::{Parser, Subcommand};
+use sawfish::slaspec::assets::Assets;
+use sawfish::slaspec::builder::{FamilyFilter, Progress, SLASpecBuilder};
 
 /// Easiest side quest :)
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Output directory
-    #[arg(short, long)]
-    outdir: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+
+    /// Read the data files found in this directory (laid out like `data/`)
+    /// instead of the embedded ones
+    #[arg(long, global = true)]
+    data_dir: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate the SLEIGH files
+    Build {
+        /// Output directory
+        #[arg(short, long)]
+        outdir: PathBuf,
+
+        /// Factor register operands into shared sub-tables
+        #[arg(short, long)]
+        subtables: bool,
+
+        /// Only generate the families of these names, a trailing `*` matches the
+        /// names starting with the rest (e.g. `Dsp32Alu,LoopSetup*`)
+        #[arg(long, value_delimiter = ',')]
+        only: Vec<String>,
+
+        /// Skip the families of these names, with the same `*` as `--only`.
+        /// Multi still keeps the 32-bit families of its parallel slot
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+    },
+    /// Generate a Markdown reference page per instruction family
+    Doc {
+        /// Output directory
+        #[arg(short, long)]
+        outdir: PathBuf,
+    },
+    /// Export the instruction model as JSON
+    Export {
+        /// Write the JSON to this file instead of the standard output
+        #[arg(short, long)]
+        outfile: Option<PathBuf>,
+    },
+    /// Report the size of every family and estimate the .sla footprint
+    Stats {
+        /// Factor register operands into shared sub-tables first
+        #[arg(short, long)]
+        subtables: bool,
+    },
+    /// Report overlapping, unreachable and missing encodings
+    Check,
+    /// Assemble Blackfin instructions into little-endian machine code
+    Asm {
+        /// Source file, one instruction per line or `;` separated
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Load address of the first instruction
+        #[arg(short, long, default_value = "0", value_parser = parse_addr)]
+        addr: u32,
+
+        /// Write the machine code to this flat binary file
+        #[arg(short, long)]
+        outfile: Option<PathBuf>,
+
+        /// Instructions (e.g. `"R0 = [I0++]; NOP"`)
+        instrs: Vec<String>,
+    },
+    /// Disassemble little-endian machine code
+    Disasm {
+        /// Flat binary file to decode
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+
+        /// Load address of the first byte
+        #[arg(short, long, default_value = "0", value_parser = parse_addr)]
+        addr: u32,
+
+        /// Bytes in hex (e.g. `00 9c 08 e1`)
+        bytes: Vec<String>,
+    },
+}
+
+fn parse_addr(s: &str) -> Result<u32, String> {
+    let res = match s.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+
+    res.map_err(|e| e.to_string())
+}
+
+fn parse_bytes(args: &[String]) -> Result<Vec<u8>, String> {
+    let hex: String = args
+        .iter()
+        .flat_map(|arg| arg.split_whitespace())
+        .map(|chunk| chunk.trim_start_matches("0x"))
+        .collect();
+
+    if hex.len() % 2 != 0 {
+        return Err(format!("odd number of hex digits in `{hex}`"));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|e| e.to_string()))
+        .collect()
 }
 
-fn main() {
+fn main() -> ExitCode {
     let args = Args::parse();
-    let slab = SLASpecBuilder::new();
-    slab.build(&args.outdir);
+    let assets = match args.data_dir {
+        Some(dir) if !dir.is_dir() => {
+            eprintln!("No data directory {}", dir.display());
+            return ExitCode::FAILURE;
+        }
+        Some(dir) => Assets::with_dir(dir),
+        None => Assets::embedded(),
+    };
+    let filter = match &args.command {
+        Command::Build { only, exclude, .. } => FamilyFilter::new(only.clone(), exclude.clone()),
+        _ => FamilyFilter::default(),
+    };
+    // Only the build reports its progress, the other commands print their
+    // result on the standard output
+    let progress: Progress = match &args.command {
+        Command::Build { .. } => Box::new(|msg| println!("{msg}")),
+        _ => Box::new(|_| {}),
+    };
+    let mut slab = match SLASpecBuilder::with_progress(&filter, assets, progress) {
+        Ok(slab) => slab,
+        Err(err) => {
+            eprintln!("Couldn't load the instruction families: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match args.command {
+        Command::Build {
+            outdir, subtables, ..
+        } => {
+            if subtables {
+                slab.factor_subtables();
+            }
+            if let Err(err) = slab.build(&outdir) {
+                eprintln!("Couldn't build the SLEIGH files: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Doc { outdir } => {
+            if let Err(err) = slab.build_docs(&outdir) {
+                eprintln!("Couldn't build the documentation: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Export { outfile } => {
+            let json = slab.export();
+
+            match outfile {
+                Some(path) => {
+                    if let Err(err) = fs::write(&path, json) {
+                        eprintln!("Couldn't write {}: {err}", path.display());
+                        return ExitCode::FAILURE;
+                    }
+                }
+                None => print!("{json}"),
+            }
+        }
+        Command::Stats { subtables } => {
+            if subtables {
+                slab.factor_subtables();
+            }
+
+            let stats = slab.stats();
+            print!("{}", stats.build());
+
+            if !stats.fits() {
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Check => {
+            let reports = slab.check_encodings();
+
+            for report in &reports {
+                println!("{}", report.build());
+            }
+
+            if !reports.iter().all(|report| report.is_sound()) {
+                return ExitCode::FAILURE;
+            }
+        }
+        Command::Asm {
+            file,
+            addr,
+            outfile,
+            instrs,
+        } => {
+            let source = match file {
+                Some(path) => fs::read_to_string(path).map_err(|e| e.to_string()),
+                None => Ok(instrs.join("\n")),
+            };
+
+            let instrs = match source.and_then(|src| slab.assembler().assemble_source(&src, addr)) {
+                Ok(instrs) => instrs,
+                Err(err) => {
+                    eprintln!("Couldn't assemble the source: {err}");
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            for instr in &instrs {
+                println!("{}", instr.build());
+            }
+
+            if let Some(path) = outfile {
+                let bytes: Vec<u8> = instrs.into_iter().flat_map(|instr| instr.bytes).collect();
+                if let Err(err) = fs::write(&path, bytes) {
+                    eprintln!("Couldn't write {}: {err}", path.display());
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        Command::Disasm { file, addr, bytes } => {
+            let data = match file {
+                Some(path) => fs::read(path).map_err(|e| e.to_string()),
+                None => parse_bytes(&bytes),
+            };
+
+            let data = match data {
+                Ok(data) => data,
+                Err(err) => {
+                    eprintln!("Couldn't read the machine code: {err}");
+                    return ExitCode::FAILURE;
+                }
+            };
+
+            for instr in slab.disassembler().disassemble(&data, addr) {
+                println!("{}", instr.build());
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
 }
@@ -0,0 +1,470 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use super::disasm::{Disassembler, Instruction, eval, field_num, field_range, locate, set_field};
+use super::instructions::core::{InstrBuilder, InstrFamilyBuilder, Prefixed};
+use super::instructions::format::{Token, display_tokens};
+use super::instructions::pattern::{Field, FieldType};
+
+#[derive(Debug, Clone)]
+enum Capture {
+    Num(i128),
+    Reg(u16),
+    Text(String),
+}
+
+#[derive(Debug, Default)]
+struct Captures {
+    fields: HashMap<String, Capture>,
+    vars: Vec<(String, i128)>,
+}
+
+struct Entry<'a> {
+    ifam: &'a InstrFamilyBuilder,
+    instr: &'a InstrBuilder,
+    words: usize,
+    tokens: Vec<Token>,
+    literals: Vec<usize>,
+}
+
+impl<'a> Entry<'a> {
+    fn new(ifam: &'a InstrFamilyBuilder, instr: &'a InstrBuilder, width: usize) -> Self {
+        Entry {
+            ifam,
+            instr,
+            words: width / 16,
+            tokens: template(instr),
+            literals: Vec::new(),
+        }
+    }
+
+    /// Cheap test that every literal of the template shows up, in order.
+    /// `present` tells which of the interned literals `text` contains.
+    fn may_match(&self, text: &str, present: &[bool]) -> bool {
+        if !self.literals.iter().all(|id| present[*id]) {
+            return false;
+        }
+
+        let mut rest = text;
+
+        for tok in &self.tokens {
+            if let Token::Literal(lit) = tok {
+                match rest.find(lit.as_str()) {
+                    Some(pos) => rest = &rest[pos + lit.len()..],
+                    None => return false,
+                }
+            }
+        }
+
+        true
+    }
+}
+
+pub struct Assembler<'a> {
+    disasm: Disassembler<'a>,
+    entries: Vec<Entry<'a>>,
+    tables: HashMap<String, Vec<Entry<'a>>>,
+    literals: Vec<String>,
+}
+
+/// Assembly text with whitespace dropped and letters in upper case, so the
+/// spacing and case of the source don't matter.
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+/// The number at the start of `text`, with the length of its text.
+fn parse_num(text: &str) -> Option<(i128, usize)> {
+    let (neg, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let (radix, digits, skip) = match unsigned.strip_prefix("0X") {
+        Some(rest) => (16, rest, 2),
+        None => (10, unsigned, 0),
+    };
+
+    let len = digits.chars().take_while(|c| c.is_digit(radix)).count();
+    if len == 0 {
+        return None;
+    }
+
+    let val = i128::from_str_radix(&digits[..len], radix).ok()?;
+    Some((if neg { -val } else { val }, neg as usize + skip + len))
+}
+
+fn template(instr: &InstrBuilder) -> Vec<Token> {
+    let display = instr.get_display();
+    let tokens = if display.is_empty() {
+        vec![Token::Literal(instr.get_name())]
+    } else {
+        // A display that can't be parsed matches no text
+        display_tokens(&display).unwrap_or_default()
+    };
+
+    tokens
+        .into_iter()
+        .filter_map(|tok| match tok {
+            Token::Literal(lit) => {
+                let lit = normalize(&lit);
+                (!lit.is_empty()).then_some(Token::Literal(lit))
+            }
+            tok => Some(tok),
+        })
+        .collect()
+}
+
+fn eval_vars(
+    instr: &InstrBuilder,
+    fields: &HashMap<String, i128>,
+    addr: u32,
+    next: u32,
+) -> HashMap<String, i128> {
+    let mut vars: HashMap<String, i128> = HashMap::new();
+    vars.insert(String::from("inst_start"), addr as i128);
+    vars.insert(String::from("inst_next"), next as i128);
+
+    for action in instr.get_actions().exprs() {
+        eval(action, fields, &mut vars);
+    }
+
+    vars
+}
+
+impl<'a> Assembler<'a> {
+    /// Families are given with the width of their instructions, the shortest
+    /// encodings are tried first.
+    pub fn new(families: Vec<(usize, &'a [InstrFamilyBuilder])>) -> Self {
+        let disasm = Disassembler::new(families.clone());
+        let families: Vec<(usize, &InstrFamilyBuilder)> = families
+            .into_iter()
+            .flat_map(|(width, ifams)| ifams.iter().map(move |ifam| (width, ifam)))
+            .collect();
+        let mut entries = Vec::new();
+        let mut tables: HashMap<String, Vec<Entry>> = HashMap::new();
+
+        for (width, ifam) in families {
+            for instr in ifam.instrs() {
+                entries.push(Entry::new(ifam, instr, width));
+            }
+
+            for table in ifam.tables() {
+                let table_entries = ifam
+                    .table_instrs(&table)
+                    .into_iter()
+                    .flatten()
+                    .map(|instr| Entry::new(ifam, instr, width));
+                tables.entry(table).or_default().extend(table_entries);
+            }
+        }
+
+        entries.sort_by_key(|entry| entry.words);
+
+        // Templates share few distinct literals, look each up once per text
+        let mut ids: HashMap<String, usize> = HashMap::new();
+        for entry in entries.iter_mut().chain(tables.values_mut().flatten()) {
+            for tok in &entry.tokens {
+                if let Token::Literal(lit) = tok {
+                    let next_id = ids.len();
+                    entry
+                        .literals
+                        .push(*ids.entry(lit.clone()).or_insert(next_id));
+                }
+            }
+        }
+
+        let mut literals = vec![String::new(); ids.len()];
+        for (lit, id) in ids {
+            literals[id] = lit;
+        }
+
+        Assembler {
+            disasm,
+            entries,
+            tables,
+            literals,
+        }
+    }
+
+    fn present(&self, text: &str) -> Vec<bool> {
+        self.literals
+            .iter()
+            .map(|lit| text.contains(lit.as_str()))
+            .collect()
+    }
+
+    /// Matches `text` against the template `tokens` of `instr`, and encodes the
+    /// first match accepted by `check`.
+    #[allow(clippy::too_many_arguments)]
+    fn fit(
+        &self,
+        ifam: &InstrFamilyBuilder,
+        instr: &InstrBuilder,
+        words: usize,
+        tokens: &[Token],
+        text: &str,
+        caps: &mut Captures,
+        addr: u32,
+        check: &dyn Fn(&[u16]) -> bool,
+    ) -> Option<Vec<u16>> {
+        let Some((tok, rest)) = tokens.split_first() else {
+            if !text.is_empty() {
+                return None;
+            }
+            return self
+                .encode(ifam, instr, words, caps, addr)
+                .filter(|encoded| check(encoded));
+        };
+
+        match tok {
+            Token::Literal(lit) => {
+                let text = text.strip_prefix(lit.as_str())?;
+                self.fit(ifam, instr, words, rest, text, caps, addr, check)
+            }
+            Token::Variable(id) => {
+                let (val, len) = parse_num(text)?;
+                caps.vars.push((id.clone(), val));
+                let res = self.fit(ifam, instr, words, rest, &text[len..], caps, addr, check);
+                caps.vars.pop();
+                res
+            }
+            Token::Field(id) => {
+                let (_, field) = locate(instr.pattern(), id)?;
+                let mut candidates: Vec<(Capture, usize)> = Vec::new();
+
+                match field.ftype() {
+                    FieldType::Variable(regset) => {
+                        for (index, reg) in regset.regs().iter().enumerate() {
+                            let name = normalize(reg.trim_matches('"'));
+                            if reg != "_" && text.starts_with(&name) {
+                                candidates.push((Capture::Reg(index as u16), name.len()));
+                            }
+                        }
+                    }
+                    FieldType::Table(_) => {
+                        for end in (1..=text.len()).rev() {
+                            if text.is_char_boundary(end) {
+                                candidates.push((Capture::Text(text[..end].to_string()), end));
+                            }
+                        }
+                    }
+                    _ => {
+                        let (val, len) = parse_num(text)?;
+                        candidates.push((Capture::Num(val), len));
+                    }
+                }
+
+                for (cap, len) in candidates {
+                    caps.fields.insert(id.clone(), cap);
+                    let res = self.fit(ifam, instr, words, rest, &text[len..], caps, addr, check);
+                    if res.is_some() {
+                        caps.fields.remove(id);
+                        return res;
+                    }
+                }
+
+                caps.fields.remove(id);
+                None
+            }
+        }
+    }
+
+    /// Fills the pattern of `instr` from the captured operands. Fields only
+    /// shown through a display variable are solved from the actions, which
+    /// are linear in the fields they read.
+    fn encode(
+        &self,
+        ifam: &InstrFamilyBuilder,
+        instr: &InstrBuilder,
+        words: usize,
+        caps: &Captures,
+        addr: u32,
+    ) -> Option<Vec<u16>> {
+        let pattern = instr.pattern();
+        let mut encoded = vec![0u16; words];
+        let mut unknown: Vec<(usize, Field)> = Vec::new();
+
+        for (wi, word) in pattern.fields().into_iter().enumerate().take(words) {
+            for field in word {
+                match (field.ftype(), caps.fields.get(&field.id())) {
+                    (FieldType::Mask(val), _) => set_field(&mut encoded[wi], &field, val as i128)?,
+                    (FieldType::Variable(_), Some(Capture::Reg(index))) => {
+                        set_field(&mut encoded[wi], &field, *index as i128)?
+                    }
+                    (FieldType::UImmVal | FieldType::SImmVal, Some(Capture::Num(val))) => {
+                        set_field(&mut encoded[wi], &field, *val)?
+                    }
+                    (FieldType::UImmVal | FieldType::SImmVal, None) => unknown.push((wi, field)),
+                    (FieldType::Table(index), Some(Capture::Text(text))) => {
+                        let sub = self.encode_table(ifam, index, text, addr)?;
+                        for (word, sub_word) in encoded[wi..].iter_mut().zip(sub) {
+                            *word |= sub_word;
+                        }
+                    }
+                    (_, Some(_)) => return None,
+                    (_, None) => {}
+                }
+            }
+        }
+
+        let next = addr.wrapping_add(2 * words as u32);
+        let mut fields: HashMap<String, i128> = HashMap::new();
+        for (wi, word) in pattern.fields().iter().enumerate().take(words) {
+            for field in word {
+                fields.insert(field.id(), field_num(field, encoded[wi]));
+            }
+        }
+
+        for (id, target) in &caps.vars {
+            let base = *eval_vars(instr, &fields, addr, next).get(id)?;
+
+            // Contribution of a unit step of each field still free
+            let mut deps: Vec<(usize, Field, i128)> = unknown
+                .iter()
+                .filter_map(|(wi, field)| {
+                    let mut probe = fields.clone();
+                    probe.insert(field.id(), 1);
+                    let coef = eval_vars(instr, &probe, addr, next).get(id)? - base;
+                    (coef != 0).then(|| (*wi, field.clone(), coef))
+                })
+                .collect();
+            deps.sort_by_key(|(_, _, coef)| Reverse(coef.abs()));
+
+            let mut rest = target - base;
+            for (i, (wi, field, coef)) in deps.iter().enumerate() {
+                let min_rest: i128 = deps[i + 1..]
+                    .iter()
+                    .map(|(_, f, c)| {
+                        let (min, max) = field_range(f);
+                        (c * min).min(c * max)
+                    })
+                    .sum();
+                let (min, max) = field_range(field);
+                let val =
+                    ((rest - min_rest).div_euclid(coef.abs()) * coef.signum()).clamp(min, max);
+
+                rest -= coef * val;
+                set_field(&mut encoded[*wi], field, val)?;
+                fields.insert(field.id(), val);
+                unknown.retain(|(_, f)| f.id() != field.id());
+            }
+
+            if rest != 0 {
+                return None;
+            }
+        }
+
+        Some(encoded)
+    }
+
+    /// Encodes `text` with one of the tables built by sub-table `index`.
+    fn encode_table(
+        &self,
+        ifam: &InstrFamilyBuilder,
+        index: usize,
+        text: &str,
+        addr: u32,
+    ) -> Option<Vec<u16>> {
+        for (guard, guard_val, table) in ifam.subtable(index)?.built_tables() {
+            let present = self.present(text);
+            for entry in self.tables.get(&table).into_iter().flatten() {
+                if !entry.may_match(text, &present) {
+                    continue;
+                }
+
+                let Some(mut encoded) = self.fit(
+                    entry.ifam,
+                    entry.instr,
+                    entry.words,
+                    &entry.tokens,
+                    text,
+                    &mut Captures::default(),
+                    addr,
+                    &|_| true,
+                ) else {
+                    continue;
+                };
+
+                for (wi, word) in entry.instr.pattern().fields().iter().enumerate() {
+                    for field in word {
+                        if field.token_name(&entry.ifam.prefix()) == guard {
+                            set_field(&mut encoded[wi], field, guard_val as i128)?;
+                        }
+                    }
+                }
+
+                return Some(encoded);
+            }
+        }
+
+        None
+    }
+
+    /// Encodes a single instruction placed at `addr`. Encodings that would be
+    /// decoded as some other instruction are never produced.
+    pub fn assemble(&self, text: &str, addr: u32) -> Option<Instruction> {
+        let text = normalize(text);
+        let present = self.present(&text);
+
+        for entry in &self.entries {
+            if !entry.may_match(&text, &present) {
+                continue;
+            }
+
+            let next = addr.wrapping_add(2 * entry.words as u32);
+            let renders = |encoded: &[u16]| {
+                self.disasm
+                    .render(entry.ifam, entry.instr, encoded, addr, next)
+                    .is_some_and(|rendered| normalize(&rendered) == text)
+            };
+            let Some(encoded) = self.fit(
+                entry.ifam,
+                entry.instr,
+                entry.words,
+                &entry.tokens,
+                &text,
+                &mut Captures::default(),
+                addr,
+                &renders,
+            ) else {
+                continue;
+            };
+
+            let bytes: Vec<u8> = encoded.iter().flat_map(|w| w.to_le_bytes()).collect();
+            if let Some(instr) = self
+                .disasm
+                .decode(&bytes, addr)
+                .filter(|instr| instr.len() == bytes.len() && normalize(&instr.text) == text)
+            {
+                return Some(instr);
+            }
+        }
+
+        None
+    }
+
+    /// Encodes a listing of `;` or newline separated instructions, `//`
+    /// starts a comment.
+    pub fn assemble_source(&self, source: &str, addr: u32) -> Result<Vec<Instruction>, String> {
+        let mut instrs = Vec::new();
+        let mut iaddr = addr;
+
+        for (line_no, line) in source.lines().enumerate() {
+            let code = line.split("//").next().unwrap_or_default();
+
+            for stmt in code.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+                let instr = self
+                    .assemble(stmt, iaddr)
+                    .ok_or_else(|| format!("line {}: can't assemble `{stmt}`", line_no + 1))?;
+
+                iaddr = iaddr.wrapping_add(instr.len() as u32);
+                instrs.push(instr);
+            }
+        }
+
+        Ok(instrs)
+    }
+}
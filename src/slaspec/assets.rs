@@ -0,0 +1,63 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::error::Error;
+use super::instructions::decl::{self, Definition};
+
+const LOOP_SINC: &str = "loop.sinc.part";
+const FAMILY_DIR: &str = "families";
+
+/// Hardware loop definitions included in the main `.slaspec`
+const EMBEDDED_LOOP_SINC: &str = include_str!("../../data/loop.sinc.part");
+
+/// Declarative family definitions of `data/families` with their file names,
+/// listed by `build.rs`
+const EMBEDDED_FAMILIES: &[(&str, &str)] = &include!(concat!(env!("OUT_DIR"), "/families.rs"));
+
+/// Data files the generator is built from. They are embedded in the tool,
+/// a data directory laid out like `data/` overrides the ones it holds.
+#[derive(Debug, Default, Clone)]
+pub struct Assets {
+    dir: Option<PathBuf>,
+}
+
+impl Assets {
+    pub fn embedded() -> Self {
+        Assets { dir: None }
+    }
+
+    pub fn with_dir(dir: impl Into<PathBuf>) -> Self {
+        Assets {
+            dir: Some(dir.into()),
+        }
+    }
+
+    /// `name` in the data directory, when it holds one
+    fn find(&self, name: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(name))
+            .filter(|path| path.exists())
+    }
+
+    pub fn loop_sinc(&self) -> Result<String, Error> {
+        match self.find(LOOP_SINC) {
+            Some(path) => fs::read_to_string(&path).map_err(|e| Error::io(path, e)),
+            None => Ok(EMBEDDED_LOOP_SINC.to_string()),
+        }
+    }
+
+    /// The declarative families, in the order of their file names
+    pub fn families(&self) -> Result<Vec<Definition>, Error> {
+        match self.find(FAMILY_DIR) {
+            Some(dir) => decl::load_dir(&dir),
+            None => EMBEDDED_FAMILIES
+                .iter()
+                .map(|(name, text)| {
+                    decl::parse_family(text)
+                        .map_err(|err| err.in_file(Path::new(FAMILY_DIR).join(name)))
+                })
+                .collect(),
+        }
+    }
+}
@@ -1,24 +1,161 @@
-use std::fs::{File, copy, create_dir_all};
-use std::io::{Read, Write};
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, create_dir_all};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use itertools::Itertools;
 
 use crate::slaspec::instructions::core::Prefixed;
 
+use super::asm::Assembler;
+use super::assets::Assets;
+use super::coverage::{SpaceReport, check_space};
+use super::cspec::CSpecBuilder;
+use super::disasm::Disassembler;
+use super::doc::DocBuilder;
+use super::error::Error;
+use super::export::ModelExport;
 use super::globals::{ALIGNMENT, ENDIAN, RAM_SAPCE, REGISTER_SPACE};
 use super::instructions::core::InstrFamilyBuilder;
+use super::instructions::decl::Definition;
+use super::interp::Interpreter;
+use super::pspec::PSpecBuilder;
+use super::registers::register_file;
+use super::sleigh::{Document, Item, SpaceDef, SpaceKind};
+use super::stats::SpecStats;
 
 use super::instructions::instr16::*;
 use super::instructions::instr32::*;
 use super::instructions::instr64::*;
 
+/// Selects the instruction families to generate. A pattern matches the family
+/// of that name, ignoring case, and one ending with `*` every family whose name
+/// starts with the rest of it.
+#[derive(Debug, Default, Clone)]
+pub struct FamilyFilter {
+    only: Vec<String>,
+    exclude: Vec<String>,
+}
+
+impl FamilyFilter {
+    pub fn new(only: Vec<String>, exclude: Vec<String>) -> Self {
+        FamilyFilter { only, exclude }
+    }
+
+    fn matches(pattern: &str, name: &str) -> bool {
+        let (pattern, name) = (pattern.to_lowercase(), name.to_lowercase());
+
+        match pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == pattern,
+        }
+    }
+
+    pub fn excludes(&self, name: &str) -> bool {
+        self.exclude.iter().any(|pat| Self::matches(pat, name))
+    }
+
+    pub fn keeps(&self, name: &str) -> bool {
+        (self.only.is_empty() || self.only.iter().any(|pat| Self::matches(pat, name)))
+            && !self.excludes(name)
+    }
+
+    /// Patterns matching none of the `names`
+    pub fn unmatched(&self, names: &[String]) -> Vec<&str> {
+        self.only
+            .iter()
+            .chain(self.exclude.iter())
+            .filter(|pat| !names.iter().any(|name| Self::matches(pat, name)))
+            .map(|pat| pat.as_str())
+            .collect()
+    }
+}
+
+/// Generated files, by path relative to the output directory
+pub type FileTree = BTreeMap<PathBuf, String>;
+
+/// Receives the progress messages of a `SLASpecBuilder`
+pub type Progress = Box<dyn Fn(&str)>;
+
 pub struct SLASpecBuilder {
     ifams_16: Vec<InstrFamilyBuilder>,
     ifams_32: Vec<InstrFamilyBuilder>,
     ifams_64: Vec<InstrFamilyBuilder>,
+    assets: Assets,
+    progress: Progress,
 }
 
 impl SLASpecBuilder {
     pub fn new() -> Self {
+        Self::with_filter(&FamilyFilter::default())
+    }
+
+    /// Only instantiates the families kept by `filter`. Multi also keeps the
+    /// 32-bit families its parallel slot is built from, excluded ones included,
+    /// and notes those in the progress messages.
+    ///
+    /// Panics if an embedded family definition is invalid, `tests/decl.rs`
+    /// loads all of them.
+    pub fn with_filter(filter: &FamilyFilter) -> Self {
+        Self::with_assets(filter, Assets::embedded())
+            .unwrap_or_else(|err| panic!("invalid embedded family: {err}"))
+    }
+
+    /// Like `with_filter`, the declarative families and the hardware loop
+    /// definitions come from `assets`. The progress messages are dropped, see
+    /// `with_progress`.
+    pub fn with_assets(filter: &FamilyFilter, assets: Assets) -> Result<Self, Error> {
+        Self::with_progress(filter, assets, Box::new(|_| {}))
+    }
+
+    /// Inserts each declarative family after the one it names, or at the end
+    /// of the families of its size
+    fn insert_definitions(
+        defs: Vec<Definition>,
+        ifams: [&mut Vec<InstrFamilyBuilder>; 3],
+    ) -> Result<(), Error> {
+        let [ifams_16, ifams_32, ifams_64] = ifams;
+        // Families listed after the same one keep the order of their files
+        let mut followers: HashMap<String, usize> = HashMap::new();
+
+        for def in defs {
+            let ifams = match def.size {
+                16 => &mut *ifams_16,
+                32 => &mut *ifams_32,
+                _ => &mut *ifams_64,
+            };
+
+            match def.after {
+                Some(after) => {
+                    let index = ifams
+                        .iter()
+                        .position(|ifam| ifam.name() == after)
+                        .ok_or_else(|| {
+                            Error::UnknownFamily {
+                                name: after.clone(),
+                            }
+                            .in_family(&def.ifam.name())
+                        })?;
+                    let count = followers.entry(after).or_default();
+                    ifams.insert(index + 1 + *count, def.ifam);
+                    *count += 1;
+                }
+                None => ifams.push(def.ifam),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Like `with_assets`, the progress messages of this builder go to
+    /// `progress`. Fails when a declarative
+    /// family can't be loaded.
+    pub fn with_progress(
+        filter: &FamilyFilter,
+        assets: Assets,
+        progress: Progress,
+    ) -> Result<Self, Error> {
+        let mut names = Vec::new();
         let mut instr_count = 0;
         let mut instr_total = 0;
         let mut ifams_16: Vec<InstrFamilyBuilder> = vec![
@@ -27,7 +164,6 @@ impl SLASpecBuilder {
             progctrl::instr_fam(),
             pushpopreg::instr_fam(),
             cc2dreg::instr_fam(),
-            cachectrl::instr_fam(),
             cc2stat::instr_fam(),
             pushpopmult::instr_fam(),
             ccmv::instr_fam(),
@@ -50,16 +186,6 @@ impl SLASpecBuilder {
             ldstiifp::instr_fam(),
         ];
 
-        println!("Init 16-bits instructions...");
-        for ifam in ifams_16.iter_mut() {
-            ifam.init_tokens_and_vars();
-            println!("\t{:16} -> {:6} intruction(s)", ifam.name(), ifam.len());
-            instr_count += ifam.len();
-        }
-        println!("Count: {} 16-bits instructions\n", instr_count);
-        instr_total += instr_count;
-        instr_count = 0;
-
         let mut ifams_32: Vec<InstrFamilyBuilder> = vec![
             // MAIN_32A
             nop32::instr_fam(),
@@ -78,191 +204,440 @@ impl SLASpecBuilder {
             ldstexcl::instr_fam(),
         ];
 
-        println!("Init 32-bits instructions...");
+        let mut ifams_64: Vec<InstrFamilyBuilder> = vec![
+            ldstabs::instr_fam(),
+            ldimm::instr_fam(),
+            jump32::instr_fam(),
+        ];
+
+        Self::insert_definitions(
+            assets.families()?,
+            [&mut ifams_16, &mut ifams_32, &mut ifams_64],
+        )?;
+
+        names.extend(ifams_16.iter().map(|ifam| ifam.name()));
+        ifams_16.retain(|ifam| filter.keeps(&ifam.name()));
+
+        progress("Init 16-bits instructions...");
+        for ifam in ifams_16.iter_mut() {
+            ifam.init_tokens_and_vars();
+            progress(&format!(
+                "\t{:16} -> {:6} intruction(s)",
+                ifam.name(),
+                ifam.len()
+            ));
+            instr_count += ifam.len();
+        }
+        progress(&format!("Count: {} 16-bits instructions\n", instr_count));
+        instr_total += instr_count;
+        instr_count = 0;
+
+        let keep_multi = filter.keeps("Multi");
+        names.extend(ifams_32.iter().map(|ifam| ifam.name()));
+        for ifam in ifams_32
+            .iter()
+            .filter(|ifam| keep_multi && ifam.is_multi() && filter.excludes(&ifam.name()))
+        {
+            progress(&format!(
+                "NOTE: {} is kept for the parallel slot of Multi",
+                ifam.name()
+            ));
+        }
+        ifams_32.retain(|ifam| filter.keeps(&ifam.name()) || (keep_multi && ifam.is_multi()));
+
+        progress("Init 32-bits instructions...");
         for ifam in ifams_32.iter_mut() {
             ifam.init_tokens_and_vars();
-            println!("\t{:16} -> {:6} intruction(s)", ifam.name(), ifam.len());
+            progress(&format!(
+                "\t{:16} -> {:6} intruction(s)",
+                ifam.name(),
+                ifam.len()
+            ));
             instr_count += ifam.len();
         }
-        println!("Count: {} 32-bits instructions\n", instr_count);
+        progress(&format!("Count: {} 32-bits instructions\n", instr_count));
         instr_total += instr_count;
         instr_count = 0;
 
-        let mut ifams_64: Vec<InstrFamilyBuilder> = vec![
-            ldstabs::instr_fam(),
-            ldimm::instr_fam(),
-            jump32::instr_fam(),
-        ];
+        names.extend(ifams_64.iter().map(|ifam| ifam.name()));
+        names.push(String::from("Multi"));
+        ifams_64.retain(|ifam| filter.keeps(&ifam.name()));
 
-        println!("Init 64-bits instructions...");
+        if keep_multi {
+            ifams_64.push(multi::instr_fam(&ifams_32));
+        }
+
+        progress("Init 64-bits instructions...");
         for ifam in ifams_64.iter_mut() {
             ifam.init_tokens_and_vars();
-            println!("\t{:16} -> {:6} intruction(s)", ifam.name(), ifam.len());
+            progress(&format!(
+                "\t{:16} -> {:6} intruction(s)",
+                ifam.name(),
+                ifam.len()
+            ));
             instr_count += ifam.len();
         }
-        println!("Count: {} 64-bits instructions\n", instr_count);
+        progress(&format!("Count: {} 64-bits instructions\n", instr_count));
         instr_total += instr_count;
 
-        println!("Intruction total: {}", instr_total);
+        progress(&format!("Intruction total: {}", instr_total));
+
+        for pattern in filter.unmatched(&names) {
+            progress(&format!("WARNING: no family matches `{pattern}`"));
+        }
 
-        println!("INIT DONE :)\n");
+        progress("INIT DONE :)\n");
 
-        SLASpecBuilder {
+        Ok(SLASpecBuilder {
             ifams_16,
             ifams_32,
             ifams_64,
+            assets,
+            progress,
+        })
+    }
+
+    fn report(&self, msg: &str) {
+        (self.progress)(msg)
+    }
+
+    fn factor_family_subtables(ifams: &mut [InstrFamilyBuilder], progress: &Progress) -> usize {
+        let mut instr_count = 0;
+
+        for ifam in ifams.iter_mut() {
+            ifam.factor_subtables();
+            progress(&format!(
+                "\t{:16} -> {:6} constructor(s), {:4} sub-table(s)",
+                ifam.name(),
+                ifam.len(),
+                ifam.subtables_len()
+            ));
+            instr_count += ifam.len();
         }
+
+        instr_count
     }
 
-    fn build_main_header() -> String {
-        let mut header = String::new();
+    pub fn factor_subtables(&mut self) {
+        let mut instr_total = 0;
 
-        header += &format!("define endian={};\n", ENDIAN);
-        header += &format!("define alignment={};\n", ALIGNMENT);
-        header += "\n";
-        header += &format!(
-            "define space {} type=ram_space size=4 default;\n",
-            RAM_SAPCE
-        );
-        header += &format!(
-            "define space {} type=register_space size=2;\n",
-            REGISTER_SPACE
-        );
-        header += "\n";
+        self.report("Factoring sub-tables...");
+        instr_total += Self::factor_family_subtables(&mut self.ifams_16, &self.progress);
+        instr_total += Self::factor_family_subtables(&mut self.ifams_32, &self.progress);
+        instr_total += Self::factor_family_subtables(&mut self.ifams_64, &self.progress);
+        self.report(&format!("Constructor total: {}", instr_total));
+
+        self.report("FACTORING DONE :)\n");
+    }
+
+    pub fn check_encodings(&self) -> Vec<SpaceReport> {
+        [16, 32]
+            .into_iter()
+            .map(|width| check_space(width, &self.ifams_16, &self.ifams_32, &self.ifams_64))
+            .collect()
+    }
+
+    pub fn assembler(&self) -> Assembler<'_> {
+        Assembler::new(vec![
+            (16, &self.ifams_16),
+            (32, &self.ifams_32),
+            (64, &self.ifams_64),
+        ])
+    }
+
+    pub fn disassembler(&self) -> Disassembler<'_> {
+        Disassembler::new(vec![
+            (64, &self.ifams_64),
+            (32, &self.ifams_32),
+            (16, &self.ifams_16),
+        ])
+    }
+
+    pub fn interpreter(&self) -> Interpreter<'_> {
+        Interpreter::new(vec![
+            (64, &self.ifams_64),
+            (32, &self.ifams_32),
+            (16, &self.ifams_16),
+        ])
+    }
+
+    /// Every family, the 16-bit ones first
+    pub fn families(&self) -> Vec<&InstrFamilyBuilder> {
+        self.ifams_16
+            .iter()
+            .chain(self.ifams_32.iter())
+            .chain(self.ifams_64.iter())
+            .collect()
+    }
+
+    /// The instruction model of every family as JSON
+    pub fn export(&self) -> String {
+        ModelExport::new(self.families()).build()
+    }
+
+    pub fn stats(&self) -> SpecStats {
+        SpecStats::new(&self.families())
+    }
+
+    /// Writes the Markdown reference pages of every family into `path`
+    pub fn build_docs(&self, path: &Path) -> Result<(), Error> {
+        Self::create_dir(path)?;
+
+        for (name, page) in DocBuilder::new(self.families()).build() {
+            let file = path.join(name);
+
+            if let Some(dir) = file.parent() {
+                Self::create_dir(dir)?;
+            }
+            Self::write_file(&file, page.as_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    fn build_main_header() -> Document {
+        let space = |name: &str, kind, size, default| {
+            Item::Space(SpaceDef {
+                name: name.to_string(),
+                kind,
+                size,
+                default,
+            })
+        };
+        let mut header = Document::new();
+
+        header.items.extend([
+            Item::Define {
+                key: String::from("endian"),
+                value: ENDIAN.to_string(),
+            },
+            Item::Define {
+                key: String::from("alignment"),
+                value: ALIGNMENT.to_string(),
+            },
+            Item::Blank,
+            space(RAM_SAPCE, SpaceKind::Ram, 4, true),
+            space(REGISTER_SPACE, SpaceKind::Register, 2, false),
+            Item::Blank,
+        ]);
+
+        for include in ["includes/registers.sinc", "includes/macros.sinc"] {
+            header
+                .items
+                .extend([Item::Include(include.to_string()), Item::Blank]);
+        }
 
         header
     }
 
-    fn hwloop_sinc() -> Vec<u8> {
-        let mut file = File::open("data/loop.sinc.part").unwrap();
-        let mut data = vec![];
+    fn create_file(path: &Path) -> Result<File, Error> {
+        File::create(path).map_err(|e| Error::io(path, e))
+    }
 
-        file.read_to_end(&mut data).unwrap();
-        data
+    fn create_dir(path: &Path) -> Result<(), Error> {
+        create_dir_all(path).map_err(|e| Error::io(path, e))
     }
 
-    fn build_main_file(path: &Path) {
-        let mut file = File::create(path).unwrap();
-        file.write_all(Self::build_main_header().as_bytes())
-            .unwrap();
+    fn write_to(file: &mut File, path: &Path, data: &[u8]) -> Result<(), Error> {
+        file.write_all(data).map_err(|e| Error::io(path, e))
+    }
 
-        file.write_all("@include \"includes/registers.sinc\"\n\n".as_bytes())
-            .unwrap();
+    fn write_file(path: &Path, data: &[u8]) -> Result<(), Error> {
+        Self::write_to(&mut Self::create_file(path)?, path, data)
+    }
+
+    fn build_main_file(&self) -> Result<String, Error> {
+        let mut data = Self::build_main_header().build();
 
-        file.write_all(&Self::hwloop_sinc()).unwrap();
+        data += &self.assets.loop_sinc()?;
+        data += "with: phase=1 {\n@include \"includes/instructions.sinc\"\n}\n";
 
-        file.write_all("with: phase=1 {\n@include \"includes/instructions.sinc\"\n}\n".as_bytes())
-            .unwrap();
+        Ok(data)
     }
 
-    fn instr_file_inc(dir: &str, file: &str) -> String {
-        format!("@include \"{}/{}\"\n", dir, file)
+    fn build_macros(&self) -> String {
+        let mut macros = Document::new();
+        macros.items.extend([
+            Item::Comment(String::from("# Shared p-code ##")),
+            Item::Blank,
+        ]);
+
+        for mac in self
+            .ifams_16
+            .iter()
+            .chain(&self.ifams_32)
+            .chain(&self.ifams_64)
+            .flat_map(|ifam| ifam.macros())
+            .sorted_by(|a, b| a.name.cmp(&b.name))
+            .dedup_by(|a, b| a.name == b.name)
+        {
+            macros.items.extend([Item::Macro(mac), Item::Blank]);
+        }
+
+        macros.build()
+    }
+
+    fn instr_file_inc(dir: &str, file: &str) -> Item {
+        Item::Include(format!("{}/{}", dir, file))
     }
 
     fn create_family_file(
         ifam: &InstrFamilyBuilder,
         instr_str: &str,
         instr_dir: &Path,
-        inc_file: &mut File,
-    ) {
+        inc_file: &mut Document,
+        files: &mut FileTree,
+    ) -> Result<(), Error> {
         let filename = format!("{}.sinc", ifam.name());
-        let instr_path = instr_dir.join(&filename);
-        inc_file
-            .write_all(Self::instr_file_inc(instr_str, &filename).as_bytes())
-            .unwrap();
-        let mut instr_file = File::create(instr_path).unwrap();
+        inc_file.push(Self::instr_file_inc(instr_str, &filename));
 
-        instr_file.write_all(ifam.build().as_bytes()).unwrap();
+        files.insert(instr_dir.join(&filename), ifam.build()?);
+        Ok(())
     }
 
     fn create_family_dir(
         ifam: &InstrFamilyBuilder,
         instr_str: &str,
         instr_dir: &Path,
-        inc_file: &mut File,
-    ) {
+        inc_file: &mut Document,
+        files: &mut FileTree,
+    ) -> Result<(), Error> {
         let instr_dir_path = instr_dir.join(ifam.name());
         let instr_fname = format!("{}.sinc", ifam.name());
-        let instr_inc_path = instr_dir.join(&instr_fname);
-
-        inc_file
-            .write_all(Self::instr_file_inc(instr_str, &instr_fname).as_bytes())
-            .unwrap();
-        let mut instr_file = File::create(instr_inc_path).unwrap();
+        let id_instrs = ifam.build_id_instrs()?;
 
-        instr_file.write_all(ifam.build_head().as_bytes()).unwrap();
-        create_dir_all(&instr_dir_path).unwrap();
+        inc_file.push(Self::instr_file_inc(instr_str, &instr_fname));
+        let mut instr_file = ifam.build_head();
 
-        for (id, instr) in ifam.build_id_instrs() {
+        for (id, instr) in id_instrs {
             let instr_id_fname = format!("{}-{}.sinc", ifam.prefix(), id);
-            let instr_id_path = instr_dir_path.join(&instr_id_fname);
 
-            instr_file
-                .write_all(Self::instr_file_inc(&ifam.name(), &instr_id_fname).as_bytes())
-                .unwrap();
-            let mut instr_id_file = File::create(instr_id_path).unwrap();
-
-            instr_id_file.write_all(instr.as_bytes()).unwrap();
+            instr_file.push(Self::instr_file_inc(&ifam.name(), &instr_id_fname));
+            files.insert(instr_dir_path.join(&instr_id_fname), instr.build());
         }
+
+        files.insert(instr_dir.join(&instr_fname), instr_file.build());
+        Ok(())
     }
 
     fn build_instrs(
         instrs: &Vec<InstrFamilyBuilder>,
         inc_dir: &Path,
         instr_str: &str,
-        inc_file: &mut File,
-    ) {
+        inc_file: &mut Document,
+        files: &mut FileTree,
+    ) -> Result<(), Error> {
         let instr_dir = inc_dir.join(instr_str);
 
-        create_dir_all(&instr_dir).unwrap();
-
         for ifam in instrs {
             if ifam.sub_fam() == 1 {
-                Self::create_family_file(ifam, instr_str, &instr_dir, inc_file);
+                Self::create_family_file(ifam, instr_str, &instr_dir, inc_file, files)?;
             } else {
-                Self::create_family_dir(ifam, instr_str, &instr_dir, inc_file);
+                Self::create_family_dir(ifam, instr_str, &instr_dir, inc_file, files)?;
             }
         }
-    }
-
-    pub fn build(&self, path: &Path) {
-        if create_dir_all(path).is_err() {
-            panic!("Output directory cannot be created")
-        }
-
-        println!("Building blackfinplus.slaspec...");
-        Self::build_main_file(&path.join("blackfinplus.slaspec"));
-        let inc_dir = path.join("includes");
-        println!("DONE!\n");
-
-        create_dir_all(&inc_dir).unwrap();
 
-        println!("Copying registers.sinc...");
-        copy("data/registers.sinc", inc_dir.join("registers.sinc")).unwrap();
-        println!("DONE!\n");
+        Ok(())
+    }
 
-        let mut instr_inc_file = File::create(inc_dir.join("instructions.sinc")).unwrap();
+    /// Generates the SLEIGH files without writing them
+    pub fn generate(&self) -> Result<FileTree, Error> {
+        let mut files = FileTree::new();
+        let inc_dir = Path::new("includes");
 
-        println!("Building 16-bits instructions...");
-        instr_inc_file
-            .write_all("## 16-bits instructions ##\n\n".as_bytes())
-            .unwrap();
+        self.report("Building blackfinplus.slaspec...");
+        files.insert(
+            PathBuf::from("blackfinplus.slaspec"),
+            self.build_main_file()?,
+        );
+        self.report("DONE!\n");
 
-        Self::build_instrs(&self.ifams_16, &inc_dir, "instr16", &mut instr_inc_file);
+        self.report("Building blackfinplus.pspec...");
+        files.insert(
+            PathBuf::from("blackfinplus.pspec"),
+            PSpecBuilder::new().build(),
+        );
+        self.report("DONE!\n");
 
-        println!("Building 32-bits instructions...");
-        instr_inc_file
-            .write_all("\n## 32-bits instructions ##\n\n".as_bytes())
-            .unwrap();
+        self.report("Building blackfinplus.cspec...");
+        files.insert(
+            PathBuf::from("blackfinplus.cspec"),
+            CSpecBuilder::new().build(),
+        );
+        self.report("DONE!\n");
+
+        self.report("Building registers.sinc...");
+        files.insert(inc_dir.join("registers.sinc"), register_file().build());
+        self.report("DONE!\n");
+
+        self.report("Building macros.sinc...");
+        files.insert(inc_dir.join("macros.sinc"), self.build_macros());
+        self.report("DONE!\n");
+
+        let mut instr_inc_file = Document::new();
+
+        self.report("Building 16-bits instructions...");
+        instr_inc_file.items.extend([
+            Item::Comment(String::from("# 16-bits instructions ##")),
+            Item::Blank,
+        ]);
+
+        Self::build_instrs(
+            &self.ifams_16,
+            inc_dir,
+            "instr16",
+            &mut instr_inc_file,
+            &mut files,
+        )?;
+
+        self.report("Building 32-bits instructions...");
+        instr_inc_file.items.extend([
+            Item::Blank,
+            Item::Comment(String::from("# 32-bits instructions ##")),
+            Item::Blank,
+        ]);
+
+        Self::build_instrs(
+            &self.ifams_32,
+            inc_dir,
+            "instr32",
+            &mut instr_inc_file,
+            &mut files,
+        )?;
+
+        self.report("Building 64-bits instructions...");
+        instr_inc_file.items.extend([
+            Item::Blank,
+            Item::Comment(String::from("# 64-bits instructions ##")),
+            Item::Blank,
+        ]);
+
+        Self::build_instrs(
+            &self.ifams_64,
+            inc_dir,
+            "instr64",
+            &mut instr_inc_file,
+            &mut files,
+        )?;
+
+        files.insert(inc_dir.join("instructions.sinc"), instr_inc_file.build());
+
+        Ok(files)
+    }
 
-        Self::build_instrs(&self.ifams_32, &inc_dir, "instr32", &mut instr_inc_file);
+    /// Generates the SLEIGH files and writes them into `path`
+    pub fn build(&self, path: &Path) -> Result<(), Error> {
+        Self::create_dir(path)?;
 
-        println!("Building 64-bits instructions...");
-        instr_inc_file
-            .write_all("\n## 64-bits instructions ##\n\n".as_bytes())
-            .unwrap();
+        for (name, data) in self.generate()? {
+            let file_path = path.join(name);
+            if let Some(dir) = file_path.parent() {
+                Self::create_dir(dir)?;
+            }
+            Self::write_file(&file_path, data.as_bytes())?;
+        }
+        self.report("ALL DONE :3");
 
-        Self::build_instrs(&self.ifams_64, &inc_dir, "instr64", &mut instr_inc_file);
-        println!("ALL DONE :3");
+        Ok(())
     }
 }
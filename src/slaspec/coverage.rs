@@ -0,0 +1,504 @@
+use std::collections::HashSet;
+
+use itertools::Itertools;
+
+use super::instructions::core::{InstrBuilder, InstrFamilyBuilder};
+use super::instructions::pattern::{Field, FieldType, Pattern};
+
+// The two upper bits of the first word are set for instructions longer than 16 bits
+const LONG_PREFIX: u64 = 0b11;
+
+/// Set of encodings whose `mask` bits equal the ones of `val`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Cube {
+    mask: u64,
+    val: u64,
+}
+
+impl Cube {
+    fn intersects(&self, other: &Cube) -> bool {
+        (self.val ^ other.val) & self.mask & other.mask == 0
+    }
+
+    fn contains(&self, other: &Cube) -> bool {
+        self.mask & !other.mask == 0 && (self.val ^ other.val) & self.mask == 0
+    }
+
+    fn fix(&self, bit: usize, set: bool) -> Cube {
+        Cube {
+            mask: self.mask | 1 << bit,
+            val: if set {
+                self.val | 1 << bit
+            } else {
+                self.val & !(1 << bit)
+            },
+        }
+    }
+
+    /// Disjoint cubes covering the encodings of `self` that are not in `other`.
+    fn subtract(&self, other: &Cube) -> Vec<Cube> {
+        if !self.intersects(other) {
+            return vec![*self];
+        }
+
+        let mut pieces = Vec::new();
+        let mut rest = *self;
+        let free = other.mask & !self.mask;
+
+        for bit in (0..64).filter(|bit| free & 1 << bit != 0) {
+            let set = other.val & 1 << bit != 0;
+            pieces.push(rest.fix(bit, !set));
+            rest = rest.fix(bit, set);
+        }
+
+        pieces
+    }
+
+    fn size_log2(&self, width: usize) -> usize {
+        width - (self.mask.count_ones() as usize)
+    }
+
+    fn to_string(self, width: usize) -> String {
+        (0..width)
+            .rev()
+            .map(|bit| {
+                if self.mask & 1 << bit == 0 {
+                    'x'
+                } else if self.val & 1 << bit != 0 {
+                    '1'
+                } else {
+                    '0'
+                }
+            })
+            .chunks(4)
+            .into_iter()
+            .map(|nibble| nibble.collect::<String>())
+            .join("_")
+    }
+}
+
+fn subtract_all(cubes: Vec<Cube>, others: &[Cube]) -> Vec<Cube> {
+    others.iter().fold(cubes, |rest, other| {
+        rest.iter().flat_map(|cube| cube.subtract(other)).collect()
+    })
+}
+
+/// Merges single values into the cubes of a `len` bits field.
+fn value_cubes(vals: &[u64], len: usize) -> Vec<Cube> {
+    let mut cubes: Vec<Cube> = vals
+        .iter()
+        .map(|val| Cube {
+            mask: (1 << len) - 1,
+            val: *val,
+        })
+        .collect();
+
+    loop {
+        let mut merged = None;
+
+        'search: for (i, a) in cubes.iter().enumerate() {
+            for (j, b) in cubes.iter().enumerate().skip(i + 1) {
+                let diff = a.val ^ b.val;
+                if a.mask == b.mask && diff.count_ones() == 1 {
+                    merged = Some((
+                        i,
+                        j,
+                        Cube {
+                            mask: a.mask & !diff,
+                            val: a.val & !diff,
+                        },
+                    ));
+                    break 'search;
+                }
+            }
+        }
+
+        match merged {
+            Some((i, j, cube)) => {
+                cubes.remove(j);
+                cubes[i] = cube;
+            }
+            None => return cubes,
+        }
+    }
+}
+
+/// Values of a field the decoder accepts, `None` when every value is valid.
+fn field_values(field: &Field) -> Option<Vec<u64>> {
+    match field.ftype() {
+        FieldType::Mask(val) => Some(vec![val as u64]),
+        FieldType::Variable(regset) => {
+            let regs = regset.regs();
+            let vals: Vec<u64> = (0..1 << field.len())
+                .filter(|i| regs.get(*i).is_some_and(|reg| reg != "_"))
+                .map(|i| i as u64)
+                .collect();
+
+            if vals.len() == 1 << field.len() {
+                None
+            } else {
+                Some(vals)
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Encodings of the first `words` words of a pattern, `None` when the pattern
+/// refers to a sub-table.
+fn pattern_cubes(pattern: &Pattern, words: usize) -> Option<Vec<Cube>> {
+    let mut cubes = vec![Cube { mask: 0, val: 0 }];
+
+    for (wi, word) in pattern.fields().iter().enumerate().take(words) {
+        let shift = (words - 1 - wi) * 16;
+
+        for field in word {
+            if field.is_table() {
+                return None;
+            }
+
+            if let Some(vals) = field_values(field) {
+                let offset = shift + field.start();
+                let field_cubes = value_cubes(&vals, field.len());
+
+                cubes = cubes
+                    .iter()
+                    .cartesian_product(field_cubes.iter())
+                    .map(|(cube, fcube)| Cube {
+                        mask: cube.mask | fcube.mask << offset,
+                        val: cube.val | fcube.val << offset,
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    Some(cubes)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Length {
+    Shorter,
+    Same,
+    Longer,
+}
+
+struct Constructor {
+    label: String,
+    length: Length,
+    cubes: Vec<Cube>,
+}
+
+impl Constructor {
+    fn label(ifam: &InstrFamilyBuilder, index: usize, instr: &InstrBuilder) -> String {
+        format!(
+            "{}#{index} {} \"{}\"",
+            ifam.name(),
+            instr.get_name(),
+            instr.get_display()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverlapKind {
+    /// Neither constructor is a special case of the other
+    Ambiguous,
+    /// Both constructors match exactly the same encodings
+    Duplicate,
+    /// The constructor matches the prefix of a longer instruction
+    Length,
+}
+
+#[derive(Debug, Clone)]
+pub struct Overlap {
+    pub kind: OverlapKind,
+    pub first: String,
+    pub second: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpaceReport {
+    pub width: usize,
+    pub constructors: usize,
+    pub skipped: usize,
+    pub specializations: usize,
+    pub overlaps: Vec<Overlap>,
+    pub unreachable: Vec<String>,
+    pub uncovered: Vec<String>,
+    pub uncovered_encodings: u64,
+}
+
+struct Space {
+    width: usize,
+    constructors: Vec<Constructor>,
+    skipped: usize,
+    pairs: HashSet<(usize, usize)>,
+    uncovered: Vec<Cube>,
+}
+
+impl Space {
+    fn new(width: usize) -> Self {
+        Space {
+            width,
+            constructors: Vec::new(),
+            skipped: 0,
+            pairs: HashSet::new(),
+            uncovered: Vec::new(),
+        }
+    }
+
+    fn add_families(&mut self, ifams: &[InstrFamilyBuilder], ifam_width: usize) {
+        let length = match ifam_width.cmp(&self.width) {
+            std::cmp::Ordering::Less => Length::Shorter,
+            std::cmp::Ordering::Equal => Length::Same,
+            std::cmp::Ordering::Greater => Length::Longer,
+        };
+        let words = self.width.min(ifam_width) / 16;
+        let extend = self.width.saturating_sub(ifam_width);
+
+        for ifam in ifams {
+            for (index, instr) in ifam.instrs().into_iter().enumerate() {
+                match pattern_cubes(instr.pattern(), words) {
+                    Some(cubes) => self.constructors.push(Constructor {
+                        label: Constructor::label(ifam, index, instr),
+                        length,
+                        cubes: cubes
+                            .into_iter()
+                            .map(|cube| Cube {
+                                mask: cube.mask << extend,
+                                val: cube.val << extend,
+                            })
+                            .collect(),
+                    }),
+                    None if length == Length::Same => self.skipped += 1,
+                    None => {}
+                }
+            }
+        }
+    }
+
+    fn domain(&self) -> Cube {
+        if self.width == 16 {
+            return Cube { mask: 0, val: 0 };
+        }
+
+        Cube {
+            mask: LONG_PREFIX << (self.width - 2),
+            val: LONG_PREFIX << (self.width - 2),
+        }
+    }
+
+    fn record(&mut self, a: usize, b: usize) {
+        let (la, lb) = (self.constructors[a].length, self.constructors[b].length);
+
+        // Shorter instructions are checked in their own space
+        if a == b || la != Length::Same && lb != Length::Same {
+            return;
+        }
+        if la == Length::Shorter || lb == Length::Shorter {
+            return;
+        }
+
+        self.pairs.insert((a.min(b), a.max(b)));
+    }
+
+    fn walk(&mut self, region: Cube, items: Vec<(usize, Cube)>) {
+        if items.is_empty() {
+            self.uncovered.push(region);
+            return;
+        }
+
+        let covering: Vec<usize> = items
+            .iter()
+            .filter(|(_, cube)| cube.contains(&region))
+            .map(|(owner, _)| *owner)
+            .collect();
+
+        if !covering.is_empty() {
+            for cover in &covering {
+                for (owner, _) in &items {
+                    self.record(*cover, *owner);
+                }
+            }
+            return;
+        }
+
+        // Split on the bit most items decide on
+        let bit = (0..self.width)
+            .rev()
+            .filter(|bit| region.mask & 1 << bit == 0)
+            .max_by_key(|bit| {
+                items
+                    .iter()
+                    .filter(|(_, cube)| cube.mask & 1 << bit != 0)
+                    .count()
+            })
+            .unwrap();
+
+        for set in [false, true] {
+            let sub = region.fix(bit, set);
+            let sub_items = items
+                .iter()
+                .filter(|(_, cube)| cube.intersects(&sub))
+                .copied()
+                .collect();
+            self.walk(sub, sub_items);
+        }
+    }
+
+    fn is_subset(&self, a: usize, b: usize) -> bool {
+        subtract_all(
+            self.constructors[a].cubes.clone(),
+            &self.constructors[b].cubes,
+        )
+        .is_empty()
+    }
+
+    fn report(mut self) -> SpaceReport {
+        let domain = self.domain();
+        let items = self
+            .constructors
+            .iter()
+            .enumerate()
+            .flat_map(|(owner, c)| c.cubes.iter().map(move |cube| (owner, *cube)))
+            .filter(|(_, cube)| cube.intersects(&domain))
+            .collect();
+        self.walk(domain, items);
+
+        let mut overlaps = Vec::new();
+        let mut specializations = 0;
+        let mut winners: Vec<Vec<usize>> = vec![Vec::new(); self.constructors.len()];
+
+        for (a, b) in self.pairs.iter().copied().sorted() {
+            let (ca, cb) = (&self.constructors[a], &self.constructors[b]);
+
+            let kind = if ca.length != cb.length {
+                Some(OverlapKind::Length)
+            } else {
+                match (self.is_subset(a, b), self.is_subset(b, a)) {
+                    (true, true) => {
+                        winners[b].push(a);
+                        Some(OverlapKind::Duplicate)
+                    }
+                    (true, false) => {
+                        winners[b].push(a);
+                        specializations += 1;
+                        None
+                    }
+                    (false, true) => {
+                        winners[a].push(b);
+                        specializations += 1;
+                        None
+                    }
+                    (false, false) => Some(OverlapKind::Ambiguous),
+                }
+            };
+
+            if let Some(kind) = kind {
+                overlaps.push(Overlap {
+                    kind,
+                    first: ca.label.clone(),
+                    second: cb.label.clone(),
+                });
+            }
+        }
+
+        let unreachable = winners
+            .iter()
+            .enumerate()
+            .filter(|(_, wins)| !wins.is_empty())
+            .filter(|(owner, wins)| {
+                let others: Vec<Cube> = wins
+                    .iter()
+                    .flat_map(|w| self.constructors[*w].cubes.clone())
+                    .collect();
+                subtract_all(self.constructors[*owner].cubes.clone(), &others).is_empty()
+            })
+            .map(|(owner, _)| self.constructors[owner].label.clone())
+            .collect();
+
+        let uncovered_cubes: Vec<Cube> = self
+            .uncovered
+            .iter()
+            .copied()
+            .sorted_by_key(|cube| (cube.val, cube.mask))
+            .collect();
+
+        SpaceReport {
+            width: self.width,
+            constructors: self
+                .constructors
+                .iter()
+                .filter(|c| c.length == Length::Same)
+                .count(),
+            skipped: self.skipped,
+            specializations,
+            overlaps,
+            unreachable,
+            uncovered_encodings: uncovered_cubes
+                .iter()
+                .map(|cube| 1u64 << cube.size_log2(self.width))
+                .sum(),
+            uncovered: uncovered_cubes
+                .iter()
+                .map(|cube| cube.to_string(self.width))
+                .collect(),
+        }
+    }
+}
+
+impl SpaceReport {
+    pub fn is_sound(&self) -> bool {
+        self.overlaps.is_empty() && self.unreachable.is_empty()
+    }
+
+    pub fn build(&self) -> String {
+        let mut report = String::new();
+
+        report += &format!("## {}-bits encoding space ##\n", self.width);
+        report += &format!(
+            "{} constructor(s), {} skipped (sub-table operands), {} specialization(s)\n",
+            self.constructors, self.skipped, self.specializations
+        );
+
+        report += &format!("\nOverlaps: {}\n", self.overlaps.len());
+        for overlap in &self.overlaps {
+            report += &format!(
+                "\t{:?}: {} <-> {}\n",
+                overlap.kind, overlap.first, overlap.second
+            );
+        }
+
+        report += &format!("\nUnreachable: {}\n", self.unreachable.len());
+        for label in &self.unreachable {
+            report += &format!("\t{label}\n");
+        }
+
+        report += &format!(
+            "\nUncovered: {} range(s), {} encoding(s)\n",
+            self.uncovered.len(),
+            self.uncovered_encodings
+        );
+        for range in &self.uncovered {
+            report += &format!("\t{range}\n");
+        }
+
+        report
+    }
+}
+
+pub fn check_space(
+    width: usize,
+    ifams_16: &[InstrFamilyBuilder],
+    ifams_32: &[InstrFamilyBuilder],
+    ifams_64: &[InstrFamilyBuilder],
+) -> SpaceReport {
+    let mut space = Space::new(width);
+
+    space.add_families(ifams_16, 16);
+    space.add_families(ifams_32, 32);
+    space.add_families(ifams_64, 64);
+
+    space.report()
+}
@@ -0,0 +1,162 @@
+use super::globals::DEFAULT_MEM;
+use super::instructions::pattern::RegisterSet;
+use super::registers::{RegGroup, register_file};
+
+const ARG_REG_COUNT: usize = 3;
+// Callers reserve stack space for the register arguments
+const ARG_STACK_OFFSET: usize = 4 * ARG_REG_COUNT;
+
+pub struct CSpecBuilder {
+    dregs: Vec<String>,
+}
+
+impl CSpecBuilder {
+    pub fn new() -> Self {
+        CSpecBuilder {
+            dregs: RegisterSet::DReg.regs(),
+        }
+    }
+
+    fn arg_regs(&self) -> Vec<String> {
+        self.dregs[..ARG_REG_COUNT].to_vec()
+    }
+
+    fn return_regs(&self) -> (String, String) {
+        (
+            self.dregs[0].clone(),
+            RegisterSet::DRegPair.regs()[0].clone(),
+        )
+    }
+
+    fn callee_saved(&self) -> Vec<String> {
+        let mut regs = self.dregs[4..].to_vec();
+        regs.append(&mut register_file().names(&["P3", "P4", "P5", "FP", "SP"]));
+        regs
+    }
+
+    fn caller_saved(&self) -> Vec<String> {
+        let mut regs = self.dregs[..4].to_vec();
+        regs.append(&mut register_file().names(&["P0", "P1", "P2"]));
+        for regset in [
+            RegisterSet::IReg,
+            RegisterSet::MReg,
+            RegisterSet::BReg,
+            RegisterSet::LReg,
+        ] {
+            regs.append(&mut regset.regs());
+        }
+        regs.append(&mut register_file().group(RegGroup::Accumulator));
+        regs.append(&mut register_file().group(RegGroup::Status));
+        regs.append(&mut RegisterSet::LC.regs());
+        regs
+    }
+
+    fn build_data_organization() -> String {
+        let mut data_str = String::new();
+
+        data_str += "  <data_organization>\n";
+        for (elem, val) in [
+            ("absolute_max_alignment", 0),
+            ("machine_alignment", 4),
+            ("default_alignment", 1),
+            ("default_pointer_alignment", 4),
+            ("pointer_size", 4),
+            ("char_size", 1),
+            ("short_size", 2),
+            ("integer_size", 4),
+            ("long_size", 4),
+            ("long_long_size", 8),
+            ("float_size", 4),
+            ("double_size", 8),
+            ("long_double_size", 8),
+        ] {
+            data_str += &format!("    <{} value=\"{}\"/>\n", elem, val);
+        }
+        data_str += "  </data_organization>\n";
+
+        data_str
+    }
+
+    fn build_reg_list(tag: &str, regs: &[String]) -> String {
+        let mut list_str = String::new();
+
+        list_str += &format!("      <{}>\n", tag);
+        for reg in regs {
+            list_str += &format!("        <register name=\"{}\"/>\n", reg);
+        }
+        list_str += &format!("      </{}>\n", tag);
+
+        list_str
+    }
+
+    fn build_prototype(&self) -> String {
+        let mut proto_str = String::new();
+        let (ret, ret_pair) = self.return_regs();
+
+        proto_str += "  <default_proto>\n";
+        proto_str += "    <prototype name=\"__stdcall\" extrapop=\"0\" stackshift=\"0\">\n";
+
+        proto_str += "      <input>\n";
+        for reg in self.arg_regs() {
+            proto_str += &format!(
+                "        <pentry minsize=\"1\" maxsize=\"4\">\n          <register name=\"{}\"/>\n        </pentry>\n",
+                reg
+            );
+        }
+        proto_str += &format!(
+            "        <pentry minsize=\"1\" maxsize=\"500\" align=\"4\">\n          <addr offset=\"{}\" space=\"stack\"/>\n        </pentry>\n",
+            ARG_STACK_OFFSET
+        );
+        proto_str += "      </input>\n";
+
+        proto_str += "      <output>\n";
+        proto_str += &format!(
+            "        <pentry minsize=\"1\" maxsize=\"4\">\n          <register name=\"{}\"/>\n        </pentry>\n",
+            ret
+        );
+        proto_str += &format!(
+            "        <pentry minsize=\"5\" maxsize=\"8\">\n          <register name=\"{}\"/>\n        </pentry>\n",
+            ret_pair
+        );
+        proto_str += "      </output>\n";
+
+        proto_str += &Self::build_reg_list("unaffected", &self.callee_saved());
+        proto_str += &Self::build_reg_list("killedbycall", &self.caller_saved());
+
+        proto_str += "    </prototype>\n";
+        proto_str += "  </default_proto>\n";
+
+        proto_str
+    }
+
+    pub fn build(&self) -> String {
+        let mut cspec = String::new();
+
+        cspec += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\n";
+        cspec += "<compiler_spec>\n";
+        cspec += &Self::build_data_organization();
+        cspec += &format!(
+            "  <global>\n    <range space=\"{}\"/>\n  </global>\n",
+            DEFAULT_MEM
+        );
+        cspec += &format!(
+            "  <stackpointer register=\"{}\" space=\"{}\" growth=\"negative\"/>\n",
+            register_file().names(&["SP"])[0],
+            DEFAULT_MEM
+        );
+        cspec += &format!(
+            "  <returnaddress>\n    <register name=\"{}\"/>\n  </returnaddress>\n",
+            register_file().names(&["RETS"])[0]
+        );
+        cspec += &self.build_prototype();
+        cspec += "</compiler_spec>\n";
+
+        cspec
+    }
+}
+
+impl Default for CSpecBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,510 @@
+use std::collections::HashMap;
+
+use super::instructions::core::{InstrBuilder, InstrFamilyBuilder, Prefixed};
+use super::instructions::expr::{Expr, Op};
+use super::instructions::format::display_fill;
+use super::instructions::pattern::{Field, FieldType, Pattern};
+
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub addr: u32,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+impl Instruction {
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn build(&self) -> String {
+        format!(
+            "{:08x}:  {:24}{}",
+            self.addr,
+            self.bytes
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect::<Vec<String>>()
+                .join(" "),
+            self.text
+        )
+    }
+}
+
+struct Entry<'a> {
+    ifam: &'a InstrFamilyBuilder,
+    instr: &'a InstrBuilder,
+    words: usize,
+    mask: u64,
+    val: u64,
+}
+
+pub struct Disassembler<'a> {
+    families: Vec<(usize, &'a InstrFamilyBuilder)>,
+    entries: Vec<Entry<'a>>,
+}
+
+pub(super) fn field_value(field: &Field, word: u16) -> u16 {
+    let mask = ((1u32 << field.len()) - 1) as u16;
+    (word >> field.start()) & mask
+}
+
+pub(super) fn field_num(field: &Field, word: u16) -> i128 {
+    let val = field_value(field, word) as i128;
+
+    if field.is_signed() && val >> (field.len() - 1) != 0 {
+        val - (1 << field.len())
+    } else {
+        val
+    }
+}
+
+/// Smallest and largest value `field` can hold.
+pub(super) fn field_range(field: &Field) -> (i128, i128) {
+    if field.is_signed() {
+        (-(1 << (field.len() - 1)), (1 << (field.len() - 1)) - 1)
+    } else {
+        (0, (1 << field.len()) - 1)
+    }
+}
+
+pub(super) fn set_field(word: &mut u16, field: &Field, val: i128) -> Option<()> {
+    let (min, max) = field_range(field);
+    if val < min || val > max {
+        return None;
+    }
+
+    let mask = ((1u32 << field.len()) - 1) as u16;
+    *word = (*word & !(mask << field.start())) | ((val as u16 & mask) << field.start());
+    Some(())
+}
+
+fn hex(val: i128) -> String {
+    if val < 0 {
+        format!("-{:#x}", -val)
+    } else {
+        format!("{val:#x}")
+    }
+}
+
+fn join_words(words: &[u16]) -> u64 {
+    words.iter().fold(0, |acc, w| acc << 16 | *w as u64)
+}
+
+fn mask_key(pattern: &Pattern, words: usize) -> (u64, u64) {
+    let mut mask = 0;
+    let mut val = 0;
+
+    for (wi, word) in pattern.fields().iter().enumerate().take(words) {
+        let shift = (words - 1 - wi) * 16;
+
+        for field in word {
+            if let FieldType::Mask(fval) = field.ftype() {
+                let offset = shift + field.start();
+                mask |= ((1u64 << field.len()) - 1) << offset;
+                val |= (fval as u64) << offset;
+            }
+        }
+    }
+
+    (mask, val)
+}
+
+pub(super) fn locate(pattern: &Pattern, id: &str) -> Option<(usize, Field)> {
+    pattern
+        .fields()
+        .into_iter()
+        .enumerate()
+        .find_map(|(wi, word)| word.into_iter().find(|f| f.id() == id).map(|f| (wi, f)))
+}
+
+pub(super) fn eval(
+    expr: &Expr,
+    fields: &HashMap<String, i128>,
+    vars: &mut HashMap<String, i128>,
+) -> Option<i128> {
+    match expr {
+        Expr::Line { current, next } => {
+            let val = eval(current, fields, vars);
+            match next {
+                Some(next) => eval(next, fields, vars),
+                None => val,
+            }
+        }
+        Expr::Field { id, is_reg: _ } => fields.get(id).copied(),
+        Expr::Var { id } => vars.get(id).copied(),
+        Expr::Number { val } => Some(*val),
+        Expr::Group { expr } => eval(expr, fields, vars),
+        Expr::Size { var, size: _ } | Expr::Trunc { var, size: _ } => eval(var, fields, vars),
+        Expr::Unary { op, expr } => {
+            let val = eval(expr, fields, vars)?;
+            match op {
+                Op::Minus => Some(-val),
+                Op::BitNot => Some(!val),
+                Op::Bang => Some((val == 0) as i128),
+                _ => None,
+            }
+        }
+        Expr::Binary {
+            lhs,
+            op: Op::Copy,
+            rhs,
+        } => {
+            let val = eval(rhs, fields, vars)?;
+            if let Expr::Var { id } = &**lhs {
+                vars.insert(id.clone(), val);
+            }
+            Some(val)
+        }
+        Expr::Binary { lhs, op, rhs } => {
+            let l = eval(lhs, fields, vars)?;
+            let r = eval(rhs, fields, vars)?;
+            match op {
+                Op::Plus => Some(l + r),
+                Op::Minus => Some(l - r),
+                Op::Mult => Some(l * r),
+                Op::Rem => (r != 0).then(|| l % r),
+                Op::BitOr => Some(l | r),
+                Op::BitAnd => Some(l & r),
+                Op::BitXor => Some(l ^ r),
+                Op::LShft => Some(l << r),
+                Op::RShft | Op::ARShft => Some(l >> r),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+impl<'a> Disassembler<'a> {
+    /// Families are given with the width of their instructions, the longest
+    /// encodings are tried first.
+    pub fn new(families: Vec<(usize, &'a [InstrFamilyBuilder])>) -> Self {
+        let families: Vec<(usize, &InstrFamilyBuilder)> = families
+            .into_iter()
+            .flat_map(|(width, ifams)| ifams.iter().map(move |ifam| (width, ifam)))
+            .collect();
+        let mut entries = Vec::new();
+
+        for (width, ifam) in families.iter().copied() {
+            for instr in ifam.instrs() {
+                let (mask, val) = mask_key(instr.pattern(), width / 16);
+                entries.push(Entry {
+                    ifam,
+                    instr,
+                    words: width / 16,
+                    mask,
+                    val,
+                });
+            }
+        }
+
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.words));
+
+        Disassembler { families, entries }
+    }
+
+    /// Family, constructor and words of the row of sub-table `index` matching
+    /// `words`, with its assembly text.
+    pub(super) fn resolve_table_instr(
+        &self,
+        ifam: &InstrFamilyBuilder,
+        index: usize,
+        words: &[u16],
+        addr: u32,
+        next: u32,
+    ) -> Option<(&'a InstrFamilyBuilder, &'a InstrBuilder, usize, String)> {
+        for (guard, guard_val, table) in ifam.subtable(index)?.built_tables() {
+            for (width, fam) in &self.families {
+                let Some(instrs) = fam.table_instrs(&table) else {
+                    continue;
+                };
+                if words.len() < width / 16 {
+                    continue;
+                }
+                let words = &words[..width / 16];
+
+                for instr in instrs {
+                    let guarded = instr
+                        .pattern()
+                        .fields()
+                        .iter()
+                        .enumerate()
+                        .any(|(wi, word)| {
+                            word.iter().any(|f| {
+                                f.token_name(&fam.prefix()) == guard
+                                    && field_value(f, words[wi]) == guard_val
+                            })
+                        });
+
+                    if !guarded {
+                        continue;
+                    }
+
+                    if let Some(text) = self.render(fam, instr, words, addr, next) {
+                        return Some((fam, instr, words.len(), text));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    fn resolve_table(
+        &self,
+        ifam: &InstrFamilyBuilder,
+        index: usize,
+        words: &[u16],
+        addr: u32,
+        next: u32,
+    ) -> Option<String> {
+        self.resolve_table_instr(ifam, index, words, addr, next)
+            .map(|(_, _, _, text)| text)
+    }
+
+    /// Register exported by the row of sub-table `index` matching `word`, the
+    /// word `wi` of the instruction.
+    fn resolve_export(
+        ifam: &InstrFamilyBuilder,
+        index: usize,
+        wi: usize,
+        word: u16,
+    ) -> Option<String> {
+        let export = ifam
+            .subtable(index)?
+            .exported(|token| ifam.token(wi, token).map(|f| field_value(f, word)))?;
+        let field = ifam.token(wi, &export)?;
+
+        match field.ftype() {
+            FieldType::Variable(regset) => regset
+                .regs()
+                .get(field_value(field, word) as usize)
+                .filter(|reg| *reg != "_")
+                .map(|reg| reg.trim_matches('"').to_string()),
+            _ => None,
+        }
+    }
+
+    /// Assembly text of `instr` if it matches `words`.
+    pub(super) fn render(
+        &self,
+        ifam: &InstrFamilyBuilder,
+        instr: &InstrBuilder,
+        words: &[u16],
+        addr: u32,
+        next: u32,
+    ) -> Option<String> {
+        let pattern = instr.pattern();
+        let mut nums: HashMap<String, i128> = HashMap::new();
+        let mut texts: HashMap<String, String> = HashMap::new();
+
+        for (wi, word) in pattern.fields().iter().enumerate() {
+            for field in word {
+                let val = match words.get(wi) {
+                    Some(w) => field_value(field, *w),
+                    None if field.is_blank() => continue,
+                    None => return None,
+                };
+
+                match field.ftype() {
+                    FieldType::Mask(mval) if mval != val => return None,
+                    FieldType::Variable(regset) => {
+                        let regs = regset.regs();
+                        match regs.get(val as usize) {
+                            Some(reg) if reg != "_" => {
+                                texts.insert(field.id(), reg.trim_matches('"').to_string());
+                            }
+                            _ => return None,
+                        }
+                    }
+                    FieldType::Table(index) => {
+                        let text = match Self::resolve_export(ifam, index, wi, words[wi]) {
+                            Some(text) => text,
+                            None => self.resolve_table(ifam, index, &words[wi..], addr, next)?,
+                        };
+                        texts.insert(field.id(), text);
+                    }
+                    _ => {}
+                }
+
+                nums.insert(field.id(), field_num(field, words[wi]));
+            }
+        }
+
+        let mut vars: HashMap<String, i128> = HashMap::new();
+        vars.insert(String::from("inst_start"), addr as i128);
+        vars.insert(String::from("inst_next"), next as i128);
+        for action in instr.get_actions().exprs() {
+            eval(action, &nums, &mut vars);
+        }
+
+        let display = instr.get_display();
+        if display.is_empty() {
+            return Some(instr.get_name());
+        }
+
+        display_fill(
+            &display,
+            |id| match texts.get(id) {
+                Some(text) => text.clone(),
+                None => match locate(pattern, id) {
+                    Some((wi, field)) => hex(field_num(&field, words[wi])),
+                    None => String::new(),
+                },
+            },
+            |id| vars.get(id).map_or(String::new(), |val| hex(*val)),
+        )
+        .ok()
+    }
+
+    /// Words of an encoding of `instr`, with every operand set to a valid value.
+    fn sample(
+        &self,
+        ifam: &InstrFamilyBuilder,
+        instr: &InstrBuilder,
+        words: usize,
+    ) -> Option<Vec<u16>> {
+        let mut sample = vec![0u16; words];
+
+        for (wi, word) in instr.pattern().fields().iter().enumerate().take(words) {
+            for field in word {
+                let val = match field.ftype() {
+                    FieldType::Mask(val) => val as i128,
+                    FieldType::Variable(regset) => {
+                        regset.regs().iter().rposition(|reg| reg != "_")? as i128
+                    }
+                    FieldType::UImmVal | FieldType::SImmVal => {
+                        let (_, max) = field_range(field);
+                        if field.is_signed() {
+                            -(0x5555 & max)
+                        } else {
+                            0x5555 & max
+                        }
+                    }
+                    FieldType::Table(index) => {
+                        let (guard, guard_val, table) =
+                            ifam.subtable(index)?.built_tables().into_iter().next()?;
+                        let (width, fam, sub) = self.families.iter().find_map(|(width, fam)| {
+                            fam.table_instrs(&table)
+                                .and_then(|instrs| instrs.first())
+                                .map(|sub| (*width, *fam, sub))
+                        })?;
+                        let mut sub_words = self.sample(fam, sub, width / 16)?;
+
+                        for (si, sub_word) in sub.pattern().fields().iter().enumerate() {
+                            for sub_field in sub_word {
+                                if sub_field.token_name(&fam.prefix()) == guard {
+                                    set_field(&mut sub_words[si], sub_field, guard_val as i128)?;
+                                }
+                            }
+                        }
+                        for (word, sub_word) in sample[wi..].iter_mut().zip(sub_words) {
+                            *word |= sub_word;
+                        }
+                        continue;
+                    }
+                    _ => continue,
+                };
+
+                set_field(&mut sample[wi], field, val)?;
+            }
+        }
+
+        Some(sample)
+    }
+
+    /// One encoding of every constructor, in little-endian bytes. A
+    /// constructor with no valid encoding is reported by its family, name and
+    /// display.
+    pub fn samples(&self) -> Vec<Result<Vec<u8>, String>> {
+        self.entries
+            .iter()
+            .map(|entry| {
+                self.sample(entry.ifam, entry.instr, entry.words)
+                    .map(|words| words.iter().flat_map(|w| w.to_le_bytes()).collect())
+                    .ok_or_else(|| {
+                        format!(
+                            "{} {} \"{}\"",
+                            entry.ifam.name(),
+                            entry.instr.get_name(),
+                            entry.instr.get_display()
+                        )
+                    })
+            })
+            .collect()
+    }
+
+    /// Family and constructor of the instruction at the start of `bytes`,
+    /// loaded at `addr`, with its decoding.
+    pub(super) fn lookup(
+        &self,
+        bytes: &[u8],
+        addr: u32,
+    ) -> Option<(&'a InstrFamilyBuilder, &'a InstrBuilder, Instruction)> {
+        let words: Vec<u16> = bytes
+            .chunks_exact(2)
+            .take(4)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect();
+
+        for entry in &self.entries {
+            if words.len() < entry.words {
+                continue;
+            }
+
+            let words = &words[..entry.words];
+            if join_words(words) & entry.mask != entry.val {
+                continue;
+            }
+
+            let next = addr.wrapping_add(2 * entry.words as u32);
+            if let Some(text) = self.render(entry.ifam, entry.instr, words, addr, next) {
+                return Some((
+                    entry.ifam,
+                    entry.instr,
+                    Instruction {
+                        addr,
+                        bytes: bytes[..2 * entry.words].to_vec(),
+                        text,
+                    },
+                ));
+            }
+        }
+
+        None
+    }
+
+    /// Decodes the instruction at the start of `bytes`, loaded at `addr`.
+    pub fn decode(&self, bytes: &[u8], addr: u32) -> Option<Instruction> {
+        self.lookup(bytes, addr).map(|(_, _, instr)| instr)
+    }
+
+    /// Decodes a flat sequence of instructions, undecodable half-words are
+    /// reported as `ILLEGAL`.
+    pub fn disassemble(&self, bytes: &[u8], addr: u32) -> Vec<Instruction> {
+        let mut instrs = Vec::new();
+        let mut offset = 0;
+
+        while offset + 1 < bytes.len() {
+            let iaddr = addr.wrapping_add(offset as u32);
+            let instr = match self.decode(&bytes[offset..], iaddr) {
+                Some(instr) => instr,
+                None => Instruction {
+                    addr: iaddr,
+                    bytes: bytes[offset..offset + 2].to_vec(),
+                    text: String::from("ILLEGAL"),
+                },
+            };
+
+            offset += instr.len();
+            instrs.push(instr);
+        }
+
+        instrs
+    }
+}
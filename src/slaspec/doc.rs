@@ -0,0 +1,221 @@
+use super::instructions::core::{InstrBuilder, InstrFamilyBuilder, Prefixed};
+use super::instructions::expr::Code;
+use super::instructions::pattern::{Field, FieldType, Pattern};
+
+/// Markdown reference pages of the instruction families, one per family plus
+/// an index, drawn from the same model as the SLEIGH files.
+pub struct DocBuilder<'a> {
+    ifams: Vec<&'a InstrFamilyBuilder>,
+}
+
+impl<'a> DocBuilder<'a> {
+    pub fn new(ifams: Vec<&'a InstrFamilyBuilder>) -> Self {
+        DocBuilder { ifams }
+    }
+
+    fn page_name(ifam: &InstrFamilyBuilder) -> String {
+        format!("{}.md", ifam.name())
+    }
+
+    /// Page of the sub-family `id`, named like its `.sinc` file and stored
+    /// in the directory of the family
+    fn sub_page_name(ifam: &InstrFamilyBuilder, id: &str) -> String {
+        format!("{}-{id}.md", ifam.prefix())
+    }
+
+    fn layout(pattern: &Pattern, fill: impl Fn(&Field) -> bool) -> String {
+        format!("```text\n{}\n```\n", pattern.layout(fill).join("\n"))
+    }
+
+    fn code(instr: &InstrBuilder, code: &Code) -> String {
+        format!(
+            "```text\n{}\n```\n",
+            code.build(instr.pattern(), &instr.prefix())
+                .trim_start_matches('\n')
+        )
+    }
+
+    fn values(ifam: &InstrFamilyBuilder, field: &Field) -> Option<String> {
+        match field.ftype() {
+            FieldType::Variable(regset) => Some(regset.regs().join(" ")),
+            FieldType::UImmVal => Some(String::from("unsigned immediate")),
+            FieldType::SImmVal => Some(String::from("signed immediate")),
+            FieldType::Any => Some(String::from("any")),
+            FieldType::Table(index) => Some(format!(
+                "sub-table `{}`",
+                ifam.subtable(index)
+                    .map_or(index.to_string(), |table| table.name())
+            )),
+            FieldType::Blank | FieldType::Mask(_) => None,
+        }
+    }
+
+    fn operands(ifam: &InstrFamilyBuilder, pattern: &Pattern) -> String {
+        let mut rows = String::new();
+
+        for (wi, word) in pattern.fields().iter().enumerate() {
+            for field in word {
+                if let Some(values) = Self::values(ifam, field) {
+                    rows += &format!(
+                        "| `{}` | {} | {}:{} | {} |\n",
+                        field.id(),
+                        wi,
+                        field.end(),
+                        field.start(),
+                        values
+                    );
+                }
+            }
+        }
+
+        if rows.is_empty() {
+            return rows;
+        }
+
+        format!("| Field | Word | Bits | Values |\n|---|---|---|---|\n{rows}\n")
+    }
+
+    fn instr_section(ifam: &InstrFamilyBuilder, table: &str, instr: &InstrBuilder) -> String {
+        let mut section = String::new();
+
+        section += &format!("### `{}`\n\n", instr.get_display());
+        section += &match instr.get_name().as_str() {
+            "" => format!("Constructor of table `{table}`.\n\n"),
+            name => format!("Constructor `{name}` of table `{table}`.\n\n"),
+        };
+        section += &Self::layout(instr.pattern(), |_| true);
+        section += "\n";
+        section += &Self::operands(ifam, instr.pattern());
+
+        if !instr.get_actions().is_empty() {
+            section += "Actions:\n\n";
+            section += &Self::code(instr, &instr.get_actions());
+            section += "\n";
+        }
+
+        if instr.get_pcodes().is_empty() {
+            section += "No p-code.\n\n";
+        } else {
+            section += &Self::code(instr, &instr.get_pcodes());
+            section += "\n";
+        }
+
+        section
+    }
+
+    pub fn family_page(ifam: &InstrFamilyBuilder) -> String {
+        let mut page = String::new();
+
+        page += &format!("# {} ({})\n\n", ifam.name(), ifam.desc());
+        page += &format!(
+            "{}-bit instructions, token prefix `{}`, {} encoding(s).\n\n",
+            ifam.bits(),
+            ifam.prefix(),
+            ifam.len()
+        );
+
+        page += "## Base layout\n\n";
+        page += &Self::layout(ifam.base_pattern(), |field| {
+            field.id().starts_with("sig") || field.id().starts_with("mask")
+        });
+        page += "\n";
+
+        if !ifam.pcodeops().is_empty() {
+            page += "## Operations\n\n";
+            for op in ifam.pcodeops() {
+                page += &format!("- `{op}`\n");
+            }
+            page += "\n";
+        }
+
+        if ifam.subtables_len() > 0 {
+            page += "## Sub-tables\n\n```text\n";
+            for table in (0..ifam.subtables_len()).filter_map(|index| ifam.subtable(index)) {
+                page += &table.build();
+            }
+            page += "```\n\n";
+        }
+
+        if ifam.sub_fam() == 1 {
+            page += "## Instructions\n\n";
+            page += &Self::instr_sections(ifam, &ifam.name());
+        } else {
+            page += "## Sub-families\n\n| Sub-family | Table | Encodings |\n|---|---|---|\n";
+            for id in ifam.ids() {
+                let table = ifam.table_name(&id);
+                page += &format!(
+                    "| [{id}]({}/{}) | `{table}` | {} |\n",
+                    ifam.name(),
+                    Self::sub_page_name(ifam, &id),
+                    ifam.table_instrs(&table).map_or(0, |instrs| instrs.len())
+                );
+            }
+        }
+
+        page
+    }
+
+    fn instr_sections(ifam: &InstrFamilyBuilder, table: &str) -> String {
+        ifam.table_instrs(table)
+            .into_iter()
+            .flatten()
+            .map(|instr| Self::instr_section(ifam, table, instr))
+            .collect()
+    }
+
+    /// Instructions of the sub-family `id`, for families too large for a
+    /// single page
+    pub fn sub_family_page(ifam: &InstrFamilyBuilder, id: &str) -> String {
+        let table = ifam.table_name(id);
+        let mut page = String::new();
+
+        page += &format!("# {}: {id}\n\n", ifam.name());
+        page += &format!(
+            "Constructors of table `{table}`, see [{}](../{}).\n\n",
+            ifam.name(),
+            Self::page_name(ifam)
+        );
+        page += "## Instructions\n\n";
+        page += &Self::instr_sections(ifam, &table);
+
+        page
+    }
+
+    pub fn index(&self) -> String {
+        let mut index = String::from("# Blackfin+ instruction families\n\n");
+
+        index += "| Family | Description | Bits | Encodings |\n|---|---|---|---|\n";
+        for ifam in &self.ifams {
+            index += &format!(
+                "| [{}]({}) | {} | {} | {} |\n",
+                ifam.name(),
+                Self::page_name(ifam),
+                ifam.desc(),
+                ifam.bits(),
+                ifam.len()
+            );
+        }
+
+        index
+    }
+
+    /// File name and content of every page
+    pub fn build(&self) -> Vec<(String, String)> {
+        let mut pages = vec![(String::from("index.md"), self.index())];
+
+        for ifam in &self.ifams {
+            pages.push((Self::page_name(ifam), Self::family_page(ifam)));
+
+            if ifam.sub_fam() > 1 {
+                for id in ifam.ids() {
+                    pages.push((
+                        format!("{}/{}", ifam.name(), Self::sub_page_name(ifam, &id)),
+                        Self::sub_family_page(ifam, &id),
+                    ));
+                }
+            }
+        }
+
+        pages
+    }
+}
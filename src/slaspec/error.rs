@@ -0,0 +1,144 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// A display template that can't be parsed
+    Display {
+        display: String,
+        reason: &'static str,
+    },
+    UnknownField {
+        field: String,
+    },
+    UnknownFamily {
+        name: String,
+    },
+    SplitLength {
+        field: String,
+        len: usize,
+        split_len: usize,
+    },
+    DivideLength {
+        field: String,
+        len: usize,
+        part_len: usize,
+    },
+    /// Operands of a p-code expression with incompatible sizes
+    Size {
+        mismatches: Vec<String>,
+    },
+    /// Registers read by the disassembly actions of a parallel slot, they
+    /// have no copy taken before the bundle
+    SlotRead {
+        regs: Vec<String>,
+    },
+    Instr {
+        name: String,
+        display: String,
+        source: Box<Error>,
+    },
+    Family {
+        name: String,
+        source: Box<Error>,
+    },
+    /// A declarative family definition that can't be loaded
+    Definition {
+        line: usize,
+        reason: String,
+    },
+    File {
+        path: PathBuf,
+        source: Box<Error>,
+    },
+    Io {
+        path: PathBuf,
+        source: Arc<io::Error>,
+    },
+}
+
+impl Error {
+    pub fn io(path: impl Into<PathBuf>, source: io::Error) -> Self {
+        Error::Io {
+            path: path.into(),
+            source: Arc::new(source),
+        }
+    }
+
+    pub fn in_instr(self, name: &str, display: &str) -> Self {
+        Error::Instr {
+            name: name.to_string(),
+            display: display.to_string(),
+            source: Box::new(self),
+        }
+    }
+
+    pub fn in_family(self, name: &str) -> Self {
+        Error::Family {
+            name: name.to_string(),
+            source: Box::new(self),
+        }
+    }
+
+    pub fn in_file(self, path: impl Into<PathBuf>) -> Self {
+        Error::File {
+            path: path.into(),
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Display { display, reason } => write!(f, "display \"{display}\": {reason}"),
+            Error::UnknownField { field } => write!(f, "no field {field}"),
+            Error::UnknownFamily { name } => write!(f, "no family {name}"),
+            Error::SplitLength {
+                field,
+                len,
+                split_len,
+            } => write!(
+                f,
+                "field {field} has {len} bit(s) but is split into {split_len} bit(s)"
+            ),
+            Error::DivideLength {
+                field,
+                len,
+                part_len,
+            } => write!(
+                f,
+                "field {field} has {len} bit(s) but is divided into fields of {part_len} bit(s)"
+            ),
+            Error::Size { mismatches } => {
+                write!(f, "size mismatch in {}", mismatches.join(", "))
+            }
+            Error::SlotRead { regs } => {
+                write!(f, "the actions of a parallel slot read {}", regs.join(", "))
+            }
+            Error::Instr {
+                name,
+                display,
+                source,
+            } => write!(f, "instruction {name} \"{display}\": {source}"),
+            Error::Family { name, source } => write!(f, "family {name}: {source}"),
+            Error::Definition { line, reason } => write!(f, "line {line}: {reason}"),
+            Error::File { path, source } => write!(f, "{}: {source}", path.display()),
+            Error::Io { path, source } => write!(f, "{}: {source}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Instr { source, .. }
+            | Error::Family { source, .. }
+            | Error::File { source, .. } => Some(source.as_ref()),
+            Error::Io { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use super::instructions::core::{InstrBuilder, InstrFamilyBuilder, Prefixed};
+use super::instructions::expr::Code;
+use super::instructions::pattern::{Field, FieldType, Pattern};
+
+use serde_json::{Value, json};
+
+/// Serializes the instruction families to JSON, for the tools that need the
+/// ISA without parsing the SLEIGH files.
+pub struct ModelExport<'a> {
+    ifams: Vec<&'a InstrFamilyBuilder>,
+}
+
+impl<'a> ModelExport<'a> {
+    pub fn new(ifams: Vec<&'a InstrFamilyBuilder>) -> Self {
+        ModelExport { ifams }
+    }
+
+    fn field_type(ifam: &InstrFamilyBuilder, ftype: &FieldType) -> Value {
+        match ftype {
+            FieldType::Blank => json!({ "kind": "blank" }),
+            FieldType::Any => json!({ "kind": "any" }),
+            FieldType::UImmVal => json!({ "kind": "uimm" }),
+            FieldType::SImmVal => json!({ "kind": "simm" }),
+            FieldType::Mask(val) => json!({ "kind": "mask", "value": val }),
+            FieldType::Variable(regset) => json!({
+                "kind": "variable",
+                "set": regset.to_string(),
+                "registers": regset.regs(),
+            }),
+            FieldType::Table(index) => json!({
+                "kind": "table",
+                "table": ifam
+                    .subtable(*index)
+                    .map_or(json!(index), |table| json!(table.name())),
+            }),
+        }
+    }
+
+    fn field(ifam: &InstrFamilyBuilder, word: usize, field: &Field) -> Value {
+        json!({
+            "id": field.id(),
+            "word": word,
+            "start": field.start(),
+            "end": field.end(),
+            "type": Self::field_type(ifam, &field.ftype()),
+        })
+    }
+
+    fn pattern(ifam: &InstrFamilyBuilder, pattern: &Pattern) -> Value {
+        pattern
+            .fields()
+            .iter()
+            .enumerate()
+            .flat_map(|(word, fields)| fields.iter().map(move |f| Self::field(ifam, word, f)))
+            .collect()
+    }
+
+    fn code(instr: &InstrBuilder, code: &Code) -> Value {
+        let text = code.build(instr.pattern(), &instr.prefix());
+
+        json!(
+            text.lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<&str>>()
+                .join("\n")
+        )
+    }
+
+    fn instr(ifam: &InstrFamilyBuilder, table: &str, instr: &InstrBuilder) -> Value {
+        json!({
+            "table": table,
+            "name": instr.get_name(),
+            "display": instr.get_display(),
+            "pattern": Self::pattern(ifam, instr.pattern()),
+            "actions": Self::code(instr, &instr.get_actions()),
+            "pcode": Self::code(instr, &instr.get_pcodes()),
+        })
+    }
+
+    fn family(ifam: &InstrFamilyBuilder) -> Value {
+        let instrs: Vec<Value> = ifam
+            .tables()
+            .iter()
+            .flat_map(|table| {
+                ifam.table_instrs(table)
+                    .into_iter()
+                    .flatten()
+                    .map(move |instr| Self::instr(ifam, table, instr))
+            })
+            .collect();
+        let subtables: Vec<Value> = (0..ifam.subtables_len())
+            .filter_map(|index| ifam.subtable(index))
+            .map(|table| json!({ "name": table.name(), "sleigh": table.build() }))
+            .collect();
+
+        json!({
+            "name": ifam.name(),
+            "prefix": ifam.prefix(),
+            "description": ifam.desc(),
+            "bits": ifam.bits(),
+            "multi": ifam.is_multi(),
+            "base_pattern": Self::pattern(ifam, ifam.base_pattern()),
+            "pcodeops": ifam.pcodeops(),
+            "subtables": subtables,
+            "instructions": instrs,
+        })
+    }
+
+    pub fn build(&self) -> String {
+        let families: Vec<Value> = self.ifams.iter().map(|ifam| Self::family(ifam)).collect();
+
+        serde_json::to_string_pretty(&json!({ "families": families }))
+            .expect("a JSON value always serializes")
+            + "\n"
+    }
+}
@@ -6,3 +6,18 @@ pub const DEFAULT_MEM: &str = RAM_SAPCE;
 
 pub const ENDIAN: &str = "little";
 pub const ALIGNMENT: usize = 2;
+
+pub const PROGRAM_COUNTER: &str = "PC";
+pub const CONTEXT_REGISTER: &str = "contextreg";
+
+// Core view of the L1 memories and of the core MMR space
+pub const MEMORY_BLOCKS: [(&str, u32, u32, &str); 5] = [
+    ("L1_DATA_A", 0xFF800000, 0x8000, "rw"),
+    ("L1_DATA_B", 0xFF900000, 0x8000, "rw"),
+    ("L1_CODE", 0xFFA00000, 0x10000, "rwx"),
+    ("L1_SCRATCH", 0xFFB00000, 0x1000, "rw"),
+    ("CORE_MMR", 0x1FC00000, 0x400000, "rwv"),
+];
+
+// `SlaFormat.MAX_FILE_SIZE` in Ghidra, checked against the uncompressed .sla
+pub const SLA_MAX_FILE_SIZE: usize = 16 * 1024 * 1024;
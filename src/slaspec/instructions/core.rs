@@ -1,12 +1,18 @@
-use std::collections::{HashMap, HashSet};
-
-use itertools::Itertools;
-
+use crate::slaspec::error::Error;
 use crate::slaspec::instructions::{format::display_format, pattern::Pattern};
+use crate::slaspec::sleigh::{
+    Attach, Constraint, Constructor, DisplaySection, Document, Item, Layout, Macro, Stmt, TokenDef,
+    TokenField,
+};
+use std::collections::{BTreeMap, BTreeSet};
 
 use super::{
     expr::{Code, Expr},
+    flags::FlagEffect,
     pattern::{Field, FieldType, ProtoPattern},
+    simplify::simplify,
+    sizes::SizeCheck,
+    subtable::{SubTable, factor_instrs},
     util::mask_hex,
 };
 
@@ -18,6 +24,8 @@ pub struct InstrBuilder {
     display: String,
     actions: Code,
     pcodes: Code,
+    flags: FlagEffect,
+    error: Option<Error>,
 }
 
 impl InstrBuilder {
@@ -29,9 +37,23 @@ impl InstrBuilder {
             display: String::new(),
             actions: Code::new(),
             pcodes: Code::new(),
+            flags: FlagEffect::new(),
+            error: None,
         }
     }
 
+    /// Keeps the first error of the chain, it is reported by `build`.
+    fn fail(&mut self, err: Error) {
+        self.error.get_or_insert(err);
+    }
+
+    /// Records an error found while assembling the instruction, `build`
+    /// reports it.
+    pub fn set_error(mut self, err: Error) -> Self {
+        self.fail(err);
+        self
+    }
+
     pub fn set_pattern(mut self, pattern: Pattern) -> Self {
         self.pattern = pattern;
         self
@@ -94,8 +116,9 @@ impl InstrBuilder {
         self
     }
 
+    /// P-code of the instruction, with its flag writes
     pub fn get_pcodes(&self) -> Code {
-        self.pcodes.clone()
+        self.flags.apply(self.pcodes.clone())
     }
 
     pub fn set_pcodes(mut self, pcodes: Code) -> Self {
@@ -103,109 +126,120 @@ impl InstrBuilder {
         self
     }
 
+    /// Adds the ASTAT flags written by the instruction
+    pub fn add_flags(mut self, flags: FlagEffect) -> Self {
+        self.flags = self.flags.merge(flags);
+        self
+    }
+
+    pub fn get_flags(&self) -> &FlagEffect {
+        &self.flags
+    }
+
     pub fn set_field_type(mut self, field_id: &str, ftype: FieldType) -> Self {
+        if self.pattern.get_field(field_id).is_none() {
+            self.fail(Error::UnknownField {
+                field: field_id.to_string(),
+            });
+        }
+
         self.pattern = self.pattern.clone().set_field_type(field_id, ftype);
         self
     }
 
-    pub fn set_field_type_opt(mut self, cond: bool, field_id: &str, ftype: FieldType) -> Self {
+    pub fn set_field_type_opt(self, cond: bool, field_id: &str, ftype: FieldType) -> Self {
         if cond {
-            self.pattern = self.pattern.clone().set_field_type(field_id, ftype);
+            return self.set_field_type(field_id, ftype);
         }
 
         self
     }
 
     pub fn split_field(mut self, field_id: &str, split: ProtoPattern) -> Self {
-        self.pattern = self.pattern.clone().split_field(field_id, split);
+        match self.pattern.clone().split_field(field_id, split) {
+            Ok(pattern) => self.pattern = pattern,
+            Err(err) => self.fail(err),
+        }
         self
     }
 
     pub fn divide_field(mut self, field_id: &str, div: ProtoPattern) -> Self {
-        self.pattern = self.pattern.clone().divide_field(field_id, div);
+        match self.pattern.clone().divide_field(field_id, div) {
+            Ok(pattern) => self.pattern = pattern,
+            Err(err) => self.fail(err),
+        }
         self
     }
 
     fn build_name(&self) -> String {
-        format!(":^\"{}\"", self.name)
-    }
-
-    fn build_pattern(&self, alt: bool, alt_display: &str) -> String {
-        let mut pattern_str = format!(
-            "\n\tis {}",
-            if alt {
-                format!("{alt_display} & ")
-            } else {
-                "".to_string()
-            }
-        );
-
-        let words = self.pattern.fields();
-        for word in words {
-            if word.is_empty() {
-                continue;
-            }
-            for field in word {
-                if field.is_blank() {
-                    continue;
-                }
-
-                pattern_str += &field.token_name(&self.prefix);
-                if let FieldType::Mask(val) = field.ftype() {
-                    pattern_str += "=";
-                    pattern_str += &mask_hex(val, field.len());
-                }
-                pattern_str += " & "
-            }
-            pattern_str.truncate(pattern_str.len() - 2);
-            pattern_str += "\n\t ; ";
+        if self.name.is_empty() {
+            // The display starts with an operand
+            return String::new();
         }
-        pattern_str.truncate(pattern_str.len() - 5);
 
-        pattern_str
+        format!("^\"{}\"", self.name)
     }
 
-    fn build_action(&self) -> String {
-        let mut actions = String::new();
-
-        if self.actions.is_empty() {
-            return actions;
+    fn build_pattern(&self, alt: bool, alt_display: &str) -> Vec<Vec<Constraint>> {
+        let mut words: Vec<Vec<Constraint>> = self
+            .pattern
+            .fields()
+            .iter()
+            .map(|word| {
+                word.iter()
+                    .filter(|field| !field.is_blank())
+                    .map(|field| {
+                        let name = field.token_name(&self.prefix);
+                        match field.ftype() {
+                            FieldType::Mask(val) => Constraint::Eq {
+                                field: name,
+                                value: mask_hex(val, field.len()),
+                            },
+                            _ => Constraint::Operand(name),
+                        }
+                    })
+                    .collect::<Vec<Constraint>>()
+            })
+            .filter(|word| !word.is_empty())
+            .collect();
+
+        if alt && let Some(word) = words.first_mut() {
+            word.insert(0, Constraint::Operand(alt_display.to_string()));
         }
 
-        actions += &self.actions.build(&self.pattern, &self.prefix);
-
-        format!("\n[{}\n]", actions)
+        words
     }
 
-    fn build_pcode(&self) -> String {
-        if self.pcodes.is_empty() {
-            return String::from("{}");
-        }
+    pub fn build(&self, table: &str, alt_display: String) -> Result<(Constructor, bool), Error> {
+        let context = |err: Error| err.in_instr(&self.name, &self.display);
 
-        let mut pcodes = String::new();
-        let nl = if self.actions.is_empty() { "\n" } else { " " };
-
-        pcodes += &self.pcodes.build(&self.pattern, &self.prefix);
-
-        format!("{}{{{}\n}}", nl, pcodes)
-    }
+        if let Some(err) = &self.error {
+            return Err(context(err.clone()));
+        }
 
-    pub fn build(&self, alt_display: String) -> (String, bool) {
-        let (display, vars) = display_format(&self.display, &self.pattern, &self.prefix);
+        let (display, vars) =
+            display_format(&self.display, &self.pattern, &self.prefix).map_err(context)?;
+        let actions = simplify(self.actions.clone());
+        let pcodes = simplify(self.get_pcodes());
+        SizeCheck::check(&self.pattern, &self.prefix, &pcodes).map_err(context)?;
         let empty_display = display.is_empty();
         let no_vars = vars == 0;
         let alt = no_vars && !empty_display;
-        (
-            format!(
-                "{} {}{}{}{}",
-                self.build_name(),
-                if alt { &alt_display } else { &display },
-                self.build_pattern(alt, &alt_display),
-                self.build_action(),
-                self.build_pcode()
-            ),
+        Ok((
+            Constructor {
+                table: table.to_string(),
+                display: DisplaySection::Operands(format!(
+                    "{} {}",
+                    self.build_name(),
+                    if alt { &alt_display } else { &display }
+                )),
+                pattern: self.build_pattern(alt, &alt_display),
+                actions: actions.statements(&self.pattern, &self.prefix),
+                semantics: pcodes.statements(&self.pattern, &self.prefix),
+                layout: Layout::Block,
+            },
             alt,
-        )
+        ))
     }
 }
 
@@ -214,10 +248,11 @@ pub struct InstrFamilyBuilder {
     desc: String,
     prefix: String,
     base_pattern: Pattern,
-    instructions: HashMap<String, Vec<InstrBuilder>>,
-    tokens: [HashSet<Field>; 4],
-    variables: HashSet<Field>,
+    instructions: BTreeMap<String, Vec<InstrBuilder>>,
+    tokens: [BTreeSet<Field>; 4],
+    variables: BTreeSet<Field>,
     pcodeops: Vec<String>,
+    subtables: Vec<SubTable>,
     multi: bool,
 }
 
@@ -228,15 +263,16 @@ impl InstrFamilyBuilder {
             desc: String::from(desc),
             prefix: String::from(prefix),
             base_pattern: Pattern::from(base_pattern),
-            instructions: HashMap::new(),
+            instructions: BTreeMap::new(),
             tokens: [
-                HashSet::new(),
-                HashSet::new(),
-                HashSet::new(),
-                HashSet::new(),
+                BTreeSet::new(),
+                BTreeSet::new(),
+                BTreeSet::new(),
+                BTreeSet::new(),
             ],
-            variables: HashSet::new(),
+            variables: BTreeSet::new(),
             pcodeops: Vec::new(),
+            subtables: Vec::new(),
             multi: false,
         }
     }
@@ -247,15 +283,16 @@ impl InstrFamilyBuilder {
             desc: String::from(desc),
             prefix: String::from(prefix),
             base_pattern: Pattern::from(base_pattern),
-            instructions: HashMap::new(),
+            instructions: BTreeMap::new(),
             tokens: [
-                HashSet::new(),
-                HashSet::new(),
-                HashSet::new(),
-                HashSet::new(),
+                BTreeSet::new(),
+                BTreeSet::new(),
+                BTreeSet::new(),
+                BTreeSet::new(),
             ],
-            variables: HashSet::new(),
+            variables: BTreeSet::new(),
             pcodeops: Vec::new(),
+            subtables: Vec::new(),
             multi: false,
         }
     }
@@ -266,15 +303,16 @@ impl InstrFamilyBuilder {
             desc: String::from(desc),
             prefix: String::from(prefix),
             base_pattern: Pattern::from(base_pattern),
-            instructions: HashMap::new(),
+            instructions: BTreeMap::new(),
             tokens: [
-                HashSet::new(),
-                HashSet::new(),
-                HashSet::new(),
-                HashSet::new(),
+                BTreeSet::new(),
+                BTreeSet::new(),
+                BTreeSet::new(),
+                BTreeSet::new(),
             ],
-            variables: HashSet::new(),
+            variables: BTreeSet::new(),
             pcodeops: Vec::new(),
+            subtables: Vec::new(),
             multi: false,
         }
     }
@@ -283,6 +321,36 @@ impl InstrFamilyBuilder {
         self.name.clone()
     }
 
+    pub fn desc(&self) -> String {
+        self.desc.clone()
+    }
+
+    pub fn base_pattern(&self) -> &Pattern {
+        &self.base_pattern
+    }
+
+    /// Width of the instructions of the family
+    pub fn bits(&self) -> usize {
+        16 * self
+            .base_pattern
+            .fields()
+            .iter()
+            .filter(|word| !word.is_empty())
+            .count()
+    }
+
+    pub fn pcodeops(&self) -> &[String] {
+        &self.pcodeops
+    }
+
+    pub fn tokens_len(&self) -> usize {
+        self.tokens.iter().map(|word| word.len()).sum()
+    }
+
+    pub fn variables(&self) -> impl Iterator<Item = &Field> {
+        self.variables.iter()
+    }
+
     pub fn len(&self) -> usize {
         let mut total = 0;
 
@@ -301,6 +369,61 @@ impl InstrFamilyBuilder {
         self.multi = multi;
     }
 
+    pub fn is_multi(&self) -> bool {
+        self.multi
+    }
+
+    pub fn instrs(&self) -> Vec<&InstrBuilder> {
+        self.instructions
+            .keys()
+            .flat_map(|id| self.instructions.get(id).unwrap())
+            .collect()
+    }
+
+    /// Ids of the sub-families, `base` for the family table itself
+    pub fn ids(&self) -> Vec<String> {
+        self.instructions.keys().cloned().collect()
+    }
+
+    /// Constructor table of the sub-family `id`
+    pub fn table_name(&self, id: &str) -> String {
+        if id == "base" {
+            self.name()
+        } else {
+            format!("{}{id}", self.name)
+        }
+    }
+
+    pub fn tables(&self) -> Vec<String> {
+        self.instructions
+            .keys()
+            .map(|id| self.table_name(id))
+            .collect()
+    }
+
+    pub fn table_instrs(&self, table: &str) -> Option<&Vec<InstrBuilder>> {
+        self.instructions
+            .iter()
+            .find_map(|(id, instrs)| (self.table_name(id) == table).then_some(instrs))
+    }
+
+    /// Token field of word `word` named `name` in the generated spec
+    pub fn token(&self, word: usize, name: &str) -> Option<&Field> {
+        self.tokens
+            .get(word)?
+            .iter()
+            .find(|field| field.token_name(&self.prefix) == name)
+    }
+
+    pub fn subtable(&self, index: usize) -> Option<&SubTable> {
+        self.subtables.get(index)
+    }
+
+    pub fn add_subtable(&mut self, table: SubTable) -> usize {
+        self.subtables.push(table);
+        self.subtables.len() - 1
+    }
+
     pub fn add_pcodeop(&mut self, pcodeop: &str) {
         self.pcodeops.push(String::from(pcodeop));
     }
@@ -324,12 +447,14 @@ impl InstrFamilyBuilder {
     pub fn init_tokens_and_vars(&mut self) {
         for (wi, field) in self
             .instructions
-            .iter()
-            .sorted_by_key(|(id, _inst)| (*id).clone())
-            .flat_map(|(_id, instrs)| instrs)
+            .values()
+            .flatten()
             .flat_map(|instr| instr.pattern().fields().into_iter().enumerate())
             .flat_map(|(wi, fields)| fields.into_iter().map(move |field| (wi, field)))
         {
+            if field.is_table() {
+                continue;
+            }
             if field.is_blank() {
                 if self.multi && field.id() == "m" {
                     self.tokens[wi].insert(field.clone());
@@ -343,224 +468,277 @@ impl InstrFamilyBuilder {
         }
     }
 
-    fn build_desc(&self) -> String {
-        let mut desc_str = String::new();
-
-        desc_str += &format!("## {} ({})\n", self.desc, self.name);
-        desc_str += "##\n";
+    pub fn subtables_len(&self) -> usize {
+        self.subtables.len()
+    }
 
-        let pattern_sep = "## +---+---+---+---|---+---+---+---|---+---+---+---|---+---+---+---+\n";
+    pub fn factor_subtables(&mut self) {
+        let mut subtables = self.subtables.clone();
 
-        for word in self.base_pattern.fields() {
-            if word.is_empty() {
-                continue;
-            }
-            desc_str += pattern_sep;
-            let mut word_str = "## ".to_string();
-
-            for field in word {
-                if field.id().starts_with("sig") || field.id().starts_with("mask") {
-                    if let FieldType::Mask(mask) = field.ftype() {
-                        let bin_str = format!("{mask:0len$b}", len = field.len());
-                        for bit in bin_str.chars() {
-                            word_str += &format!("| {bit} ");
-                        }
-                    }
-                } else {
-                    word_str += &format!("|{:.^len$}", field.id(), len = (4 * field.len() - 1));
-                }
+        for id in self.instructions.keys().cloned().collect::<Vec<String>>() {
+            if let Some(instrs) = self.instructions.remove(&id) {
+                let factored = factor_instrs(&instrs, &self.prefix, &mut subtables);
+                self.instructions.insert(id, factored);
             }
+        }
+
+        self.subtables = subtables;
+    }
 
-            word_str += "|\n";
-            desc_str += &word_str;
+    fn section(title: &str) -> Item {
+        Item::Comment(format!("## {title} ###"))
+    }
+
+    fn build_desc(&self) -> Vec<Item> {
+        let mut items = vec![
+            Item::Comment(format!("# {} ({})", self.desc, self.name)),
+            Item::Comment(String::from("#")),
+        ];
+
+        for line in self
+            .base_pattern
+            .layout(|field| field.id().starts_with("sig") || field.id().starts_with("mask"))
+        {
+            items.push(Item::Comment(format!("# {line}")));
         }
 
-        desc_str += pattern_sep;
-        desc_str
+        items
     }
 
-    fn build_tokens(&self) -> String {
-        let mut tokens_str = String::new();
+    fn build_tokens(&self) -> Vec<Item> {
+        let mut items = vec![];
 
         for i in 0..4 {
             if self.tokens[i].is_empty() {
                 continue;
             }
-            let mut tokens: Vec<Field> = self.tokens[i].clone().into_iter().collect();
-            tokens.sort();
-            tokens.reverse();
-
-            tokens_str += &format!("define token {}Instr{} (16)\n", self.prefix, (i + 1) * 16);
-            for tok in tokens {
-                tokens_str += &format!(
-                    "\t{:16} = ({:2},{:2}) {}\n",
-                    tok.token_name(&self.prefix),
-                    tok.start(),
-                    tok.end(),
-                    if tok.is_signed() { "signed" } else { "" }
-                );
-            }
-            tokens_str += ";\n\n"
-        }
-
-        tokens_str
-    }
-
-    fn build_variables(&self) -> String {
-        let mut var_str = String::new();
-        let mut variables: Vec<Field> = self.variables.clone().into_iter().collect();
-        variables.sort_by(|a, b| a.ftype().cmp(&b.ftype()));
-
-        for var in variables {
-            if let FieldType::Variable(regset) = var.ftype() {
-                let regs = regset.regs();
-
-                var_str += &format!(
-                    "attach {} {} [{}];\n",
-                    regset.attach_type(),
-                    var.token_name(&self.prefix),
-                    if regs.len() <= 8 {
-                        regs.join(" ")
-                    } else {
-                        format!(
-                            "\n{}\n",
-                            regs.chunks(8)
-                                .map(|chunk| format!("\t{}", chunk.join(" ")))
-                                .collect::<Vec<String>>()
-                                .join("\n")
-                        )
-                    }
-                );
-            }
+            let tokens: Vec<&Field> = self.tokens[i].iter().rev().collect();
+
+            items.push(Item::Token(TokenDef {
+                name: format!("{}Instr{}", self.prefix, (i + 1) * 16),
+                size: 16,
+                fields: tokens
+                    .iter()
+                    .map(|tok| TokenField {
+                        name: tok.token_name(&self.prefix),
+                        start: tok.start(),
+                        end: tok.end(),
+                        signed: tok.is_signed(),
+                    })
+                    .collect(),
+            }));
+            items.push(Item::Blank);
         }
 
-        var_str
+        items
     }
 
-    fn build_pcodeops(&self) -> String {
-        let mut pcodeops_str = String::new();
+    fn build_variables(&self) -> Vec<Item> {
+        let mut variables: Vec<Field> = self.variables.iter().cloned().collect();
+        // Fields attached to the same register set come by name
+        variables.sort_by_key(|var| (var.ftype(), var.token_name(&self.prefix)));
 
-        for op in &self.pcodeops {
-            pcodeops_str += &format!("define pcodeop {};\n", op);
+        variables
+            .iter()
+            .filter_map(|var| match var.ftype() {
+                FieldType::Variable(regset) => Some(Item::Attach(Attach {
+                    kind: regset.attach_type(),
+                    fields: vec![var.token_name(&self.prefix)],
+                    values: regset.regs(),
+                })),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn build_subtables(&self) -> Vec<Item> {
+        let mut items = vec![];
+
+        for table in &self.subtables {
+            items.extend(table.items());
+            items.push(Item::Blank);
         }
 
-        pcodeops_str
+        items
     }
 
-    fn build_instructions(&self, id: &str) -> String {
-        let mut instr_str = String::new();
-        let mut instr_count = 0;
+    fn build_pcodeops(&self) -> Vec<Item> {
+        self.pcodeops
+            .iter()
+            .map(|op| Item::PcodeOp(op.clone()))
+            .collect()
+    }
 
-        for instr in self.instructions.get(id).unwrap() {
+    fn build_instructions(&self, id: &str) -> Result<Vec<Item>, Error> {
+        let mut items = vec![];
+        let table = self.table_name(id);
+
+        for (instr_count, instr) in self.instructions.get(id).unwrap().iter().enumerate() {
             let literal_desc = format!("{}Desc{:02X}", self.name, instr_count);
-            let (build, alt_disp) = instr.build(literal_desc.clone());
+            let (cons, alt_disp) = instr
+                .build(&table, literal_desc.clone())
+                .map_err(|err| err.in_family(&self.name))?;
             if alt_disp {
-                instr_str += &format!("{literal_desc}: \"{}\" is epsilon {{}}\n", instr.display);
+                items.push(Item::Constructor(Constructor {
+                    table: literal_desc,
+                    display: DisplaySection::Literal(instr.display.clone()),
+                    pattern: vec![vec![Constraint::Operand(String::from("epsilon"))]],
+                    actions: vec![],
+                    semantics: vec![],
+                    layout: Layout::Inline,
+                }));
             }
 
-            if id == "base" {
-                instr_str += &format!("{}{}\n\n", self.name, build);
-            } else {
-                instr_str += &format!("{}{id}{}\n\n", self.name, build);
-            }
-
-            instr_count += 1;
+            items.push(Item::Constructor(cons));
+            items.push(Item::Blank);
         }
 
-        instr_str
+        Ok(items)
     }
 
-    fn build_all_instructions(&self) -> String {
-        let mut all_instrs = String::new();
+    fn build_all_instructions(&self) -> Result<Vec<Item>, Error> {
+        let mut items = vec![];
 
-        for id in self.instructions.keys().sorted() {
-            all_instrs += &self.build_instructions(id);
+        for id in self.instructions.keys() {
+            items.extend(self.build_instructions(id)?);
         }
 
-        all_instrs
+        Ok(items)
     }
 
-    fn build_id_final_instr(&self, id: &str) -> String {
+    fn build_id_final_instr(&self, id: &str) -> Vec<Item> {
+        let table = format!("{}{id}", self.name());
+        let final_instr = |guard: Option<u16>, semantics: Vec<Stmt>| {
+            let mut pattern = vec![];
+            if let Some(val) = guard {
+                pattern.push(Constraint::Prefix(Box::new(Constraint::Eq {
+                    field: format!("{}M", self.prefix),
+                    value: format!("{val:#x}"),
+                })));
+            }
+            pattern.push(Constraint::Operand(table.clone()));
+
+            Item::Constructor(Constructor {
+                table: String::new(),
+                display: DisplaySection::Operands(format!("^{table}")),
+                pattern: vec![pattern],
+                actions: vec![],
+                semantics,
+                layout: Layout::Inline,
+            })
+        };
+        let build = Stmt::Op(format!("build {table}"));
+
         if self.multi {
-            let mut instr = format!(
-                ":^{ifam}{id} is {}M=0x0 ... & {ifam}{id} {{ build {ifam}{id}; }}\n",
-                self.prefix,
-                ifam = self.name()
-            );
-            instr += &format!(
-                ":^{ifam}{id} is {}M=0x1 ... & {ifam}{id} {{ build {ifam}{id}; delayslot(4); }}\n",
-                self.prefix,
-                ifam = self.name()
-            );
-            instr
+            vec![
+                final_instr(Some(0), vec![build.clone()]),
+                final_instr(Some(1), vec![build, Stmt::Op(String::from("delayslot(4)"))]),
+            ]
         } else {
-            format!(
-                ":^{ifam}{id} is {ifam}{id} {{ build {ifam}{id}; }}\n",
-                ifam = self.name()
-            )
+            vec![final_instr(None, vec![build])]
         }
     }
 
-    fn build_final_instr(&self, id: &str) -> String {
+    fn build_final_instr(&self, id: &str) -> Vec<Item> {
         if id == "base" {
-            Self::build_id_final_instr(&self, "")
+            Self::build_id_final_instr(self, "")
         } else {
-            Self::build_id_final_instr(&self, id)
+            Self::build_id_final_instr(self, id)
         }
     }
 
-    fn build_all_final_instrs(&self) -> String {
-        let mut final_instrs = String::new();
+    fn build_all_final_instrs(&self) -> Vec<Item> {
+        let mut items = vec![];
 
-        for id in self.instructions.keys().sorted() {
-            final_instrs += &self.build_final_instr(id);
+        for id in self.instructions.keys() {
+            items.extend(self.build_final_instr(id));
         }
 
-        final_instrs
+        items
     }
 
-    pub fn build_head(&self) -> String {
-        let mut build = String::new();
-        build += &format!("{}\n", self.build_desc());
-        build += &format!("### Tokens ###\n\n{}\n", self.build_tokens());
+    pub fn build_head(&self) -> Document {
+        let mut doc = Document::new();
+
+        doc.items.extend(self.build_desc());
+        doc.items
+            .extend([Item::Blank, Self::section("Tokens"), Item::Blank]);
+        doc.items.extend(self.build_tokens());
+        doc.push(Item::Blank);
         if !self.variables.is_empty() {
-            build += &format!("### Variables ###\n\n{}\n\n", self.build_variables());
+            doc.items.extend([Self::section("Variables"), Item::Blank]);
+            doc.items.extend(self.build_variables());
+            doc.items.extend([Item::Blank, Item::Blank]);
         }
         if !self.pcodeops.is_empty() {
-            build += &format!("### Operations ###\n\n{}\n\n", self.build_pcodeops());
+            doc.items.extend([Self::section("Operations"), Item::Blank]);
+            doc.items.extend(self.build_pcodeops());
+            doc.items.extend([Item::Blank, Item::Blank]);
+        }
+        if !self.subtables.is_empty() {
+            doc.items.extend([Self::section("Sub-tables"), Item::Blank]);
+            doc.items.extend(self.build_subtables());
+            doc.push(Item::Blank);
         }
 
-        build
+        doc
+    }
+
+    pub fn document(&self) -> Result<Document, Error> {
+        let mut doc = self.build_head();
+
+        doc.items
+            .extend([Self::section("Instructions"), Item::Blank]);
+        doc.items.extend(self.build_all_instructions()?);
+        doc.items.extend([Item::Blank, Item::Blank]);
+        doc.items.extend(self.build_all_final_instrs());
+
+        Ok(doc)
+    }
+
+    pub fn build(&self) -> Result<String, Error> {
+        Ok(self.document()?.build())
     }
 
-    pub fn build(&self) -> String {
-        let mut build = String::new();
-        build += &self.build_head();
-        build += &format!(
-            "### Instructions ###\n\n{}\n\n{}",
-            self.build_all_instructions(),
-            self.build_all_final_instrs()
-        );
+    /// Macros called by the instructions of the family, sorted by name
+    pub fn macros(&self) -> Vec<Macro> {
+        let empty = Pattern::new([vec![], vec![], vec![], vec![]]);
+        let mut defs = vec![];
 
-        build
+        for instr in self.instrs() {
+            for def in instr.pcodes.macros() {
+                if !defs.iter().any(|known: &Macro| known.name == def.name()) {
+                    defs.push(Macro {
+                        name: def.name(),
+                        params: def.params().to_vec(),
+                        body: simplify(def.body()).statements(&empty, ""),
+                    });
+                }
+            }
+        }
+
+        defs.sort_by(|a, b| a.name.cmp(&b.name));
+        defs
     }
 
-    pub fn build_id_instrs(&self) -> Vec<(String, String)> {
+    pub fn build_id_instrs(&self) -> Result<Vec<(String, Document)>, Error> {
         let mut id_instrs = vec![];
 
-        for id in self.instructions.keys().sorted() {
-            id_instrs.push((
-                id.clone(),
-                format!(
-                    "### Instructions for {}: {id} ###\n\n{}\n\n{}",
-                    self.name(),
-                    self.build_instructions(id),
-                    self.build_final_instr(id)
-                ),
-            ));
+        for id in self.instructions.keys() {
+            let mut doc = Document::new();
+
+            doc.items.extend([
+                Self::section(&format!("Instructions for {}: {id}", self.name())),
+                Item::Blank,
+            ]);
+            doc.items.extend(self.build_instructions(id)?);
+            doc.items.extend([Item::Blank, Item::Blank]);
+            doc.items.extend(self.build_final_instr(id));
+
+            id_instrs.push((id.clone(), doc));
         }
 
-        id_instrs
+        Ok(id_instrs)
     }
 }
 
@@ -0,0 +1,385 @@
+//! Instruction families described as data instead of Rust factories.
+//!
+//! A definition file sets the family with top-level keys (`name`, `desc`,
+//! `prefix`, `size` in bits, the `pcodeops` it declares and the family it's
+//! listed `after`, it comes last otherwise), then lists:
+//!
+//! - `[[field]]`: the base pattern, most significant field first. Each field
+//!   has a `name`, a `len` in bits, the 16-bit `word` it's in (0 by default),
+//!   and a `type`: an integer mask, `"blank"`, `"any"`, `"uimm"`, `"simm"` or
+//!   the name of a register set (`"DReg"`, `"PReg"`...).
+//! - `[[variant]]`: one option of the variant `group` it names. Its other
+//!   keys set the type of a field when they're named after one, and define a
+//!   `{key}` placeholder otherwise.
+//! - `[[instr]]`: an instruction with its `name`, `display` template and
+//!   `semantics`, expanded over every combination of the variants of the
+//!   `groups` it lists (all of them by default). Like variants, it can set
+//!   fields.
+//!
+//! The semantics are written as a SLEIGH semantic section, `{field}` stands
+//! for a field of the instruction. Splitting or dividing fields, flags and
+//! shared macros are still only available to the Rust factories.
+
+mod schema;
+mod sem;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use itertools::Itertools;
+use toml::{Spanned, Table, Value};
+
+use crate::slaspec::error::Error;
+
+use super::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
+use super::expr::Expr;
+use super::pattern::{FieldType, ProtoField, ProtoPattern, RegisterSet};
+use schema::{FamilyDecl, FieldDecl, InstrDecl, VariantDecl, line_at};
+use sem::SemParser;
+
+fn fail<T>(line: usize, reason: String) -> Result<T, Error> {
+    Err(Error::Definition { line, reason })
+}
+
+fn field_type(val: &Value, line: usize) -> Result<FieldType, Error> {
+    match val {
+        Value::Integer(mask) => match u16::try_from(*mask) {
+            Ok(mask) => Ok(FieldType::Mask(mask)),
+            Err(_) => fail(line, format!("mask {mask:#x} doesn't fit in a field")),
+        },
+        Value::String(name) => match name.as_str() {
+            "blank" => Ok(FieldType::Blank),
+            "any" => Ok(FieldType::Any),
+            "uimm" => Ok(FieldType::UImmVal),
+            "simm" => Ok(FieldType::SImmVal),
+            _ => RegisterSet::from_name(name)
+                .map(FieldType::Variable)
+                .ok_or_else(|| Error::Definition {
+                    line,
+                    reason: format!("unknown field type `{name}`"),
+                }),
+        },
+        val => fail(
+            line,
+            format!("a field type can't be of type {}", val.type_str()),
+        ),
+    }
+}
+
+/// Replaces the `{name}` placeholders defined in `vars`, the others are
+/// fields and stay as they are.
+fn substitute(text: &str, vars: &HashMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        out += &rest[..start];
+        rest = &rest[start..];
+
+        let end = rest.find('}').map_or(rest.len(), |end| end + 1);
+        match rest[..end]
+            .strip_prefix('{')
+            .and_then(|name| name.strip_suffix('}'))
+            .and_then(|name| vars.get(name))
+        {
+            Some(val) => out += val,
+            None => out += &rest[..end],
+        }
+        rest = &rest[end..];
+    }
+
+    out + rest
+}
+
+/// Settings of a `[[variant]]` or an `[[instr]]`: field types and
+/// placeholders, from the keys the table doesn't declare itself.
+#[derive(Debug, Clone, Default)]
+struct Settings {
+    fields: Vec<(String, FieldType)>,
+    vars: HashMap<String, String>,
+}
+
+impl Settings {
+    fn from_table(table: &Table, line: usize, fields: &[String]) -> Result<Self, Error> {
+        let mut settings = Settings::default();
+
+        for (key, val) in table {
+            if fields.iter().any(|field| field == key) {
+                settings
+                    .fields
+                    .push((key.to_string(), field_type(val, line)?));
+            } else if let Value::String(val) = val {
+                settings.vars.insert(key.to_string(), val.clone());
+            } else {
+                return fail(line, format!("no field {key}"));
+            }
+        }
+
+        Ok(settings)
+    }
+
+    fn apply(&mut self, other: &Settings) {
+        self.fields.extend(other.fields.iter().cloned());
+        self.vars
+            .extend(other.vars.iter().map(|(k, v)| (k.clone(), v.clone())));
+    }
+}
+
+/// An instruction of the family, ready to be built
+#[derive(Debug, Clone)]
+struct InstrDef {
+    name: String,
+    display: String,
+    fields: Vec<(String, FieldType)>,
+    pcode: Option<Expr>,
+}
+
+struct DeclFactory {
+    instrs: Vec<InstrDef>,
+}
+
+impl InstrFactory for DeclFactory {
+    fn build_instrs(&self, ifam: &InstrFamilyBuilder) -> Vec<InstrBuilder> {
+        self.instrs
+            .iter()
+            .map(|def| {
+                def.fields.iter().fold(
+                    InstrBuilder::new(ifam)
+                        .name(&def.name)
+                        .display(def.display.clone())
+                        .add_pcode_opt(def.pcode.clone()),
+                    |instr, (field, ftype)| instr.set_field_type(field, ftype.clone()),
+                )
+            })
+            .collect()
+    }
+}
+
+/// The words of the base pattern, and the type of each field
+type BasePattern = (Vec<ProtoPattern>, Vec<(String, FieldType)>);
+
+fn base_pattern(
+    decls: &[Spanned<FieldDecl>],
+    words: usize,
+    text: &str,
+) -> Result<BasePattern, Error> {
+    let line = |span: std::ops::Range<usize>| line_at(text, span.start);
+    let mut patterns = vec![vec![]; words];
+    // Line of the first field of each word
+    let mut lines = vec![1; words];
+    let mut types = vec![];
+
+    for decl in decls {
+        let field = decl.get_ref();
+        let word = field.word.as_ref().map_or(0, |word| *word.get_ref());
+        let len = *field.len.get_ref();
+        let ftype = match &field.ftype {
+            Some(val) => field_type(val.get_ref(), line(val.span()))?,
+            None => FieldType::Blank,
+        };
+
+        match patterns.get_mut(word as usize) {
+            Some(fields) if word >= 0 && (1..=16).contains(&len) => {
+                if fields.is_empty() {
+                    lines[word as usize] = line(decl.span());
+                }
+                fields.push(ProtoField::new(&field.name, ftype.clone(), len as usize));
+                types.push((field.name.clone(), ftype));
+            }
+            Some(_) => {
+                return fail(
+                    line(field.len.span()),
+                    format!("a field can't have {len} bits"),
+                );
+            }
+            None => {
+                let span = field.word.as_ref().map_or(decl.span(), |word| word.span());
+                return fail(line(span), format!("no word {word}"));
+            }
+        }
+    }
+
+    let patterns = patterns
+        .into_iter()
+        .enumerate()
+        .map(|(word, fields)| {
+            let pattern = ProtoPattern::new(fields);
+            match pattern.len() {
+                16 => Ok(pattern),
+                len => fail(
+                    lines[word],
+                    format!("word {word} has {len} bits instead of 16"),
+                ),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((patterns, types))
+}
+
+fn instr_defs(
+    variants: &[Spanned<VariantDecl>],
+    instrs: &[Spanned<InstrDecl>],
+    base_types: &[(String, FieldType)],
+    text: &str,
+) -> Result<Vec<InstrDef>, Error> {
+    let line = |span: std::ops::Range<usize>| line_at(text, span.start);
+    let fields: Vec<String> = base_types.iter().map(|(field, _)| field.clone()).collect();
+
+    let mut groups: Vec<(String, Vec<Settings>)> = vec![];
+
+    for decl in variants {
+        let variant = decl.get_ref();
+        let settings = Settings::from_table(&variant.settings, line(decl.span()), &fields)?;
+
+        match groups.iter_mut().find(|(name, _)| *name == variant.group) {
+            Some((_, variants)) => variants.push(settings),
+            None => groups.push((variant.group.clone(), vec![settings])),
+        }
+    }
+
+    let mut defs = vec![];
+
+    for decl in instrs {
+        let table = decl.get_ref();
+        let instr = Settings::from_table(&table.settings, line(decl.span()), &fields)?;
+        let names = match &table.groups {
+            Some(names) => names.get_ref().clone(),
+            None => groups.iter().map(|(name, _)| name.clone()).collect(),
+        };
+        let selected = names
+            .iter()
+            .map(
+                |name| match groups.iter().find(|(group, _)| group == name) {
+                    Some((_, variants)) => Ok(variants.clone()),
+                    None => fail(
+                        line(table.groups.as_ref().map_or(decl.span(), |g| g.span())),
+                        format!("no variant group {name}"),
+                    ),
+                },
+            )
+            .collect::<Result<Vec<_>, _>>()?;
+        let (semantics, semantics_line) = match &table.semantics {
+            Some(semantics) => (semantics.get_ref().as_str(), line(semantics.span())),
+            None => ("", line(decl.span())),
+        };
+
+        let combinations: Vec<Vec<Settings>> = match selected.is_empty() {
+            true => vec![vec![]],
+            false => selected.into_iter().multi_cartesian_product().collect(),
+        };
+
+        for combination in combinations {
+            let mut settings = Settings::default();
+            for variant in &combination {
+                settings.apply(variant);
+            }
+            settings.apply(&instr);
+
+            let name = substitute(&table.name, &settings.vars);
+            let regs: HashMap<String, bool> = base_types
+                .iter()
+                .chain(&settings.fields)
+                .map(|(field, ftype)| (field.clone(), matches!(ftype, FieldType::Variable(_))))
+                .collect();
+
+            let pcode = SemParser::parse(&substitute(semantics, &settings.vars), &regs).map_err(
+                |reason| Error::Definition {
+                    line: semantics_line,
+                    reason: format!("semantics of {name}: {reason}"),
+                },
+            )?;
+
+            defs.push(InstrDef {
+                display: substitute(&table.display, &settings.vars),
+                fields: settings.fields,
+                name,
+                pcode,
+            });
+        }
+    }
+
+    Ok(defs)
+}
+
+/// A family loaded from a definition file
+pub struct Definition {
+    /// Size of the instructions in bits
+    pub size: usize,
+    /// Family the new one is listed after
+    pub after: Option<String>,
+    pub ifam: InstrFamilyBuilder,
+}
+
+/// Builds the family described by the definition `text`
+pub fn parse_family(text: &str) -> Result<Definition, Error> {
+    let decl = FamilyDecl::parse(text)?;
+    let size = *decl.size.get_ref();
+    let words = match size {
+        16 | 32 | 64 => size as usize / 16,
+        _ => {
+            return fail(
+                line_at(text, decl.size.span().start),
+                format!("no {size}-bit instructions"),
+            );
+        }
+    };
+
+    let (mut base, base_types) = base_pattern(&decl.field, words, text)?;
+    let instrs = instr_defs(&decl.variant, &decl.instr, &base_types, text)?;
+    let (name, desc, prefix) = (&decl.name, &decl.desc, &decl.prefix);
+
+    let mut ifam = match words {
+        1 => InstrFamilyBuilder::new_16(name, desc, prefix, base.remove(0)),
+        2 => InstrFamilyBuilder::new_32(name, desc, prefix, [base.remove(0), base.remove(0)]),
+        _ => InstrFamilyBuilder::new_64(
+            name,
+            desc,
+            prefix,
+            [
+                base.remove(0),
+                base.remove(0),
+                base.remove(0),
+                base.remove(0),
+            ],
+        ),
+    };
+
+    for pcodeop in &decl.pcodeops {
+        ifam.add_pcodeop(pcodeop);
+    }
+    ifam.add_instrs(&DeclFactory { instrs });
+
+    Ok(Definition {
+        size: size as usize,
+        after: decl.after,
+        ifam,
+    })
+}
+
+/// Loads the `.toml` family definitions of `dir` in the order of their
+/// names, there are none when it doesn't exist.
+pub fn load_dir(dir: &Path) -> Result<Vec<Definition>, Error> {
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let entries = fs::read_dir(dir).map_err(|e| Error::io(dir, e))?;
+    let mut paths = vec![];
+    for entry in entries {
+        let path = entry.map_err(|e| Error::io(dir, e))?.path();
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            paths.push(path);
+        }
+    }
+
+    paths
+        .into_iter()
+        .sorted()
+        .map(|path| {
+            let text = fs::read_to_string(&path).map_err(|e| Error::io(&path, e))?;
+            parse_family(&text).map_err(|err| err.in_file(&path))
+        })
+        .collect()
+}
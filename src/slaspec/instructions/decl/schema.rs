@@ -0,0 +1,72 @@
+use serde::Deserialize;
+use toml::{Spanned, Table, Value};
+
+use crate::slaspec::error::Error;
+
+/// A definition file, the tables of each header in the order they are written
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FamilyDecl {
+    pub name: String,
+    pub desc: String,
+    pub prefix: String,
+    pub size: Spanned<i64>,
+    #[serde(default)]
+    pub pcodeops: Vec<String>,
+    pub after: Option<String>,
+    #[serde(default)]
+    pub field: Vec<Spanned<FieldDecl>>,
+    #[serde(default)]
+    pub variant: Vec<Spanned<VariantDecl>>,
+    #[serde(default)]
+    pub instr: Vec<Spanned<InstrDecl>>,
+}
+
+/// A field of the base pattern
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldDecl {
+    pub name: String,
+    pub len: Spanned<i64>,
+    pub word: Option<Spanned<i64>>,
+    #[serde(rename = "type")]
+    pub ftype: Option<Spanned<Value>>,
+}
+
+/// An option of the variant `group`, the other keys are its settings
+#[derive(Debug, Deserialize)]
+pub struct VariantDecl {
+    pub group: String,
+    #[serde(flatten)]
+    pub settings: Table,
+}
+
+/// An instruction, the keys not listed here are its settings
+#[derive(Debug, Deserialize)]
+pub struct InstrDecl {
+    pub name: String,
+    #[serde(default)]
+    pub display: String,
+    pub semantics: Option<Spanned<String>>,
+    pub groups: Option<Spanned<Vec<String>>>,
+    #[serde(flatten)]
+    pub settings: Table,
+}
+
+/// Line of the byte `offset` of `text`
+pub fn line_at(text: &str, offset: usize) -> usize {
+    text.as_bytes()[..offset.min(text.len())]
+        .iter()
+        .filter(|&&c| c == b'\n')
+        .count()
+        + 1
+}
+
+impl FamilyDecl {
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        toml::from_str(text).map_err(|err| Error::Definition {
+            line: err.span().map_or(1, |span| line_at(text, span.start)),
+            reason: err.message().to_string(),
+        })
+    }
+}
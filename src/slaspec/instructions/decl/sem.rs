@@ -0,0 +1,386 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::slaspec::globals::DEFAULT_MEM;
+use crate::slaspec::instructions::expr::{Expr, Op};
+use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::registers::register_file;
+
+// Longest first, so that `s<=` isn't read as `s<` followed by `=`
+const PUNCTS: [&str; 33] = [
+    "s>>", "s<=", "s>=", "s<", "s>", "||", "^^", "&&", "==", "!=", "<=", ">=", "<<", ">>", "=",
+    "<", ">", "+", "-", "*", "%", "&", "|", "^", "~", "!", "(", ")", "[", "]", ",", ";", ":",
+];
+
+// Binary operators from the loosest to the tightest
+const LEVELS: [&[(&str, Op)]; 11] = [
+    &[("||", Op::Or)],
+    &[("^^", Op::Xor)],
+    &[("&&", Op::And)],
+    &[("|", Op::BitOr)],
+    &[("^", Op::BitXor)],
+    &[("&", Op::BitAnd)],
+    &[("==", Op::EQ), ("!=", Op::NE)],
+    &[
+        ("<", Op::LT),
+        (">", Op::GT),
+        ("<=", Op::LE),
+        (">=", Op::GE),
+        ("s<", Op::LTS),
+        ("s>", Op::GTS),
+        ("s<=", Op::LES),
+        ("s>=", Op::GES),
+    ],
+    &[("<<", Op::LShft), (">>", Op::RShft), ("s>>", Op::ARShft)],
+    &[("+", Op::Plus), ("-", Op::Minus)],
+    &[("*", Op::Mult), ("%", Op::Rem)],
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Num(i128),
+    Ident(String),
+    Field(String),
+    Punct(&'static str),
+}
+
+impl Tok {
+    fn text(&self) -> String {
+        match self {
+            Tok::Num(val) => format!("{val:#x}"),
+            Tok::Ident(id) => id.clone(),
+            Tok::Field(id) => format!("{{{id}}}"),
+            Tok::Punct(punct) => punct.to_string(),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Tok>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut toks = vec![];
+    let mut pos = 0;
+
+    while pos < chars.len() {
+        let c = chars[pos];
+        let rest: String = chars[pos..].iter().take(3).collect();
+
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c == '{' {
+            let end = chars[pos..]
+                .iter()
+                .position(|c| *c == '}')
+                .ok_or_else(|| String::from("unterminated field `{`"))?;
+            toks.push(Tok::Field(chars[pos + 1..pos + end].iter().collect()));
+            pos += end + 1;
+        } else if c.is_ascii_digit() {
+            let len = chars[pos..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                .count();
+            let text: String = chars[pos..pos + len]
+                .iter()
+                .filter(|c| **c != '_')
+                .collect();
+            let val = match text.strip_prefix("0x") {
+                Some(hex) => i128::from_str_radix(hex, 16),
+                None => text.parse(),
+            };
+            toks.push(Tok::Num(
+                val.map_err(|_| format!("`{text}` isn't a number"))?,
+            ));
+            pos += len;
+        } else if let Some(punct) = PUNCTS.iter().find(|punct| rest.starts_with(**punct)) {
+            toks.push(Tok::Punct(punct));
+            pos += punct.len();
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = chars[pos..]
+                .iter()
+                .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.'))
+                .count();
+            toks.push(Tok::Ident(chars[pos..pos + len].iter().collect()));
+            pos += len;
+        } else {
+            return Err(format!("unexpected `{c}`"));
+        }
+    }
+
+    Ok(toks)
+}
+
+/// Parses the p-code of a declarative instruction, written the way SLEIGH
+/// semantic sections are: `{field}` is a field of the instruction, any other
+/// name a register or a local.
+///
+/// Parentheses are kept as groups, the printed text then groups operands the
+/// same way the source did.
+pub struct SemParser<'a> {
+    toks: Vec<Tok>,
+    pos: usize,
+    fields: &'a HashMap<String, bool>,
+    locals: HashSet<String>,
+}
+
+impl<'a> SemParser<'a> {
+    /// `fields` tells the fields of the instruction, and whether each one
+    /// is attached to registers.
+    pub fn parse(text: &str, fields: &'a HashMap<String, bool>) -> Result<Option<Expr>, String> {
+        let mut parser = SemParser {
+            toks: tokenize(text)?,
+            pos: 0,
+            fields,
+            locals: HashSet::new(),
+        };
+        let mut stmts = vec![];
+
+        while parser.peek().is_some() {
+            stmts.push(parser.stmt()?);
+        }
+
+        Ok((!stmts.is_empty()).then(|| cs_mline(stmts)))
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&Tok> {
+        self.toks.get(self.pos + offset)
+    }
+
+    fn next(&mut self) -> Result<Tok, String> {
+        let tok = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| String::from("unexpected end of the semantics"))?;
+        self.pos += 1;
+        Ok(tok)
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Punct(found)) if *found == punct)
+    }
+
+    fn is_ident(&self, ident: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Ident(found)) if found == ident)
+    }
+
+    fn expect(&mut self, punct: &str) -> Result<(), String> {
+        match self.next()? {
+            Tok::Punct(found) if found == punct => Ok(()),
+            tok => Err(format!("expected `{punct}`, found `{}`", tok.text())),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        match self.next()? {
+            Tok::Ident(id) => Ok(id),
+            tok => Err(format!("expected a name, found `{}`", tok.text())),
+        }
+    }
+
+    fn size(&mut self) -> Result<usize, String> {
+        match self.next()? {
+            Tok::Num(val) if val > 0 => Ok(val as usize),
+            tok => Err(format!("expected a size, found `{}`", tok.text())),
+        }
+    }
+
+    fn stmt(&mut self) -> Result<Expr, String> {
+        if self.is_punct("<") {
+            return self.label();
+        }
+
+        let stmt = if self.is_ident("local") {
+            self.pos += 1;
+            let var = self.ident()?;
+            self.expect(":")?;
+            let local = e_local(&var, self.size()?);
+            self.locals.insert(var);
+
+            match self.is_punct("=") {
+                true => {
+                    self.pos += 1;
+                    e_copy(local, self.expr()?)
+                }
+                false => local,
+            }
+        } else if self.is_ident("goto") {
+            self.pos += 1;
+            b_goto(self.target()?)
+        } else if self.is_ident("if") {
+            self.pos += 1;
+            let cond = match self.expr()? {
+                Expr::Group { expr } => *expr,
+                cond => cond,
+            };
+            if !self.is_ident("goto") {
+                return Err(String::from("expected `goto` after the condition"));
+            }
+            self.pos += 1;
+            b_ifgoto(cond, self.target()?)
+        } else if self.is_ident("call") {
+            self.pos += 1;
+            b_call(self.target()?)
+        } else if self.is_ident("return") {
+            self.pos += 1;
+            b_ret(self.target()?)
+        } else {
+            let lhs = self.expr()?;
+            match self.is_punct("=") {
+                true => {
+                    self.pos += 1;
+                    e_copy(lhs, self.expr()?)
+                }
+                false => lhs,
+            }
+        };
+
+        self.expect(";")?;
+        Ok(stmt)
+    }
+
+    fn label(&mut self) -> Result<Expr, String> {
+        self.expect("<")?;
+        let label = b_label(&self.ident()?);
+        self.expect(">")?;
+        Ok(label)
+    }
+
+    /// Destination of a branch: a label, an indirect address or a location
+    fn target(&mut self) -> Result<Expr, String> {
+        if self.is_punct("<") {
+            self.label()
+        } else if self.is_punct("[") {
+            self.pos += 1;
+            let addr = self.expr()?;
+            self.expect("]")?;
+            Ok(b_indirect(addr))
+        } else {
+            self.unary()
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        self.binary(0)
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+
+        while let Some(op) = LEVELS[level].iter().find_map(|(punct, op)| {
+            matches!(self.peek(), Some(Tok::Punct(found)) if found == punct).then(|| op.clone())
+        }) {
+            self.pos += 1;
+            lhs = b_bin(lhs, op, self.binary(level + 1)?);
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let op = match self.peek() {
+            Some(Tok::Punct("-")) => Op::Minus,
+            Some(Tok::Punct("~")) => Op::BitNot,
+            Some(Tok::Punct("!")) => Op::Bang,
+            Some(Tok::Punct("&")) => {
+                self.pos += 1;
+                return Ok(b_ref(self.unary()?));
+            }
+            Some(Tok::Punct("*")) => {
+                self.pos += 1;
+                return self.ptr();
+            }
+            _ => return self.postfix(),
+        };
+
+        self.pos += 1;
+        Ok(b_un(op, self.unary()?))
+    }
+
+    /// `*[space]:size addr`, the space defaults to the RAM
+    fn ptr(&mut self) -> Result<Expr, String> {
+        let space = match self.is_punct("[") {
+            true => {
+                self.pos += 1;
+                let space = self.ident()?;
+                self.expect("]")?;
+                space
+            }
+            false => DEFAULT_MEM.to_string(),
+        };
+        self.expect(":")?;
+        let size = self.size()?;
+
+        Ok(b_ptr(&space, self.unary()?, size))
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let mut expr = self.atom()?;
+
+        loop {
+            if self.is_punct(":") {
+                self.pos += 1;
+                expr = b_size(expr, self.size()?);
+            } else if self.is_punct("(")
+                && matches!(self.peek_at(1), Some(Tok::Num(_)))
+                && matches!(self.peek_at(2), Some(Tok::Punct(")")))
+                && matches!(
+                    expr,
+                    Expr::Var { .. } | Expr::Reg { .. } | Expr::Field { .. }
+                )
+            {
+                self.pos += 1;
+                expr = b_trunc(expr, self.size()?);
+                self.expect(")")?;
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next()? {
+            Tok::Num(val) => Ok(b_num(val)),
+            Tok::Field(id) => match self.fields.get(&id) {
+                Some(is_reg) => Ok(b_field(&id, *is_reg)),
+                None => Err(format!("no field {id}")),
+            },
+            Tok::Punct("(") => {
+                let expr = self.expr()?;
+                self.expect(")")?;
+                Ok(b_grp(expr))
+            }
+            Tok::Ident(id) if self.is_punct("(") && !self.is_trunc(&id) => {
+                self.pos += 1;
+                let mut params = vec![];
+
+                while !self.is_punct(")") {
+                    params.push(self.expr()?);
+                    if !self.is_punct(")") {
+                        self.expect(",")?;
+                    }
+                }
+                self.pos += 1;
+
+                Ok(b_mac(&id, params))
+            }
+            Tok::Ident(id) => match register_file().size_of(&id) {
+                Some(_) => Ok(b_reg(&id)),
+                None => Ok(b_var(&id)),
+            },
+            tok => Err(format!("unexpected `{}`", tok.text())),
+        }
+    }
+
+    /// `id(n)` truncates a register or a local, a macro can't be named
+    /// after one
+    fn is_trunc(&self, id: &str) -> bool {
+        (register_file().size_of(id).is_some() || self.locals.contains(id))
+            && matches!(self.peek_at(1), Some(Tok::Num(_)))
+            && matches!(self.peek_at(2), Some(Tok::Punct(")")))
+    }
+}
@@ -1,6 +1,9 @@
 use std::collections::HashSet;
+use std::fmt;
+use std::rc::Rc;
 
 use super::{expr_util::*, pattern::Pattern, util::capitalize};
+use crate::slaspec::sleigh::Stmt;
 
 #[derive(Debug, Clone)]
 pub enum Op {
@@ -91,6 +94,12 @@ pub enum Expr {
         id: String,
         params: Vec<Box<Expr>>,
     },
+    /// Call of a code snippet emitted once as a SLEIGH macro
+    MacroCall {
+        mac: Rc<MacroDef>,
+        args: Vec<Expr>,
+        id: String,
+    },
     Indirect {
         val: Box<Expr>,
     },
@@ -143,28 +152,109 @@ pub enum Expr {
     Group {
         expr: Box<Expr>,
     },
+    Build {
+        table: Box<Expr>,
+    },
+}
+
+type MacroBody = dyn Fn(Vec<Expr>, &str) -> Expr;
+
+/// A code snippet shared through a SLEIGH macro. The body is built from the
+/// arguments and an id keeping its labels and locals apart, the same way the
+/// snippet would be inlined.
+pub struct MacroDef {
+    name: String,
+    params: Vec<String>,
+    body: Box<MacroBody>,
+}
+
+impl fmt::Debug for MacroDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "macro {}({})", self.name, self.params.join(", "))
+    }
+}
+
+impl MacroDef {
+    pub fn new(name: &str, params: &[&str], body: Box<MacroBody>) -> Self {
+        MacroDef {
+            name: name.to_string(),
+            params: params.iter().map(|param| param.to_string()).collect(),
+            body,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn params(&self) -> &[String] {
+        &self.params
+    }
+
+    /// Body of the definition, on the parameters
+    pub fn body(&self) -> Code {
+        let mut code = Code::new();
+        code.add_expr((self.body)(
+            self.params.iter().map(|param| b_var(param)).collect(),
+            "mac",
+        ));
+        code
+    }
+
+    pub fn expand(&self, args: Vec<Expr>, id: &str) -> Expr {
+        (self.body)(args, id)
+    }
 }
 
 impl Expr {
-    pub fn build(&self, pattern: &Pattern, prefix: &str) -> String {
+    /// Replaces the macro calls with the code they stand for
+    pub fn inline_macros(self) -> Expr {
+        match self {
+            Expr::MacroCall { mac, args, id } => mac.expand(args, &id).inline_macros(),
+            Expr::Line { current, next } => b_line(
+                current.inline_macros(),
+                next.map(|line| line.inline_macros()),
+            ),
+            expr => expr,
+        }
+    }
+
+    /// Collects the macros called by the expression
+    pub fn macros(&self, macros: &mut Vec<Rc<MacroDef>>) {
         match self {
+            Expr::MacroCall { mac, .. } if !macros.iter().any(|known| known.name == mac.name) => {
+                macros.push(mac.clone());
+            }
             Expr::Line { current, next } => {
-                return format!(
-                    "{}{}",
-                    match &**current {
-                        Expr::Label { id: _ } => format!("\n{}", current.build(pattern, prefix)),
-                        Expr::Line {
-                            current: _,
-                            next: _,
-                        } => current.build(pattern, prefix),
-                        _ => format!("\n\t{};", current.build(pattern, prefix)),
-                    },
-                    if let Some(line) = next {
-                        line.build(pattern, prefix)
-                    } else {
-                        "".to_string()
-                    }
-                );
+                current.macros(macros);
+                if let Some(line) = next {
+                    line.macros(macros);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Flattens the expression into the statements of a SLEIGH block
+    pub fn statements(&self, pattern: &Pattern, prefix: &str, stmts: &mut Vec<Stmt>) {
+        match self {
+            Expr::Line { current, next } => {
+                current.statements(pattern, prefix, stmts);
+                if let Some(line) = next {
+                    line.statements(pattern, prefix, stmts);
+                }
+            }
+            Expr::Label { .. } => stmts.push(Stmt::Label(self.build(pattern, prefix))),
+            _ => stmts.push(Stmt::Op(self.build(pattern, prefix))),
+        }
+    }
+
+    pub fn build(&self, pattern: &Pattern, prefix: &str) -> String {
+        match self {
+            Expr::Line { .. } => {
+                let mut stmts = Vec::new();
+                self.statements(pattern, prefix, &mut stmts);
+                return stmts.iter().map(|stmt| stmt.build()).collect();
             }
             Expr::Field { id, is_reg: _ } => {
                 if let Some(f) = pattern.get_field(id) {
@@ -183,6 +273,11 @@ impl Expr {
 
                 return format!("{id}({})", params_str.join(", "));
             }
+            Expr::MacroCall { mac, args, .. } => {
+                let args: Vec<String> = args.iter().map(|a| a.build(pattern, prefix)).collect();
+
+                return format!("{}({})", mac.name, args.join(", "));
+            }
             Expr::Label { id } => return format!("<{id}>"),
             Expr::Indirect { val } => return format!("[{}]", val.build(pattern, prefix)),
             Expr::Local { var, size } => {
@@ -230,76 +325,174 @@ impl Expr {
                     goto.build(pattern, prefix)
                 );
             }
+            Expr::Build { table } => {
+                return format!("build {}", table.build(pattern, prefix));
+            }
         }
 
         String::new()
     }
 
-    pub fn multify(self, prefix: &str, rhs_cpy: bool, regs: &mut HashSet<(bool, String)>) -> Expr {
+    fn reg_key(&self, prefix: &str) -> Option<(bool, String)> {
         match self {
-            Expr::Line { current, next } => b_line(
-                current.multify(prefix, rhs_cpy, regs),
-                if let Some(val) = next {
-                    Some(val.multify(prefix, rhs_cpy, regs))
-                } else {
-                    None
-                },
-            ),
-            Expr::Field { id, is_reg } => {
-                if is_reg && rhs_cpy {
-                    regs.insert((true, format!("{}{}", prefix, &capitalize(&id))));
-                    b_var(&format!("old_{}{}Reg", prefix, &capitalize(&id)))
-                } else {
-                    b_field(&format!("{}{}", prefix, &capitalize(&id)), is_reg)
-                }
+            Expr::Field { id, is_reg: true } => {
+                Some((true, format!("{}{}", prefix, &capitalize(id))))
             }
-            Expr::Var { id } => b_var(&format!("{}{}", prefix, &capitalize(&id))),
-            Expr::Reg { id } => {
-                if rhs_cpy {
-                    regs.insert((false, id.clone()));
-                    b_var(&format!("old_{id}"))
-                } else {
-                    b_reg(&id)
+            Expr::Reg { id } => Some((false, id.clone())),
+            _ => None,
+        }
+    }
+
+    fn multify_dst(
+        self,
+        prefix: &str,
+        regs: &mut HashSet<(bool, String)>,
+        written: &mut HashSet<(bool, String)>,
+    ) -> Expr {
+        match self {
+            // The address of a store is read, only the memory is written
+            Expr::Ptr { space, addr, size } => {
+                b_ptr(&space, addr.multify(prefix, true, regs, written), size)
+            }
+            Expr::Size { var, size } => b_size(var.multify_dst(prefix, regs, written), size),
+            Expr::Local { var, size } => b_local(var.multify(prefix, false, regs, written), size),
+            dst => {
+                if let Some(key) = dst.reg_key(prefix) {
+                    written.insert(key);
                 }
+                dst.multify(prefix, false, regs, written)
+            }
+        }
+    }
+
+    /// Renames the fields, variables and labels of a slot with its prefix, and
+    /// replaces the registers it reads with copies taken before the bundle.
+    /// Registers already written by the slot itself keep their new value.
+    pub fn multify(
+        self,
+        prefix: &str,
+        rhs_cpy: bool,
+        regs: &mut HashSet<(bool, String)>,
+        written: &mut HashSet<(bool, String)>,
+    ) -> Expr {
+        if let Some(key) = self.reg_key(prefix)
+            && rhs_cpy
+            && !written.contains(&key)
+        {
+            let old = match &key {
+                (true, id) => format!("old_{id}Reg"),
+                (false, id) => format!("old_{}", id.replace('.', "_")),
+            };
+            regs.insert(key);
+            return b_var(&old);
+        }
+
+        match self {
+            // Every slot names its own labels and locals, so the snippet is inlined
+            Expr::MacroCall { .. } => self.inline_macros().multify(prefix, rhs_cpy, regs, written),
+            Expr::Line { current, next } => {
+                let current = current.multify(prefix, rhs_cpy, regs, written);
+                b_line(
+                    current,
+                    next.map(|val| val.multify(prefix, rhs_cpy, regs, written)),
+                )
             }
+            Expr::Field { id, is_reg } => {
+                b_field(&format!("{}{}", prefix, &capitalize(&id)), is_reg)
+            }
+            Expr::Var { id } => match id.as_str() {
+                "inst_start" | "inst_next" => b_var(&id),
+                _ => b_var(&format!("{}{}", prefix, &capitalize(&id))),
+            },
+            Expr::Reg { id } => b_reg(&id),
             Expr::Number { val } => b_num(val),
             Expr::Macro { id, params } => b_mac(
                 &id,
                 params
                     .into_iter()
-                    .map(|e| e.multify(prefix, rhs_cpy, regs))
+                    .map(|e| e.multify(prefix, rhs_cpy, regs, written))
                     .collect(),
             ),
-            Expr::Label { id } => b_label(&capitalize(&id)),
-            Expr::Indirect { val } => b_indirect(val.multify(prefix, rhs_cpy, regs)),
-            Expr::Local { var, size } => b_local(var.multify(prefix, rhs_cpy, regs), size),
-            Expr::Unary { op, expr } => b_un(op, expr.multify(prefix, rhs_cpy, regs)),
+            Expr::Label { id } => b_label(&format!("{}{}", prefix, &capitalize(&id))),
+            Expr::Indirect { val } => b_indirect(val.multify(prefix, true, regs, written)),
+            Expr::Local { var, size } => b_local(var.multify(prefix, rhs_cpy, regs, written), size),
+            Expr::Unary { op, expr } => b_un(op, expr.multify(prefix, rhs_cpy, regs, written)),
             Expr::Binary { lhs, op, rhs } => match op {
-                Op::Copy => b_bin(
-                    lhs.multify(prefix, false, regs),
-                    op,
-                    rhs.multify(prefix, true, regs),
-                ),
-                _ => b_bin(
-                    lhs.multify(prefix, rhs_cpy, regs),
-                    op,
-                    rhs.multify(prefix, rhs_cpy, regs),
-                ),
+                Op::Copy => {
+                    // Sources are read before the destination is written
+                    let rhs = rhs.multify(prefix, true, regs, written);
+                    b_bin(lhs.multify_dst(prefix, regs, written), op, rhs)
+                }
+                _ => {
+                    let lhs = lhs.multify(prefix, rhs_cpy, regs, written);
+                    b_bin(lhs, op, rhs.multify(prefix, rhs_cpy, regs, written))
+                }
             },
-            Expr::Size { var, size } => b_size(var.multify(prefix, rhs_cpy, regs), size),
-            Expr::Trunc { var, size } => b_trunc(var.multify(prefix, rhs_cpy, regs), size),
+            Expr::Size { var, size } => b_size(var.multify(prefix, rhs_cpy, regs, written), size),
+            Expr::Trunc { var, size } => b_trunc(var.multify(prefix, rhs_cpy, regs, written), size),
             Expr::Ptr { space, addr, size } => {
-                b_ptr(&space, addr.multify(prefix, rhs_cpy, regs), size)
+                b_ptr(&space, addr.multify(prefix, true, regs, written), size)
             }
-            Expr::Ref { var } => b_ref(var.multify(prefix, rhs_cpy, regs)),
-            Expr::Return { addr } => b_ret(addr.multify(prefix, rhs_cpy, regs)),
-            Expr::Goto { dest } => b_goto(dest.multify(prefix, rhs_cpy, regs)),
-            Expr::Call { addr } => b_call(addr.multify(prefix, rhs_cpy, regs)),
-            Expr::Group { expr } => b_grp(expr.multify(prefix, rhs_cpy, regs)),
-            Expr::IfGoto { cond, goto } => b_ifgoto(
-                cond.multify(prefix, rhs_cpy, regs),
-                goto.multify(prefix, rhs_cpy, regs),
-            ),
+            // Taking the address of a register doesn't read it
+            Expr::Ref { var } => b_ref(var.multify(prefix, false, regs, written)),
+            Expr::Return { addr } => b_ret(addr.multify(prefix, true, regs, written)),
+            Expr::Goto { dest } => b_goto(dest.multify(prefix, true, regs, written)),
+            Expr::Call { addr } => b_call(addr.multify(prefix, true, regs, written)),
+            Expr::Group { expr } => b_grp(expr.multify(prefix, rhs_cpy, regs, written)),
+            Expr::IfGoto { cond, goto } => {
+                let cond = cond.multify(prefix, true, regs, written);
+                b_ifgoto(cond, goto.multify(prefix, true, regs, written))
+            }
+            Expr::Build { table } => b_build(table.multify(prefix, false, regs, written)),
+        }
+    }
+
+    pub fn stores(&self) -> bool {
+        match self {
+            Expr::Line { current, next } => {
+                current.stores() || next.as_ref().is_some_and(|val| val.stores())
+            }
+            Expr::Binary {
+                lhs,
+                op: Op::Copy,
+                rhs: _,
+            } => matches!(**lhs, Expr::Ptr { .. }),
+            Expr::MacroCall { mac, args, id } => mac.expand(args.clone(), id).stores(),
+            _ => false,
+        }
+    }
+
+    /// Rough number of p-code ops the SLEIGH compiler emits for the expression
+    pub fn op_count(&self) -> usize {
+        match self {
+            Expr::Line { current, next } => {
+                current.op_count() + next.as_ref().map_or(0, |val| val.op_count())
+            }
+            // The last op of the source writes the destination directly
+            Expr::Binary {
+                lhs,
+                op: Op::Copy,
+                rhs,
+            } => lhs.op_count() + rhs.op_count().max(1),
+            Expr::Binary { lhs, op: _, rhs } => 1 + lhs.op_count() + rhs.op_count(),
+            Expr::Unary { op: _, expr } => 1 + expr.op_count(),
+            Expr::Macro { id: _, params } => 1 + params.iter().map(|p| p.op_count()).sum::<usize>(),
+            Expr::Size { var, size: _ } | Expr::Trunc { var, size: _ } => 1 + var.op_count(),
+            Expr::Ptr { addr, .. } => 1 + addr.op_count(),
+            Expr::Return { addr } | Expr::Call { addr } => 1 + addr.op_count(),
+            Expr::Goto { dest } => 1 + dest.op_count(),
+            Expr::IfGoto { cond, goto: _ } => 1 + cond.op_count(),
+            Expr::Build { table: _ } => 1,
+            // The SLEIGH compiler expands macros in every constructor using them
+            Expr::MacroCall { mac, args, id } => mac.expand(args.clone(), id).op_count(),
+            Expr::Local { var, size: _ } | Expr::Ref { var } => var.op_count(),
+            Expr::Group { expr } => expr.op_count(),
+            Expr::Indirect { val } => val.op_count(),
+            Expr::Field { .. }
+            | Expr::Var { .. }
+            | Expr::Reg { .. }
+            | Expr::Number { .. }
+            | Expr::Label { .. } => 0,
         }
     }
 }
@@ -322,33 +515,67 @@ impl Code {
         self.exprs.is_empty()
     }
 
-    pub fn build(&self, pattern: &Pattern, prefix: &str) -> String {
-        let mut out = String::new();
+    pub fn exprs(&self) -> &[Expr] {
+        &self.exprs
+    }
+
+    pub fn inline_macros(&self) -> Code {
+        Code {
+            exprs: self
+                .exprs
+                .iter()
+                .map(|e| e.clone().inline_macros())
+                .collect(),
+        }
+    }
+
+    pub fn macros(&self) -> Vec<Rc<MacroDef>> {
+        let mut macros = Vec::new();
 
         for ex in &self.exprs {
-            match ex {
-                Expr::Label { id: _ } => out += &format!("\n{}", ex.build(pattern, prefix)),
-                Expr::Line {
-                    current: _,
-                    next: _,
-                } => out += &ex.build(pattern, prefix),
-                _ => out += &format!("\n\t{};", ex.build(pattern, prefix)),
-            }
+            ex.macros(&mut macros);
         }
 
-        out
+        macros
     }
 
-    pub fn multify(self, prefix: &str, rhs: bool, regs: &mut HashSet<(bool, String)>) -> Code {
+    pub fn statements(&self, pattern: &Pattern, prefix: &str) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+
+        for ex in &self.exprs {
+            ex.statements(pattern, prefix, &mut stmts);
+        }
+
+        stmts
+    }
+
+    pub fn build(&self, pattern: &Pattern, prefix: &str) -> String {
+        self.statements(pattern, prefix)
+            .iter()
+            .map(|stmt| stmt.build())
+            .collect()
+    }
+
+    pub fn multify(self, prefix: &str, regs: &mut HashSet<(bool, String)>) -> Code {
+        let mut written = HashSet::new();
+
         Code {
             exprs: self
                 .exprs
                 .into_iter()
-                .map(|e| e.multify(prefix, rhs, regs))
+                .map(|e| e.multify(prefix, false, regs, &mut written))
                 .collect(),
         }
     }
 
+    pub fn stores(&self) -> bool {
+        self.exprs.iter().any(|e| e.stores())
+    }
+
+    pub fn op_count(&self) -> usize {
+        self.exprs.iter().map(|e| e.op_count()).sum()
+    }
+
     pub fn append(&mut self, mut code: Code) {
         self.exprs.append(&mut code.exprs);
     }
@@ -1,10 +1,12 @@
 use std::collections::VecDeque;
+use std::rc::Rc;
 
-use crate::slaspec::globals::DEFAULT_MEM;
+use crate::slaspec::globals::{DEFAULT_MEM, REGISTER_SPACE};
+use crate::slaspec::registers::register_file;
 
 use super::{
     common::BinOp,
-    expr::{Expr, Op},
+    expr::{Expr, MacroDef, Op},
 };
 
 //// Base operations ////
@@ -26,6 +28,12 @@ pub fn b_grp(expr: Expr) -> Expr {
     }
 }
 
+pub fn b_build(table: Expr) -> Expr {
+    Expr::Build {
+        table: Box::new(table),
+    }
+}
+
 // Var operations
 pub fn b_field(id: &str, is_reg: bool) -> Expr {
     Expr::Field {
@@ -382,7 +390,57 @@ pub fn cs_smax_num(size: usize) -> Expr {
     b_grp(e_sub(cs_smin_num(size), b_num(1)))
 }
 
+/// Calls the snippet `body` through the SLEIGH macro `name`. The first
+/// argument is written, so it has to be a register, the others registers or
+/// constants: the macro would copy anything else into a temporary. When they
+/// aren't, the snippet is inlined.
+pub fn cs_macro<const N: usize>(
+    name: &str,
+    params: [&str; N],
+    args: [Expr; N],
+    id: &str,
+    body: impl Fn([Expr; N], &str) -> Expr + 'static,
+) -> Expr {
+    let is_reg = |e: &Expr| matches!(e, Expr::Field { is_reg: true, .. } | Expr::Reg { .. });
+    let is_val = |e: &Expr| {
+        is_reg(e)
+            || matches!(
+                e,
+                Expr::Var { .. } | Expr::Field { .. } | Expr::Number { .. }
+            )
+    };
+
+    if !args.first().is_some_and(is_reg) || !args.iter().skip(1).all(is_val) {
+        return body(args, id);
+    }
+
+    let mac = MacroDef::new(
+        name,
+        &params,
+        Box::new(move |args: Vec<Expr>, id: &str| match args.try_into() {
+            Ok(args) => body(args, id),
+            Err(_) => panic!("Macro expects {N} arguments"),
+        }),
+    );
+
+    Expr::MacroCall {
+        mac: Rc::new(mac),
+        args: args.into(),
+        id: id.to_string(),
+    }
+}
+
 pub fn cs_add_sat(dst: Expr, src0: Expr, src1: Expr, size: usize, id: &str) -> Expr {
+    cs_macro(
+        &format!("add_sat{size}"),
+        ["dst", "src0", "src1"],
+        [dst, src0, src1],
+        id,
+        move |[dst, src0, src1], id| add_sat(dst, src0, src1, size, id),
+    )
+}
+
+fn add_sat(dst: Expr, src0: Expr, src1: Expr, size: usize, id: &str) -> Expr {
     let sat_label = b_label(&format!("end_add_sat_{id}"));
     cs_mline(vec![
         e_copy(dst.clone(), cs_max_num(size)),
@@ -393,6 +451,16 @@ pub fn cs_add_sat(dst: Expr, src0: Expr, src1: Expr, size: usize, id: &str) -> E
 }
 
 pub fn cs_sub_sat(dst: Expr, src0: Expr, src1: Expr, id: &str) -> Expr {
+    cs_macro(
+        "sub_sat",
+        ["dst", "src0", "src1"],
+        [dst, src0, src1],
+        id,
+        move |[dst, src0, src1], id| sub_sat(dst, src0, src1, id),
+    )
+}
+
+fn sub_sat(dst: Expr, src0: Expr, src1: Expr, id: &str) -> Expr {
     let sat_label = b_label(&format!("end_sub_sat_{id}"));
     cs_mline(vec![
         e_copy(dst.clone(), b_num(0)),
@@ -403,6 +471,16 @@ pub fn cs_sub_sat(dst: Expr, src0: Expr, src1: Expr, id: &str) -> Expr {
 }
 
 pub fn cs_sadd_sat(dst: Expr, src0: Expr, src1: Expr, size: usize, id: &str) -> Expr {
+    cs_macro(
+        &format!("sadd_sat{size}"),
+        ["dst", "src0", "src1"],
+        [dst, src0, src1],
+        id,
+        move |[dst, src0, src1], id| sadd_sat(dst, src0, src1, size, id),
+    )
+}
+
+fn sadd_sat(dst: Expr, src0: Expr, src1: Expr, size: usize, id: &str) -> Expr {
     let end_label = b_label(&format!("end_sadd_sat_{id}"));
     let src0_cpy = b_var(&format!("sadd_src0_cpy_{id}"));
     cs_mline(vec![
@@ -417,6 +495,16 @@ pub fn cs_sadd_sat(dst: Expr, src0: Expr, src1: Expr, size: usize, id: &str) ->
 }
 
 pub fn cs_ssub_sat(dst: Expr, src0: Expr, src1: Expr, size: usize, id: &str) -> Expr {
+    cs_macro(
+        &format!("ssub_sat{size}"),
+        ["dst", "src0", "src1"],
+        [dst, src0, src1],
+        id,
+        move |[dst, src0, src1], id| ssub_sat(dst, src0, src1, size, id),
+    )
+}
+
+fn ssub_sat(dst: Expr, src0: Expr, src1: Expr, size: usize, id: &str) -> Expr {
     let end_label = b_label(&format!("end_ssub_sat_{id}"));
     let src0_cpy = b_var(&format!("sadd_src0_cpy_{id}"));
     cs_mline(vec![
@@ -431,6 +519,16 @@ pub fn cs_ssub_sat(dst: Expr, src0: Expr, src1: Expr, size: usize, id: &str) ->
 }
 
 pub fn cs_strunc_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
+    cs_macro(
+        &format!("strunc_sat{size}"),
+        ["dst", "src"],
+        [dst, src],
+        id,
+        move |[dst, src], id| strunc_sat(dst, src, size, id),
+    )
+}
+
+fn strunc_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
     let end_label = b_label(&format!("end_strunc_{id}"));
     cs_mline(vec![
         e_copy(dst.clone(), b_size(src.clone(), size)),
@@ -443,6 +541,16 @@ pub fn cs_strunc_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
 }
 
 pub fn cs_trunc_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
+    cs_macro(
+        &format!("trunc_sat{size}"),
+        ["dst", "src"],
+        [dst, src],
+        id,
+        move |[dst, src], id| trunc_sat(dst, src, size, id),
+    )
+}
+
+fn trunc_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
     let end_label = b_label(&format!("end_trunc_{id}"));
     cs_mline(vec![
         e_copy(dst.clone(), b_size(src.clone(), size)),
@@ -453,6 +561,16 @@ pub fn cs_trunc_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
 }
 
 pub fn cs_round(dst: Expr, dst_size: usize, src: Expr, src_size: usize, id: &str) -> Expr {
+    cs_macro(
+        &format!("round{dst_size}_{src_size}"),
+        ["dst", "src"],
+        [dst, src],
+        id,
+        move |[dst, src], id| round(dst, dst_size, src, src_size, id),
+    )
+}
+
+fn round(dst: Expr, dst_size: usize, src: Expr, src_size: usize, id: &str) -> Expr {
     let rnd_mod_label = b_label(&format!("biased_rnd_{id}"));
     let add_label = b_label(&format!("rounding_{id}"));
     let end_label = b_label(&format!("end_rnd_{id}"));
@@ -488,6 +606,16 @@ pub fn cs_round(dst: Expr, dst_size: usize, src: Expr, src_size: usize, id: &str
 }
 
 pub fn cs_round_biased(dst: Expr, dst_size: usize, src: Expr, src_size: usize, id: &str) -> Expr {
+    cs_macro(
+        &format!("round_biased{dst_size}_{src_size}"),
+        ["dst", "src"],
+        [dst, src],
+        id,
+        move |[dst, src], id| round_biased(dst, dst_size, src, src_size, id),
+    )
+}
+
+fn round_biased(dst: Expr, dst_size: usize, src: Expr, src_size: usize, id: &str) -> Expr {
     let end_label = b_label(&format!("end_rnd_{id}"));
     let rem_var = b_var(&format!("rem_var_{id}"));
     let rem_size = src_size - dst_size;
@@ -506,6 +634,16 @@ pub fn cs_round_biased(dst: Expr, dst_size: usize, src: Expr, src_size: usize, i
 }
 
 pub fn cs_max(dst: Expr, src0: Expr, src1: Expr, id: &str) -> Expr {
+    cs_macro(
+        "smax",
+        ["dst", "src0", "src1"],
+        [dst, src0, src1],
+        id,
+        move |[dst, src0, src1], id| max(dst, src0, src1, id),
+    )
+}
+
+fn max(dst: Expr, src0: Expr, src1: Expr, id: &str) -> Expr {
     cs_mline(vec![
         e_copy(dst.clone(), src0),
         b_ifgoto(
@@ -518,6 +656,16 @@ pub fn cs_max(dst: Expr, src0: Expr, src1: Expr, id: &str) -> Expr {
 }
 
 pub fn cs_min(dst: Expr, src0: Expr, src1: Expr, id: &str) -> Expr {
+    cs_macro(
+        "smin",
+        ["dst", "src0", "src1"],
+        [dst, src0, src1],
+        id,
+        move |[dst, src0, src1], id| min(dst, src0, src1, id),
+    )
+}
+
+fn min(dst: Expr, src0: Expr, src1: Expr, id: &str) -> Expr {
     cs_mline(vec![
         e_copy(dst.clone(), src0),
         b_ifgoto(
@@ -530,6 +678,16 @@ pub fn cs_min(dst: Expr, src0: Expr, src1: Expr, id: &str) -> Expr {
 }
 
 pub fn cs_abs_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
+    cs_macro(
+        &format!("abs_sat{size}"),
+        ["dst", "src"],
+        [dst, src],
+        id,
+        move |[dst, src], id| abs_sat(dst, src, size, id),
+    )
+}
+
+fn abs_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
     cs_mline(vec![
         e_copy(dst.clone(), src.clone()),
         b_ifgoto(
@@ -547,15 +705,94 @@ pub fn cs_abs_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
 }
 
 pub fn cs_neg_sat(dst: Expr, src: Expr, sat: bool, size: usize, id: &str) -> Expr {
-    let mut code = vec![e_copy(dst.clone(), e_neg(src.clone()))];
-    if sat {
-        code.push(b_ifgoto(
+    if !sat {
+        return e_copy(dst, e_neg(src));
+    }
+
+    cs_macro(
+        &format!("neg_sat{size}"),
+        ["dst", "src"],
+        [dst, src],
+        id,
+        move |[dst, src], id| neg_sat(dst, src, size, id),
+    )
+}
+
+fn neg_sat(dst: Expr, src: Expr, size: usize, id: &str) -> Expr {
+    cs_mline(vec![
+        e_copy(dst.clone(), e_neg(src.clone())),
+        b_ifgoto(
             e_ne(src, cs_smin_num(size)),
             b_label(&format!("neg_end_{}", id)),
+        ),
+        e_copy(dst, cs_smax_num(size)),
+        b_label(&format!("neg_end_{}", id)),
+    ])
+}
+
+/// Post-modifies the DAG register `ireg` by `step` (subtracted when `dec`).
+/// When its L register is not zero the result wraps into the circular
+/// buffer `[B, B + L)`, the L and B registers are found next to `ireg`.
+pub fn cs_circ_mod(ireg: Expr, step: Expr, dec: bool, id: &str) -> Expr {
+    let rf = register_file();
+    let dag_reg = |name: &str| {
+        b_ptr(
+            REGISTER_SPACE,
+            b_grp(e_add(
+                b_ref(ireg.clone()),
+                b_num((rf.get(name).offset() - rf.get("I0").offset()) as i128),
+            )),
+            4,
+        )
+    };
+    let len_var = b_var(&format!("circ_len_{id}"));
+    let base_var = b_var(&format!("circ_base_{id}"));
+    let below_label = b_label(&format!("circ_below_{id}"));
+    let end_label = b_label(&format!("circ_end_{id}"));
+
+    // Constant steps know which end of the buffer they can cross
+    let forward = match step {
+        Expr::Number { val } => Some((val >= 0) != dec),
+        _ => None,
+    };
+
+    let mut code = vec![
+        e_copy(b_local(len_var.clone(), 4), dag_reg("L0")),
+        e_copy(b_local(base_var.clone(), 4), dag_reg("B0")),
+        cs_assign_by(if dec { e_sub } else { e_add }, ireg.clone(), step.clone()),
+        b_ifgoto(e_eq(len_var.clone(), b_num(0)), end_label.clone()),
+    ];
+
+    if forward.is_none() {
+        code.push(b_ifgoto(
+            if dec {
+                e_gts(step, b_num(0))
+            } else {
+                e_lts(step, b_num(0))
+            },
+            below_label.clone(),
+        ));
+    }
+
+    if forward != Some(false) {
+        code.push(b_ifgoto(
+            e_lt(ireg.clone(), e_add(base_var.clone(), len_var.clone())),
+            end_label.clone(),
         ));
-        code.push(e_copy(dst.clone(), cs_smax_num(size)));
-        code.push(b_label(&format!("neg_end_{}", id)))
+        code.push(cs_assign_by(e_sub, ireg.clone(), len_var.clone()));
+    }
+
+    if forward.is_none() {
+        code.push(b_goto(end_label.clone()));
+        code.push(below_label);
     }
+
+    if forward != Some(true) {
+        code.push(b_ifgoto(e_ge(ireg.clone(), base_var), end_label.clone()));
+        code.push(cs_assign_by(e_add, ireg, len_var));
+    }
+
+    code.push(end_label);
     cs_mline(code)
 }
 //// Code snippet
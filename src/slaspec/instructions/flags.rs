@@ -0,0 +1,219 @@
+use super::expr::{Code, Expr};
+use super::expr_util::*;
+
+/// Arithmetic flags of ASTAT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    AZ,
+    AN,
+    AC0,
+    AC1,
+    AV0,
+    AV1,
+    V,
+}
+
+impl Flag {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Flag::AZ => "AZ",
+            Flag::AN => "AN",
+            Flag::AC0 => "AC0",
+            Flag::AC1 => "AC1",
+            Flag::AV0 => "AV0",
+            Flag::AV1 => "AV1",
+            Flag::V => "V",
+        }
+    }
+
+    /// Overflow flag of an accumulator
+    pub fn av(acc: &str) -> Flag {
+        match acc {
+            "A0" => Flag::AV0,
+            "A1" => Flag::AV1,
+            _ => panic!("{acc} is not an accumulator"),
+        }
+    }
+
+    /// Flag staying set until it's cleared by software
+    fn sticky(&self) -> Option<&'static str> {
+        match self {
+            Flag::AV0 => Some("AV0S"),
+            Flag::AV1 => Some("AV1S"),
+            Flag::V => Some("VS"),
+            _ => None,
+        }
+    }
+
+    /// Flag mirroring this one, kept for compatibility
+    fn copy(&self) -> Option<&'static str> {
+        match self {
+            Flag::AC0 => Some("AC0COPY"),
+            Flag::V => Some("VCOPY"),
+            _ => None,
+        }
+    }
+}
+
+/// Smallest signed value on `size` bytes, as an unsigned constant
+fn min_signed(size: usize) -> Expr {
+    b_num(1 << (size * 8 - 1))
+}
+
+/// ASTAT flags written by an instruction, each from a 1-byte condition.
+///
+/// Conditions on the operands are evaluated before the p-code of the
+/// instruction: they can only read registers and fields, but still see the
+/// sources when the destination is one of them. Conditions on the result are
+/// evaluated after it and can read its locals. A flag written more than once
+/// is set when any of its conditions holds, the way vector operations set it
+/// from any of their lanes.
+#[derive(Debug, Clone, Default)]
+pub struct FlagEffect {
+    operands: Vec<(Flag, Expr)>,
+    result: Vec<(Flag, Expr)>,
+}
+
+impl FlagEffect {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operands.is_empty() && self.result.is_empty()
+    }
+
+    fn write(writes: &mut Vec<(Flag, Expr)>, flag: Flag, cond: Expr) {
+        match writes.iter_mut().find(|(written, _)| *written == flag) {
+            Some(_) if matches!(cond, Expr::Number { val: 0 }) => {}
+            Some((_, prev)) if matches!(prev, Expr::Number { val: 0 }) => *prev = cond,
+            Some((_, prev)) => *prev = e_or(prev.clone(), cond),
+            None => writes.push((flag, cond)),
+        }
+    }
+
+    /// `flag` is set when `cond` holds on the operands
+    pub fn operand(mut self, flag: Flag, cond: Expr) -> Self {
+        Self::write(&mut self.operands, flag, cond);
+        self
+    }
+
+    /// `flag` is set when `cond` holds on the result
+    pub fn result(mut self, flag: Flag, cond: Expr) -> Self {
+        Self::write(&mut self.result, flag, cond);
+        self
+    }
+
+    pub fn clear(self, flag: Flag) -> Self {
+        self.operand(flag, b_num(0))
+    }
+
+    /// Writes of both effects
+    pub fn merge(self, other: FlagEffect) -> Self {
+        let merged = other
+            .operands
+            .into_iter()
+            .fold(self, |effect, (flag, cond)| effect.operand(flag, cond));
+
+        other
+            .result
+            .into_iter()
+            .fold(merged, |effect, (flag, cond)| effect.result(flag, cond))
+    }
+
+    /// AZ and AN, from the value written to `dst`
+    pub fn zero_neg(self, dst: Expr) -> Self {
+        self.result(Flag::AZ, e_eq(dst.clone(), b_num(0)))
+            .result(Flag::AN, e_lts(dst, b_num(0)))
+    }
+
+    /// `dst = lhs + rhs`, with its carry in `ac` and its overflow in `v`
+    pub fn add(self, dst: Expr, lhs: Expr, rhs: Expr, ac: Flag, v: Flag) -> Self {
+        self.operand(ac, e_carry(lhs.clone(), rhs.clone()))
+            .operand(v, e_scarry(lhs, rhs))
+            .zero_neg(dst)
+    }
+
+    /// `dst = lhs - rhs`, `ac` is set when there's no borrow
+    pub fn sub(self, dst: Expr, lhs: Expr, rhs: Expr, ac: Flag, v: Flag) -> Self {
+        self.operand(ac, e_ge(lhs.clone(), rhs.clone()))
+            .operand(v, e_sborrow(lhs, rhs))
+            .zero_neg(dst)
+    }
+
+    /// `dst = -src` on `size` bytes, with its carry in `ac` if it has one
+    pub fn neg(self, dst: Expr, src: Expr, size: usize, ac: Option<Flag>, v: Flag) -> Self {
+        let effect = match ac {
+            Some(ac) => self.operand(ac, e_eq(src.clone(), b_num(0))),
+            None => self,
+        };
+
+        effect.operand(v, e_eq(src, min_signed(size))).zero_neg(dst)
+    }
+
+    /// `dst = ABS src` on `size` bytes, only the smallest value overflows
+    pub fn abs(self, dst: Expr, src: Expr, size: usize, v: Flag) -> Self {
+        self.operand(v, e_eq(src, min_signed(size))).zero_neg(dst)
+    }
+
+    /// Bitwise operations and moves: AC0 and V are cleared
+    pub fn logic(self, dst: Expr) -> Self {
+        self.zero_neg(dst).clear(Flag::AC0).clear(Flag::V)
+    }
+
+    /// `dst` was saturated from the wider `src`, `v` is set when it didn't fit
+    pub fn saturate(self, dst: Expr, src: Expr, signed: bool, v: Flag) -> Self {
+        let ext = if signed { e_sext } else { e_zext };
+        self.result(v, e_ne(ext(dst), src))
+    }
+
+    fn flags(&self) -> Vec<Flag> {
+        let mut flags: Vec<Flag> = vec![];
+
+        for (flag, _) in self.operands.iter().chain(&self.result) {
+            if !flags.contains(flag) {
+                flags.push(*flag);
+            }
+        }
+
+        flags
+    }
+
+    /// `code` surrounded by the flag writes, followed by the sticky flags and
+    /// copies of the flags it wrote.
+    pub fn apply(&self, code: Code) -> Code {
+        if self.is_empty() {
+            return code;
+        }
+
+        let mut flagged = Code::new();
+
+        for (flag, cond) in &self.operands {
+            flagged.add_expr(e_copy(b_reg(flag.name()), cond.clone()));
+        }
+
+        flagged.append(code);
+
+        for (flag, cond) in &self.result {
+            let cond = match self.operands.iter().any(|(written, _)| written == flag) {
+                true => e_or(b_reg(flag.name()), cond.clone()),
+                false => cond.clone(),
+            };
+            flagged.add_expr(e_copy(b_reg(flag.name()), cond));
+        }
+
+        for flag in self.flags() {
+            if let Some(sticky) = flag.sticky() {
+                flagged.add_expr(e_copy(
+                    b_reg(sticky),
+                    e_or(b_reg(sticky), b_reg(flag.name())),
+                ));
+            }
+            if let Some(copy) = flag.copy() {
+                flagged.add_expr(e_copy(b_reg(copy), b_reg(flag.name())));
+            }
+        }
+
+        flagged
+    }
+}
@@ -1,7 +1,8 @@
 use super::{pattern::Pattern, util::capitalize};
+use crate::slaspec::error::Error;
 
 #[derive(Debug, Clone)]
-enum Token {
+pub enum Token {
     Literal(String),
     Field(String),
     Variable(String),
@@ -58,16 +59,23 @@ impl Scanner {
         true
     }
 
-    fn scan(&mut self) -> Vec<Token> {
+    fn error(&self, reason: &'static str) -> Error {
+        Error::Display {
+            display: self.text.clone(),
+            reason,
+        }
+    }
+
+    fn scan(&mut self) -> Result<Vec<Token>, Error> {
         while !self.at_end() {
             self.start = self.current;
-            self.scan_token();
+            self.scan_token()?;
         }
 
-        self.tokens.clone()
+        Ok(self.tokens.clone())
     }
 
-    fn scan_token(&mut self) {
+    fn scan_token(&mut self) -> Result<(), Error> {
         let c = self.advance();
 
         match c {
@@ -75,20 +83,22 @@ impl Scanner {
                 if self.chr_match('{') {
                     self.add_token(Token::Literal(String::from("{")));
                 } else {
-                    self.variable();
+                    self.variable()?;
                 }
             }
             '}' => {
                 if self.chr_match('}') {
                     self.add_token(Token::Literal(String::from("}")));
                 } else {
-                    panic!("single '}}' is not allowed");
+                    return Err(self.error("single '}' is not allowed"));
                 }
             }
             _ => {
                 self.literal();
             }
         }
+
+        Ok(())
     }
 
     fn literal(&mut self) {
@@ -100,38 +110,44 @@ impl Scanner {
         self.add_token(Token::Literal(value));
     }
 
-    fn variable(&mut self) {
+    fn variable(&mut self) -> Result<(), Error> {
         if self.chr_match('$') {
-            let content = self.var_content(true);
+            let content = self.var_content(true)?;
             self.add_token(Token::Variable(content));
         } else {
-            let content = self.var_content(false);
+            let content = self.var_content(false)?;
             self.add_token(Token::Field(content));
         }
+
+        Ok(())
     }
 
-    fn var_content(&mut self, is_var: bool) -> String {
+    fn var_content(&mut self, is_var: bool) -> Result<String, Error> {
         while !self.at_end() && self.peek() != '}' {
             self.advance();
         }
 
         if self.at_end() {
-            panic!("Missing closing bracket");
+            return Err(self.error("missing closing bracket"));
         }
 
         self.advance();
 
-        if is_var {
+        Ok(if is_var {
             String::from(&self.text[self.start as usize + 2..self.current as usize - 1])
         } else {
             String::from(&self.text[self.start as usize + 1..self.current as usize - 1])
-        }
+        })
     }
 }
 
-pub fn display_format(txt: &str, pattern: &Pattern, prefix: &str) -> (String, usize) {
+pub fn display_format(
+    txt: &str,
+    pattern: &Pattern,
+    prefix: &str,
+) -> Result<(String, usize), Error> {
     let mut scanner = Scanner::new(txt);
-    let tokens = scanner.scan();
+    let tokens = scanner.scan()?;
     let mut out = String::new();
     let mut var_count = 0;
 
@@ -143,21 +159,49 @@ pub fn display_format(txt: &str, pattern: &Pattern, prefix: &str) -> (String, us
                 var_count += 1;
             }
             Token::Field(s) => {
-                if let Some(f) = pattern.get_field(s) {
-                    out += &format!("{}{}", prefix, f.name())
+                match pattern.get_field(s) {
+                    Some(f) => out += &format!("{}{}", prefix, f.name()),
+                    None => return Err(Error::UnknownField { field: s.clone() }),
                 }
                 var_count += 1
             }
         }
     }
 
-    (out, var_count)
+    Ok((out, var_count))
+}
+
+pub fn display_tokens(txt: &str) -> Result<Vec<Token>, Error> {
+    Scanner::new(txt).scan()
+}
+
+pub fn display_fill<F, V>(txt: &str, field: F, var: V) -> Result<String, Error>
+where
+    F: Fn(&str) -> String,
+    V: Fn(&str) -> String,
+{
+    let mut scanner = Scanner::new(txt);
+    let tokens = scanner.scan()?;
+    let mut out = String::new();
+
+    for tok in tokens {
+        match &tok {
+            Token::Literal(s) => out += s,
+            Token::Variable(s) => out += &var(s),
+            Token::Field(s) => out += &field(s),
+        }
+    }
+
+    Ok(out)
 }
 
-#[allow(dead_code)]
+/// Prefixes the fields and variables of `txt`. A display that can't be parsed
+/// is kept as is, so the error is reported when it is formatted.
 pub fn display_add_prefix(txt: &str, prefix: &str) -> String {
     let mut scanner = Scanner::new(txt);
-    let tokens = scanner.scan();
+    let Ok(tokens) = scanner.scan() else {
+        return txt.to_string();
+    };
     let mut out = String::new();
 
     for tok in tokens {
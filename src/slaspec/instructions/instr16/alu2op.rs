@@ -4,6 +4,7 @@ use crate::slaspec::instructions::common::{BinOp, UnOp};
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::{FieldType, ProtoField, ProtoPattern, RegisterSet};
 
 pub fn instr_fam() -> InstrFamilyBuilder {
@@ -50,6 +51,22 @@ impl OpAssignParam {
 struct OpAssignFactory();
 
 impl OpAssignFactory {
+    fn flags(mask: u16) -> FlagEffect {
+        match mask {
+            0x0..=0x2 => FlagEffect::new().zero_neg(e_rfield("dst")).clear(Flag::V),
+            0x4 | 0x5 => {
+                // The shift overflows when the bits it drops aren't all sign bits
+                let sum = e_add(e_rfield("dst"), e_rfield("src"));
+                let dropped = e_arshft(b_grp(sum), b_num(31 - (mask as i128 - 0x3)));
+                FlagEffect::new()
+                    .operand(Flag::V, e_scarry(e_rfield("dst"), e_rfield("src")))
+                    .operand(Flag::V, e_gt(e_add(b_grp(dropped), b_num(1)), b_num(1)))
+                    .zero_neg(e_rfield("dst"))
+            }
+            _ => FlagEffect::new(),
+        }
+    }
+
     fn base_instr(ifam: &InstrFamilyBuilder, param: &OpAssignParam) -> InstrBuilder {
         let instr = InstrBuilder::new(ifam)
             .name(&param.name)
@@ -59,7 +76,8 @@ impl OpAssignFactory {
             .add_pcode(e_copy(
                 e_rfield("dst"),
                 (param.op)(e_rfield("dst"), e_rfield("src")),
-            ));
+            ))
+            .add_flags(Self::flags(param.mask));
 
         if param.div_field {
             instr.divide_field(
@@ -230,6 +248,7 @@ impl MvDregToDregFactory {
                 e_rfield("dst"),
                 e_macp(if zext { "zext" } else { "sext" }, e_rfield("src")),
             ))
+            .add_flags(FlagEffect::new().logic(e_rfield("dst")))
     }
 }
 
@@ -253,6 +272,7 @@ impl UnaryFactory {
         op_chr: char,
         op: UnOp,
         opc: u16,
+        flags: FlagEffect,
     ) -> InstrBuilder {
         InstrBuilder::new(ifam)
             .name(name)
@@ -261,14 +281,30 @@ impl UnaryFactory {
             .set_field_type("src", FieldType::Variable(RegisterSet::DReg))
             .set_field_type("dst", FieldType::Variable(RegisterSet::DReg))
             .add_pcode(e_copy(e_rfield("dst"), op(e_rfield("src"))))
+            .add_flags(flags)
     }
 }
 
 impl InstrFactory for UnaryFactory {
     fn build_instrs(&self, ifam: &InstrFamilyBuilder) -> Vec<InstrBuilder> {
+        let dst = e_rfield("dst");
         vec![
-            Self::base_instr(ifam, "Neg32", '-', e_neg, 0xe),
-            Self::base_instr(ifam, "Not32", '~', e_bit_not, 0xf),
+            Self::base_instr(
+                ifam,
+                "Neg32",
+                '-',
+                e_neg,
+                0xe,
+                FlagEffect::new().neg(dst.clone(), e_rfield("src"), 4, Some(Flag::AC0), Flag::V),
+            ),
+            Self::base_instr(
+                ifam,
+                "Not32",
+                '~',
+                e_bit_not,
+                0xf,
+                FlagEffect::new().logic(dst),
+            ),
         ]
     }
 }
@@ -2,6 +2,7 @@ use crate::slaspec::instructions::common::BinOp;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::{FieldType, ProtoField, ProtoPattern, RegisterSet};
 
 pub fn instr_fam() -> InstrFamilyBuilder {
@@ -45,6 +46,19 @@ impl TernaryOpFactory {
                 e_rfield("dst"),
                 op(e_rfield("src0"), e_rfield("src1")),
             ))
+            .add_flags(Self::flags(opc))
+    }
+
+    fn flags(opc: u16) -> FlagEffect {
+        let (dst, src0, src1) = (e_rfield("dst"), e_rfield("src0"), e_rfield("src1"));
+
+        match opc {
+            0x0 => FlagEffect::new().add(dst, src0, src1, Flag::AC0, Flag::V),
+            0x1 => FlagEffect::new().sub(dst, src0, src1, Flag::AC0, Flag::V),
+            0x2..=0x4 => FlagEffect::new().logic(dst),
+            // Pointer arithmetic leaves the flags alone
+            _ => FlagEffect::new(),
+        }
     }
 }
 
@@ -2,6 +2,7 @@ use itertools::Itertools;
 
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::{FieldType, ProtoField, ProtoPattern, RegisterSet};
 
 pub fn instr_fam() -> InstrFamilyBuilder {
@@ -56,6 +57,17 @@ impl BinOpFactory {
                     e_sext(b_size(e_field("src"), 1))
                 },
             ))
+            .add_flags(if add && !preg {
+                FlagEffect::new().add(
+                    e_rfield("dst"),
+                    e_rfield("dst"),
+                    e_field("src"),
+                    Flag::AC0,
+                    Flag::V,
+                )
+            } else {
+                FlagEffect::new()
+            })
     }
 }
 
@@ -30,11 +30,7 @@ impl DagAddImmFactory {
             .name("DagAddImm")
             .display(format!("{{i}} {}= {}", if inc { "+" } else { "-" }, val))
             .set_field_type("opc", FieldType::Mask(op))
-            .add_pcode(cs_assign_by(
-                if inc { e_add } else { e_sub },
-                e_rfield("i"),
-                b_num(val),
-            ))
+            .add_pcode(cs_circ_mod(e_rfield("i"), b_num(val), !inc, "i"))
     }
 }
 
@@ -35,11 +35,12 @@ impl DagAddFactory {
             ))
             .set_field_type("br", FieldType::Mask(brev as u16))
             .set_field_type("op", FieldType::Mask(dec as u16))
-            .add_pcode(cs_assign_by(
-                if dec { e_sub } else { e_add },
-                e_rfield("i"),
-                e_rfield("m"),
-            ))
+            .add_pcode(if brev {
+                // Bit-reversed carry addressing ignores the circular buffer
+                cs_assign_by(e_add, e_rfield("i"), e_rfield("m"))
+            } else {
+                cs_circ_mod(e_rfield("i"), e_rfield("m"), dec, "i")
+            })
     }
 }
 
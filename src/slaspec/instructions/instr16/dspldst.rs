@@ -14,8 +14,8 @@ pub fn instr_fam() -> InstrFamilyBuilder {
             ProtoField::new("sig", FieldType::Mask(0x27), 6),
             ProtoField::new("w", FieldType::Blank, 1),
             ProtoField::new("aop", FieldType::Blank, 2),
-            ProtoField::new("i", FieldType::Variable(RegisterSet::IReg), 2),
             ProtoField::new("m", FieldType::Blank, 2),
+            ProtoField::new("i", FieldType::Variable(RegisterSet::IReg), 2),
             ProtoField::new("reg", FieldType::Blank, 3),
         ]),
     );
@@ -153,10 +153,14 @@ impl DspLdStFactory {
         };
 
         instr = match aop {
-            AddrOp::Inc => instr.add_pcode(cs_assign_by(e_add, e_rfield("i"), b_num(mop.size()))),
-            AddrOp::Dec => instr.add_pcode(cs_assign_by(e_sub, e_rfield("i"), b_num(mop.size()))),
+            AddrOp::Inc => {
+                instr.add_pcode(cs_circ_mod(e_rfield("i"), b_num(mop.size()), false, "i"))
+            }
+            AddrOp::Dec => {
+                instr.add_pcode(cs_circ_mod(e_rfield("i"), b_num(mop.size()), true, "i"))
+            }
             AddrOp::None => instr,
-            AddrOp::IncM => instr.add_pcode(cs_assign_by(e_add, e_rfield("i"), e_rfield("m"))),
+            AddrOp::IncM => instr.add_pcode(cs_circ_mod(e_rfield("i"), e_rfield("m"), false, "i")),
         };
 
         instr
@@ -1,6 +1,5 @@
 pub mod alu2op;
 pub mod brcc;
-pub mod cachectrl;
 pub mod cc2dreg;
 pub mod cc2stat;
 pub mod ccflag;
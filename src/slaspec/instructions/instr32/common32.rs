@@ -1,5 +1,6 @@
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::RegisterSet;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -227,8 +228,22 @@ pub fn acc_expr(
     id: &str,
 ) -> Expr {
     let res = b_var(res_id);
+    let ovf = b_var(&format!("acc_ovf_{}", id));
     let mut code = vec![];
 
+    let ovf_cond = match (accop, mode.signed()) {
+        (AccOp::Add, true) => Some(e_scarry(acc.clone(), res.clone())),
+        (AccOp::Add, false) => Some(e_carry(acc.clone(), res.clone())),
+        (AccOp::Sub, true) => Some(e_sborrow(acc.clone(), res.clone())),
+        (AccOp::Sub, false) => Some(e_lt(acc.clone(), res.clone())),
+        _ if mode.sat32() => Some(b_num(0)),
+        _ => None,
+    };
+
+    if let Some(cond) = ovf_cond {
+        code.push(e_copy(b_local(ovf.clone(), 1), cond));
+    }
+
     match accop {
         AccOp::Copy => code.push(e_copy(acc.clone(), res.clone())),
         AccOp::Add => code.push(if ns {
@@ -257,6 +272,10 @@ pub fn acc_expr(
         code.push(b_local(tmp_var.clone(), 4));
         // 32 bit saturation is not compatible with the no saturation directive
         // So no need to account for it here.
+        code.push(e_copy(
+            ovf.clone(),
+            e_or(ovf, e_ne(e_sext(b_size(acc.clone(), 4)), acc.clone())),
+        ));
         code.push(cs_strunc_sat(tmp_var.clone(), acc.clone(), 4, id));
         code.push(e_copy(acc, e_sext(tmp_var)));
     }
@@ -310,6 +329,14 @@ pub fn extract_expr(
         src = if mode.fraction() { rnd_dst } else { src };
     }
 
+    if !ns {
+        let ext = if mode.signed() { e_sext } else { e_zext };
+        code.push(e_copy(
+            b_local(b_var(&format!("extr_ovf_{}", id)), 1),
+            e_ne(ext(b_size(src.clone(), reg_size)), src.clone()),
+        ));
+    }
+
     if ns {
         // The no saturation directive only works with integer so we can just truncate
         code.push(e_copy(dst, b_size(src, reg_size)));
@@ -323,3 +350,30 @@ pub fn extract_expr(
 
     cs_mline(code)
 }
+
+/// Flags of an `acc_expr`, `av` is set when the accumulator overflows
+pub fn acc_flags(av: Flag, accop: AccOp, mode: Mmode, id: &str) -> FlagEffect {
+    match accop {
+        AccOp::None => FlagEffect::new(),
+        AccOp::Copy if !mode.sat32() => FlagEffect::new().clear(av),
+        _ => FlagEffect::new().result(av, b_var(&format!("acc_ovf_{}", id))),
+    }
+}
+
+/// Flags of an `extract_expr`, V is set when the extracted value saturates
+pub fn extract_flags(ns: bool, id: &str) -> FlagEffect {
+    match ns {
+        true => FlagEffect::new().clear(Flag::V),
+        false => FlagEffect::new().result(Flag::V, b_var(&format!("extr_ovf_{}", id))),
+    }
+}
+
+/// Flags of a `shift` to `dst`, an arithmetic one sets `v` when it overflows
+pub fn shift_flags(dst: Expr, arithm: bool, v: Flag, id: &str) -> FlagEffect {
+    let flags = FlagEffect::new().zero_neg(dst);
+
+    match arithm {
+        true => flags.result(v, b_var(&format!("shft_ovf_{}", id))),
+        false => flags.clear(v),
+    }
+}
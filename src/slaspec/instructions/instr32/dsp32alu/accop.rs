@@ -3,6 +3,7 @@ use std::fmt;
 use crate::slaspec::instructions::common::UnOp;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::{FieldType, RegisterSet};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -31,6 +32,23 @@ impl fmt::Display for Aop {
 pub struct AccOpFactory();
 
 impl AccOpFactory {
+    /// Flags of `dst = -src` or `dst = ABS src` on accumulators
+    fn flags(dst: &str, src: &str, abs: bool) -> FlagEffect {
+        let (dst_acc, src_acc) = (b_reg(dst), b_reg(src));
+
+        match abs {
+            true => FlagEffect::new().abs(dst_acc, src_acc, 5, Flag::av(dst)),
+            false => FlagEffect::new().neg(dst_acc, src_acc, 5, None, Flag::av(dst)),
+        }
+    }
+
+    fn acc_flags(aop: Aop, hl_acc: &str, abs: bool) -> FlagEffect {
+        match aop {
+            Aop::Dual => Self::flags("A1", "A1", abs).merge(Self::flags("A0", "A0", abs)),
+            _ => Self::flags(hl_acc, &format!("{aop}"), abs),
+        }
+    }
+
     fn neg_instr(ifam: &InstrFamilyBuilder, aop: Aop, hl: bool) -> InstrBuilder {
         let hl_acc = if hl { "A1" } else { "A0" };
         InstrBuilder::new(ifam)
@@ -59,6 +77,7 @@ impl AccOpFactory {
                     &format!("{hl_acc}{aop}"),
                 )
             })
+            .add_flags(Self::acc_flags(aop, hl_acc, false))
     }
 
     fn abs_instr(ifam: &InstrFamilyBuilder, aop: Aop, hl: bool) -> InstrBuilder {
@@ -88,6 +107,7 @@ impl AccOpFactory {
                     &format!("{hl_acc}{aop}"),
                 )
             })
+            .add_flags(Self::acc_flags(aop, hl_acc, true))
     }
 
     fn weird_instr(ifam: &InstrFamilyBuilder, smode: bool, xmode: bool) -> InstrBuilder {
@@ -3,6 +3,7 @@ use itertools::Itertools;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::{FieldType, ProtoField, ProtoPattern, RegisterSet};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -59,6 +60,16 @@ impl AddSubFactory {
         }
     }
 
+    fn flags(dst_id: &str, sub: bool, ac: Flag) -> FlagEffect {
+        let (dst, src0, src1) = (e_rfield(dst_id), e_rfield("src0"), e_rfield("src1"));
+
+        if sub {
+            FlagEffect::new().sub(dst, src0, src1, ac, Flag::V)
+        } else {
+            FlagEffect::new().add(dst, src0, src1, ac, Flag::V)
+        }
+    }
+
     fn half_instr(instr: InstrBuilder, hl: bool) -> InstrBuilder {
         instr
             .set_field_type("hl", FieldType::Mask(hl as u16))
@@ -90,6 +101,7 @@ impl AddSubFactory {
             .set_field_type("src0", FieldType::Variable(src0var))
             .set_field_type("src1", FieldType::Variable(src1var))
             .add_pcode(Self::expr("dst0", sat, sub, true))
+            .add_flags(Self::flags("dst0", sub, Flag::AC0))
     }
 
     fn as32_instr(ifam: &InstrFamilyBuilder, sat: bool, aop: Aop) -> InstrBuilder {
@@ -121,6 +133,11 @@ impl AddSubFactory {
                 ])
             } else {
                 Self::expr("dst0", sat, sub, false)
+            })
+            .add_flags(if dual {
+                Self::flags("dst0", false, Flag::AC0).merge(Self::flags("dst1", true, Flag::AC1))
+            } else {
+                Self::flags("dst0", sub, Flag::AC0)
             });
 
         if dual {
@@ -178,6 +195,16 @@ impl AddSubFactory {
             } else {
                 cs_strunc_sat(e_rfield("dst0"), b_var("asr_res_rnd"), 2, "asr")
             })
+            .add_flags(if down {
+                FlagEffect::new().zero_neg(e_rfield("dst0")).clear(Flag::V)
+            } else {
+                FlagEffect::new().zero_neg(e_rfield("dst0")).saturate(
+                    e_rfield("dst0"),
+                    b_var("asr_res_rnd"),
+                    true,
+                    Flag::V,
+                )
+            })
     }
 }
 
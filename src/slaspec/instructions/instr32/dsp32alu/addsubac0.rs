@@ -1,5 +1,6 @@
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::{FieldType, RegisterSet};
 
 pub struct AddSubAc0Factory();
@@ -23,6 +24,27 @@ impl AddSubAc0Factory {
             .set_field_type("dst0", FieldType::Variable(RegisterSet::DReg))
             .set_field_type("src0", FieldType::Variable(RegisterSet::DReg))
             .set_field_type("src1", FieldType::Variable(RegisterSet::DReg))
+            // AC0 is an operand, its new value is only known from the result
+            .add_pcode(e_copy(
+                e_local("carry_ac0", 1),
+                if sub {
+                    e_or(
+                        e_gt(e_rfield("src0"), e_rfield("src1")),
+                        b_grp(e_and(
+                            b_reg("AC0"),
+                            e_eq(e_rfield("src0"), e_rfield("src1")),
+                        )),
+                    )
+                } else {
+                    e_or(
+                        e_carry(e_rfield("src0"), e_rfield("src1")),
+                        e_carry(
+                            e_add(e_rfield("src0"), e_rfield("src1")),
+                            e_zext(b_reg("AC0")),
+                        ),
+                    )
+                },
+            ))
             .add_pcode(e_copy(
                 e_local("result", 5),
                 op(e_sext(e_rfield("src0")), e_sext(e_rfield("src1"))),
@@ -37,6 +59,15 @@ impl AddSubAc0Factory {
             } else {
                 e_copy(e_rfield("dst0"), b_size(b_var("result"), 4))
             })
+            .add_flags(
+                FlagEffect::new()
+                    .result(Flag::AC0, b_var("carry_ac0"))
+                    .result(
+                        Flag::V,
+                        e_ne(e_sext(b_size(b_var("result"), 4)), b_var("result")),
+                    )
+                    .zero_neg(e_rfield("dst0")),
+            )
     }
 }
 
@@ -1,10 +1,23 @@
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::{FieldType, RegisterSet};
 
 pub struct AddSubAccExtFactory();
 
 impl AddSubAccExtFactory {
+    /// Flags of `A0 += A1` or `A0 -= A1`
+    fn acc_flags(sub: bool) -> FlagEffect {
+        let overflow = match sub {
+            true => e_sborrow(b_reg("A0"), b_reg("A1")),
+            false => e_scarry(b_reg("A0"), b_reg("A1")),
+        };
+
+        FlagEffect::new()
+            .operand(Flag::AV0, overflow)
+            .zero_neg(b_reg("A0"))
+    }
+
     fn aae_instr(ifam: &InstrFamilyBuilder, aop: bool, hl: bool) -> InstrBuilder {
         InstrBuilder::new(ifam)
             .name("AddAccExt")
@@ -36,6 +49,12 @@ impl AddSubAccExtFactory {
             } else {
                 cs_strunc_sat(e_rfield("dst0"), b_reg("A0"), 4, "aae")
             })
+            .add_flags(Self::acc_flags(false))
+            .add_flags(if aop {
+                FlagEffect::new().saturate(b_var("A0_trunc"), b_reg("A0"), true, Flag::V)
+            } else {
+                FlagEffect::new().saturate(e_rfield("dst0"), b_reg("A0"), true, Flag::V)
+            })
     }
 
     fn asa_instr(ifam: &InstrFamilyBuilder, sub: bool, sat: bool) -> InstrBuilder {
@@ -62,6 +81,7 @@ impl AddSubAccExtFactory {
             } else {
                 arithm_op(b_reg("A0"), b_reg("A0"), b_reg("A1"), 5, "asa")
             })
+            .add_flags(Self::acc_flags(sub))
     }
 
     fn asae_instr(ifam: &InstrFamilyBuilder, aop: bool, sat: bool) -> InstrBuilder {
@@ -106,6 +126,13 @@ impl AddSubAccExtFactory {
                     e_copy(e_rfield("dst1"), b_size(b_var("res_dst1"), 4)),
                 ])
             })
+            .add_flags(
+                FlagEffect::new()
+                    .saturate(e_rfield("dst0"), b_var("res_dst0"), true, Flag::V)
+                    .saturate(e_rfield("dst1"), b_var("res_dst1"), true, Flag::V)
+                    .zero_neg(e_rfield("dst0"))
+                    .zero_neg(e_rfield("dst1")),
+            )
     }
 }
 
@@ -3,6 +3,7 @@ use itertools::Itertools;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::{FieldType, ProtoField, ProtoPattern, RegisterSet};
 
 #[derive(Debug, Clone, Copy)]
@@ -24,6 +25,41 @@ impl Aop {
         .to_string()
     }
 
+    /// Whether the low and high lanes subtract
+    fn subs(&self) -> (bool, bool) {
+        match self {
+            Aop::AA => (false, false),
+            Aop::AS => (false, true),
+            Aop::SA => (true, false),
+            Aop::SS => (true, true),
+        }
+    }
+
+    /// Flags of both lanes, written to `dst_id`: the low lane carries to
+    /// AC0, the high one to AC1.
+    fn flags(&self, dst_id: &str, cross: bool) -> FlagEffect {
+        let (sub_l, sub_h) = self.subs();
+        let lane = |sub: bool, high: bool| {
+            let half = |expr: Expr| match high {
+                true => b_trunc(expr, 2),
+                false => b_size(expr, 2),
+            };
+            let dst = match high != cross {
+                true => b_trunc(e_rfield(dst_id), 2),
+                false => b_size(e_rfield(dst_id), 2),
+            };
+            let ac = if high { Flag::AC1 } else { Flag::AC0 };
+            let (src0, src1) = (half(e_rfield("src0")), half(e_rfield("src1")));
+
+            match sub {
+                true => FlagEffect::new().sub(dst, src0, src1, ac, Flag::V),
+                false => FlagEffect::new().add(dst, src0, src1, ac, Flag::V),
+            }
+        };
+
+        lane(sub_l, false).merge(lane(sub_h, true))
+    }
+
     fn expr(&self, sat: bool, id: &str) -> Expr {
         let add_expr = if sat {
             |hl: &str, id: &str| {
@@ -133,6 +169,7 @@ impl AddSubVecFactory {
             .set_field_type("aopc", FieldType::Mask(0x0))
             .add_pcode(aop.expr(sat, ""))
             .add_pcode(Self::expr_cpy("dst0", cross))
+            .add_flags(aop.flags("dst0", cross))
     }
 
     fn dual_instr(
@@ -207,6 +244,8 @@ impl AddSubVecFactory {
             .add_pcode(dst1_aop.expr(sat, "dst1"))
             .add_pcode_opt(shift_expr.clone())
             .add_pcode(Self::expr_cpy("dst1", cross))
+            .add_flags(dst0_aop.flags("dst0", cross))
+            .add_flags(dst1_aop.flags("dst1", cross))
     }
 }
 
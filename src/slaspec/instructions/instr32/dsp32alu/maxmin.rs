@@ -3,6 +3,7 @@ use std::fmt;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::{FieldType, RegisterSet};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -47,6 +48,27 @@ impl Aop {
             Aop::Neg => cs_neg_sat(dst, src0, sat, size, id),
         }
     }
+
+    fn flags(&self, dst: Expr, src0: Expr, size: usize, ac: Flag) -> FlagEffect {
+        match self {
+            Aop::Max | Aop::Min => FlagEffect::new().zero_neg(dst).clear(Flag::V),
+            Aop::Abs => FlagEffect::new().abs(dst, src0, size, Flag::V),
+            Aop::Neg => FlagEffect::new().neg(dst, src0, size, Some(ac), Flag::V),
+        }
+    }
+
+    /// Flags of the two lanes of a vector operation
+    fn vec_flags(&self) -> FlagEffect {
+        let (dst, src0) = (e_rfield("dst0"), e_rfield("src0"));
+
+        self.flags(
+            b_size(dst.clone(), 2),
+            b_size(src0.clone(), 2),
+            2,
+            Flag::AC0,
+        )
+        .merge(self.flags(b_trunc(dst, 2), b_trunc(src0, 2), 2, Flag::AC1))
+    }
 }
 
 impl fmt::Display for Aop {
@@ -135,6 +157,11 @@ impl MMANFactory {
             } else {
                 None
             })
+            .add_flags(if op32 {
+                aop.flags(e_rfield("dst0"), e_rfield("src0"), 4, Flag::AC0)
+            } else {
+                aop.vec_flags()
+            })
     }
 }
 
@@ -3,6 +3,7 @@ use itertools::Itertools;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::pattern::FieldType;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -50,6 +51,24 @@ impl Aop {
         .to_string()
     }
 
+    fn flags(&self, sat: bool) -> FlagEffect {
+        // Saturates the accumulator to 32 bits, AVx is set when it didn't fit
+        fn sat_flags(acc_id: &str) -> FlagEffect {
+            let acc_w = b_reg(&format!("{acc_id}.W"));
+
+            FlagEffect::new()
+                .operand(Flag::av(acc_id), e_ne(e_sext(acc_w.clone()), b_reg(acc_id)))
+                .zero_neg(acc_w)
+        }
+
+        match (self, sat) {
+            (Aop::A0, true) => sat_flags("A0"),
+            (Aop::A1, true) => sat_flags("A1"),
+            (Aop::Dual, true) => sat_flags("A0").merge(sat_flags("A1")),
+            _ => FlagEffect::new(),
+        }
+    }
+
     fn expr(&self, sat: bool) -> Expr {
         fn sat_acc(acc_id: &str) -> Expr {
             let neg_lab = b_label(&format!("sat_neg_{acc_id}"));
@@ -102,6 +121,7 @@ impl MvAccFactory {
             .set_field_type("s", FieldType::Mask(sat as u16))
             .set_field_type("x", FieldType::Mask(0x0))
             .add_pcode(aop.expr(sat))
+            .add_flags(aop.flags(sat))
     }
 }
 
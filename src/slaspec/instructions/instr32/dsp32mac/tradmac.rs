@@ -3,6 +3,7 @@ use itertools::Itertools;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
 use crate::slaspec::instructions::instr32::common32::*;
 use crate::slaspec::instructions::pattern::{FieldType, ProtoField, ProtoPattern, RegisterSet};
 
@@ -134,6 +135,28 @@ impl Mac {
             )
     }
 
+    pub fn flags(&self, mode: Mmode) -> FlagEffect {
+        let mut flags = FlagEffect::new();
+
+        if !self.no_accop() {
+            flags = flags.merge(acc_flags(
+                Flag::av(&self.acc.to_str()),
+                self.accop,
+                mode,
+                &format!("{}accOp", self.acc.to_str()),
+            ));
+        }
+
+        if self.assign {
+            flags = flags.merge(extract_flags(
+                false,
+                &format!("{}extrOp", self.acc.to_str()),
+            ));
+        }
+
+        flags
+    }
+
     pub fn expr(&self, full_reg: bool, mode: Mmode, mml: bool) -> Expr {
         let res_id = &format!("results_{}", self.acc.to_str());
         let src0_id = &format!("src0{}", self.acc.to_str());
@@ -303,6 +326,13 @@ impl TradMacParam {
         }
     }
 
+    fn flags(&self) -> FlagEffect {
+        match self.mac_enum() {
+            MacEnum::Mac0(mac) | MacEnum::Mac1(mac) => mac.flags(self.mode),
+            MacEnum::Mac10(mac0, mac1) => mac0.flags(self.mode).merge(mac1.flags(Mmode::Default)),
+        }
+    }
+
     fn all_macs(assign: bool) -> Vec<(Option<Mac>, Option<Mac>)> {
         let opacc = AccOp::all();
         let oper = Oper::all();
@@ -387,7 +417,10 @@ impl TradMacFactory {
             .name(&params.name())
             .display(params.display());
 
-        instr = params.set_fields(instr).add_pcode(params.pcode());
+        instr = params
+            .set_fields(instr)
+            .add_pcode(params.pcode())
+            .add_flags(params.flags());
 
         instr
     }
@@ -3,6 +3,7 @@ use itertools::Itertools;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::FlagEffect;
 use crate::slaspec::instructions::instr32::common32::*;
 use crate::slaspec::instructions::pattern::{FieldType, RegisterSet};
 
@@ -222,7 +223,11 @@ impl Mac32Factory {
                 "Mac32"
             })
             .display(Self::display(params))
-            .add_pcode(Self::expr(params));
+            .add_pcode(Self::expr(params))
+            .add_flags(match params.assign {
+                true => extract_flags(params.no_sat, "Mac32"),
+                false => FlagEffect::new(),
+            });
 
         instr = Self::set_fields(instr, params);
 
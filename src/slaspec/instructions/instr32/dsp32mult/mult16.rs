@@ -3,6 +3,7 @@ use itertools::Itertools;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr::Expr;
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::FlagEffect;
 use crate::slaspec::instructions::instr32::common32::*;
 use crate::slaspec::instructions::pattern::{FieldType, ProtoField, ProtoPattern, RegisterSet};
 
@@ -184,6 +185,14 @@ impl Mult16Factory {
         }
     }
 
+    fn flags(params: Mult16Params) -> FlagEffect {
+        match params.oper_enum() {
+            OperEnum::L => extract_flags(false, "Low"),
+            OperEnum::H => extract_flags(false, "High"),
+            OperEnum::LH => extract_flags(false, "Low").merge(extract_flags(false, "High")),
+        }
+    }
+
     fn base_instr(ifam: &InstrFamilyBuilder, params: Mult16Params) -> InstrBuilder {
         let mut instr = InstrBuilder::new(ifam)
             .name(if params.w0.is_some() && params.w1.is_some() {
@@ -192,7 +201,8 @@ impl Mult16Factory {
                 "Mult16"
             })
             .display(Self::display(params))
-            .add_pcode(Self::expr(params));
+            .add_pcode(Self::expr(params))
+            .add_flags(Self::flags(params));
 
         instr = Self::set_fields(instr, params);
 
@@ -134,6 +134,7 @@ impl Mult32Factory {
                 9,
                 "Mult32",
             ))
+            .add_flags(extract_flags(params.no_sat, "Mult32"))
     }
 }
 
@@ -18,10 +18,12 @@ pub fn shift(dst: Expr, src: Expr, size: usize, arithm: bool, sat: bool, id: &st
     let lshft_lab = &format!("lshift_{}", id);
     let rshft_lab = &format!("rshift_{}", id);
     let end_lab = &format!("end_shift_{}", id);
+    let ovf_var = &format!("shft_ovf_{}", id);
     let nbits = ((size * 8) as f64).log2().floor() as usize + 1;
 
     let mut code = vec![
         e_local(res_var, size),
+        e_copy(e_local(ovf_var, 1), b_num(0)),
         e_local(shift_var, 2),
         get_shift(shift_var, nbits),
         b_ifgoto(e_gts(b_var(shift_var), b_num(0)), b_label(rshft_lab)),
@@ -56,6 +58,10 @@ pub fn shift(dst: Expr, src: Expr, size: usize, arithm: bool, sat: bool, id: &st
             b_var(buf_var),
             e_lshft(e_sext(src.clone()), b_var(shift_var)),
         ));
+        code.push(e_copy(
+            b_var(ovf_var),
+            e_ne(e_sext(b_size(b_var(buf_var), size)), b_var(buf_var)),
+        ));
         code.push(cs_strunc_sat(
             b_var(res_var),
             b_var(buf_var),
@@ -67,6 +73,16 @@ pub fn shift(dst: Expr, src: Expr, size: usize, arithm: bool, sat: bool, id: &st
             b_var(res_var),
             e_lshft(src.clone(), b_var(shift_var)),
         ));
+        if arithm {
+            // The bits shifted out weren't all copies of the sign bit
+            code.push(e_copy(
+                b_var(ovf_var),
+                e_ne(
+                    b_grp(e_arshft(b_var(res_var), b_var(shift_var))),
+                    src.clone(),
+                ),
+            ));
+        }
     }
 
     code.push(e_copy(dst.clone(), b_var(res_var)));
@@ -3,6 +3,8 @@ use itertools::Itertools;
 use super::common::*;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::Flag;
+use crate::slaspec::instructions::instr32::common32::shift_flags;
 use crate::slaspec::instructions::pattern::{FieldType, RegisterSet};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -107,6 +109,12 @@ impl Shift16Factory {
                 sop.sat(),
                 &sop.name(),
             ))
+            .add_flags(shift_flags(
+                e_rfield("dst"),
+                sop.arithm(),
+                Flag::V,
+                sop.name(),
+            ))
     }
 }
 
@@ -1,6 +1,8 @@
 use super::common::*;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::Flag;
+use crate::slaspec::instructions::instr32::common32::shift_flags;
 use crate::slaspec::instructions::pattern::{FieldType, RegisterSet};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -83,6 +85,20 @@ impl Shift16VecFactory {
                     e_zext(b_var("res_vecL")),
                 ),
             ))
+            .add_flags(
+                shift_flags(
+                    b_size(e_rfield("dst"), 2),
+                    sop.arithm(),
+                    Flag::V,
+                    &format!("{}L", sop.name()),
+                )
+                .merge(shift_flags(
+                    b_trunc(e_rfield("dst"), 2),
+                    sop.arithm(),
+                    Flag::V,
+                    &format!("{}H", sop.name()),
+                )),
+            )
     }
 }
 
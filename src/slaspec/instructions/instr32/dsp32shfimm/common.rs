@@ -15,9 +15,11 @@ pub fn shift(
     let lshft_lab = &format!("lshift_{}", id);
     let rshft_lab = &format!("rshift_{}", id);
     let end_lab = &format!("end_shift_{}", id);
+    let ovf_var = &format!("shft_ovf_{}", id);
 
     let mut code = vec![
         e_local(res_var, size),
+        e_copy(e_local(ovf_var, 1), b_num(0)),
         e_copy(e_local(shift_var, 1), shft),
         b_ifgoto(e_gts(b_var(shift_var), b_num(0)), b_label(rshft_lab)),
         b_ifgoto(e_lts(b_var(shift_var), b_num(0)), b_label(lshft_lab)),
@@ -51,6 +53,10 @@ pub fn shift(
             b_var(buf_var),
             e_lshft(e_sext(src.clone()), b_var(shift_var)),
         ));
+        code.push(e_copy(
+            b_var(ovf_var),
+            e_ne(e_sext(b_size(b_var(buf_var), size)), b_var(buf_var)),
+        ));
         code.push(cs_strunc_sat(
             b_var(res_var),
             b_var(buf_var),
@@ -62,6 +68,16 @@ pub fn shift(
             b_var(res_var),
             e_lshft(src.clone(), b_var(shift_var)),
         ));
+        if arithm {
+            // The bits shifted out weren't all copies of the sign bit
+            code.push(e_copy(
+                b_var(ovf_var),
+                e_ne(
+                    b_grp(e_arshft(b_var(res_var), b_var(shift_var))),
+                    src.clone(),
+                ),
+            ));
+        }
     }
 
     code.push(e_copy(dst.clone(), b_var(res_var)));
@@ -3,6 +3,8 @@ use itertools::Itertools;
 use super::common::*;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
+use crate::slaspec::instructions::instr32::common32::shift_flags;
 use crate::slaspec::instructions::pattern::{FieldType, ProtoField, ProtoPattern, RegisterSet};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -101,6 +103,10 @@ impl Shift32Factory {
                     sop.sat(),
                     &sop.name(),
                 )
+            })
+            .add_flags(match sop {
+                Sop::RotShft => FlagEffect::new(),
+                _ => shift_flags(e_rfield("dst"), sop.arithm(), Flag::V, sop.name()),
             });
 
         if sop != Sop::RotShft {
@@ -3,6 +3,8 @@ use itertools::Itertools;
 use super::common::*;
 use crate::slaspec::instructions::core::{InstrBuilder, InstrFactory, InstrFamilyBuilder};
 use crate::slaspec::instructions::expr_util::*;
+use crate::slaspec::instructions::flags::{Flag, FlagEffect};
+use crate::slaspec::instructions::instr32::common32::shift_flags;
 use crate::slaspec::instructions::pattern::{FieldType, ProtoField, ProtoPattern};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -93,6 +95,10 @@ impl ShiftAccFactory {
                     false,
                     &sop.name(),
                 )
+            })
+            .add_flags(match sop {
+                Sop::RotShft => FlagEffect::new(),
+                _ => shift_flags(b_reg(acc_id), sop.arithm(), Flag::av(acc_id), sop.name()),
             });
 
         if sop != Sop::RotShft {
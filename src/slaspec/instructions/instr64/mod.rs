@@ -1,3 +1,4 @@
 pub mod jump32;
 pub mod ldimm;
 pub mod ldstabs;
+pub mod multi;
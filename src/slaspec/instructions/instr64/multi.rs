@@ -2,17 +2,19 @@ use std::collections::HashSet;
 
 use itertools::Itertools;
 
+use crate::slaspec::error::Error;
 use crate::slaspec::instructions::{
     core::{InstrBuilder, InstrFactory, InstrFamilyBuilder, Prefixed},
     expr::Code,
-    expr_util::{b_local, b_reg, b_var, e_copy, e_rfield},
+    expr_util::{b_build, b_local, b_reg, b_var, e_copy, e_field, e_rfield},
     format::display_add_prefix,
     instr16::*,
-    instr32::*,
     pattern::{FieldType, Pattern, ProtoField, ProtoPattern},
+    subtable::SubTable,
 };
+use crate::slaspec::registers::register_file;
 
-pub fn instr_fam() -> InstrFamilyBuilder {
+pub fn instr_fam(ifams32: &[InstrFamilyBuilder]) -> InstrFamilyBuilder {
     let mut ifam = InstrFamilyBuilder::new_64(
         "Multi",
         "64-bit Instruction Shell",
@@ -28,47 +30,57 @@ pub fn instr_fam() -> InstrFamilyBuilder {
         ],
     );
 
-    ifam.add_instrs(&MultiFactory());
+    // The 32-bit slot takes any instruction of the families allowed in parallel,
+    // with its M bit set
+    let slot32 = ifam.add_subtable(SubTable::builds(
+        &format!("{}Slot32Tbl0", (&ifam).prefix()),
+        "slot32",
+        0x1,
+        ifams32
+            .iter()
+            .filter(|ifam32| ifam32.is_multi())
+            .flat_map(|ifam32| {
+                ifam32
+                    .tables()
+                    .into_iter()
+                    .map(move |table| (format!("{}M", ifam32.prefix()), table))
+            })
+            .collect(),
+    ));
+
+    ifam.add_instrs(&MultiFactory { slot32 });
 
     ifam
 }
 
-struct MultiFactory();
+struct MultiFactory {
+    slot32: usize,
+}
 
 impl MultiFactory {
-    fn multi_name(
-        instr32: &InstrBuilder,
-        instr16a: &InstrBuilder,
-        instr16b: &InstrBuilder,
-    ) -> String {
-        vec![instr32.get_name(), instr16a.get_name(), instr16b.get_name()].join(" || ")
+    fn slot_prefix(ifam16: &InstrFamilyBuilder, slot: &str) -> String {
+        format!("{}{slot}", ifam16.prefix())
     }
 
     fn multi_display(
-        ifam32: &InstrFamilyBuilder,
-        instr32: &InstrBuilder,
         ifam16a: &InstrFamilyBuilder,
         instr16a: &InstrBuilder,
         ifam16b: &InstrFamilyBuilder,
         instr16b: &InstrBuilder,
     ) -> String {
-        let mut displays = Vec::new();
-
-        if instr32.get_name() != "NOP32" {
-            displays.push(display_add_prefix(&instr32.get_display(), &ifam32.prefix()));
-        }
+        let mut displays = vec![String::from("{slot32}")];
 
         if instr16a.get_name() != "NOP" {
             displays.push(display_add_prefix(
                 &instr16a.get_display(),
-                &format!("{}A", ifam16a.prefix()),
+                &Self::slot_prefix(ifam16a, "A"),
             ));
         }
 
         if instr16b.get_name() != "NOP" {
             displays.push(display_add_prefix(
                 &instr16b.get_display(),
-                &format!("{}B", ifam16b.prefix()),
+                &Self::slot_prefix(ifam16b, "B"),
             ));
         }
 
@@ -76,68 +88,57 @@ impl MultiFactory {
     }
 
     fn multi_pattern(
-        ifam32: &InstrFamilyBuilder,
-        instr32: &InstrBuilder,
+        &self,
         ifam16a: &InstrFamilyBuilder,
         instr16a: &InstrBuilder,
         ifam16b: &InstrFamilyBuilder,
         instr16b: &InstrBuilder,
     ) -> Pattern {
-        let fields32 = instr32.pattern().fields_prefix(&ifam32.prefix());
         let fields16a = instr16a
             .pattern()
-            .fields_prefix(&format!("{}A", ifam16a.prefix()));
+            .fields_prefix(&Self::slot_prefix(ifam16a, "A"));
         let fields16b = instr16b
             .pattern()
-            .fields_prefix(&format!("{}B", ifam16b.prefix()));
-
-        let mut h32 = fields32[0].clone();
-        let l32 = fields32[1].clone();
-        let a16 = fields16a[0].clone();
-        let b16 = fields16b[0].clone();
+            .fields_prefix(&Self::slot_prefix(ifam16b, "B"));
 
-        if let Some(field) = h32.get(0) {
-            if field.len() == 5 {
-                h32[0] = ProtoField::new("sig", FieldType::Mask(0x19), 5).to_field_end(15);
-            } else {
-                panic!("Cannot make a multi instr with this 32 bit instr")
-            }
-        } else {
-            panic!("This 32 bit instr is missing a pattern")
-        }
+        // The sub-table spans both words of the 32-bit slot
+        let slot32 = ProtoField::new("slot32", FieldType::Table(self.slot32), 16).to_field_end(15);
 
-        Pattern::new([h32, l32, a16, b16])
+        Pattern::new([
+            vec![slot32],
+            vec![],
+            fields16a[0].clone(),
+            fields16b[0].clone(),
+        ])
     }
 
     fn multi_action(
-        ifam32: &InstrFamilyBuilder,
-        instr32: &InstrBuilder,
         ifam16a: &InstrFamilyBuilder,
         instr16a: &InstrBuilder,
         ifam16b: &InstrFamilyBuilder,
         instr16b: &InstrBuilder,
-    ) -> Code {
+    ) -> Result<Code, Error> {
         let mut regs: HashSet<(bool, String)> = HashSet::new();
-        let mut action32 = instr32
+        let mut action16a = instr16a
             .get_actions()
-            .multify(&ifam32.prefix(), false, &mut regs);
-        let action16a =
-            instr16a
-                .get_actions()
-                .multify(&format!("{}A", ifam16a.prefix()), false, &mut regs);
-        let action16b =
-            instr16b
-                .get_actions()
-                .multify(&format!("{}B", ifam16b.prefix()), false, &mut regs);
-
-        action32.append(action16a);
-        action32.append(action16b);
-        action32
+            .multify(&Self::slot_prefix(ifam16a, "A"), &mut regs);
+        let action16b = instr16b
+            .get_actions()
+            .multify(&Self::slot_prefix(ifam16b, "B"), &mut regs);
+
+        // Actions run at disassembly time, before any copy of the registers
+        if !regs.is_empty() {
+            return Err(Error::SlotRead {
+                regs: regs.into_iter().map(|(_, reg)| reg).sorted().collect(),
+            });
+        }
+
+        action16a.append(action16b);
+        Ok(action16a)
     }
 
     fn multi_pcode(
-        ifam32: &InstrFamilyBuilder,
-        instr32: &InstrBuilder,
+        pattern: &Pattern,
         ifam16a: &InstrFamilyBuilder,
         instr16a: &InstrBuilder,
         ifam16b: &InstrFamilyBuilder,
@@ -145,95 +146,96 @@ impl MultiFactory {
     ) -> Code {
         let mut regs: HashSet<(bool, String)> = HashSet::new();
         let mut code = Code::new();
-        let action32 = instr32
+        let pcode16a = instr16a
             .get_pcodes()
-            .multify(&ifam32.prefix(), false, &mut regs);
-        let action16a =
-            instr16a
-                .get_pcodes()
-                .multify(&format!("{}A", ifam16a.prefix()), false, &mut regs);
-        let action16b =
-            instr16b
-                .get_pcodes()
-                .multify(&format!("{}B", ifam16b.prefix()), false, &mut regs);
+            .multify(&Self::slot_prefix(ifam16a, "A"), &mut regs);
+        let pcode16b = instr16b
+            .get_pcodes()
+            .multify(&Self::slot_prefix(ifam16b, "B"), &mut regs);
 
+        // Every slot reads the registers as they were before the bundle
         for (field, reg_id) in regs.iter().sorted() {
             if *field {
+                let size = match pattern.get_field(reg_id).map(|f| f.ftype()) {
+                    Some(FieldType::Variable(regset)) => regset.size(),
+                    _ => panic!("Field {reg_id} is not a register"),
+                };
                 code.add_expr(e_copy(
-                    b_local(b_var(&format!("old_{reg_id}Reg")), 4),
+                    b_local(b_var(&format!("old_{reg_id}Reg")), size),
                     e_rfield(reg_id),
                 ));
             } else {
+                let size = match register_file().size_of(reg_id) {
+                    Some(size) => size,
+                    None => panic!("Register {reg_id} is not defined in the register file"),
+                };
                 code.add_expr(e_copy(
-                    b_local(b_var(&format!("old_{reg_id}")), 4),
+                    b_local(b_var(&format!("old_{}", reg_id.replace('.', "_"))), size),
                     b_reg(reg_id),
                 ));
             }
         }
 
-        code.append(action32);
-        code.append(action16a);
-        code.append(action16b);
+        code.add_expr(b_build(e_field("slot32")));
+        code.append(pcode16a);
+        code.append(pcode16b);
         code
     }
 
     fn multi_instr(
+        &self,
         ifam: &InstrFamilyBuilder,
-        ifam32: &InstrFamilyBuilder,
-        instr32: &InstrBuilder,
         ifam16a: &InstrFamilyBuilder,
         instr16a: &InstrBuilder,
         ifam16b: &InstrFamilyBuilder,
         instr16b: &InstrBuilder,
     ) -> InstrBuilder {
-        InstrBuilder::new(ifam)
-            .name(&Self::multi_name(instr32, instr16a, instr16b))
-            .display(Self::multi_display(
-                ifam32, instr32, ifam16a, instr16a, ifam16b, instr16b,
-            ))
-            .set_pattern(Self::multi_pattern(
-                ifam32, instr32, ifam16a, instr16a, ifam16b, instr16b,
-            ))
-            .set_actions(Self::multi_action(
-                ifam32, instr32, ifam16a, instr16a, ifam16b, instr16b,
-            ))
+        let pattern = self.multi_pattern(ifam16a, instr16a, ifam16b, instr16b);
+
+        let instr = InstrBuilder::new(ifam)
+            .name("")
+            .display(Self::multi_display(ifam16a, instr16a, ifam16b, instr16b))
             .set_pcodes(Self::multi_pcode(
-                ifam32, instr32, ifam16a, instr16a, ifam16b, instr16b,
+                &pattern, ifam16a, instr16a, ifam16b, instr16b,
             ))
+            .set_pattern(pattern);
+
+        match Self::multi_action(ifam16a, instr16a, ifam16b, instr16b) {
+            Ok(actions) => instr.set_actions(actions),
+            Err(err) => instr.set_error(err),
+        }
     }
 }
 
 impl InstrFactory for MultiFactory {
     fn build_instrs(&self, ifam: &InstrFamilyBuilder) -> Vec<InstrBuilder> {
-        let ifams32 = vec![nop32::instr_fam(), dsp32mac::instr_fam()];
-
-        let ifams16 = vec![
-            // MAIN_16A
+        // First 16-bit slot: any load/store or DAG modify
+        let ifams16a = [
             nop16::instr_fam(),
-            ptr2op::instr_fam(),
-            compi2op::instr_fam(),
-            // MAIN_16B
             ldstpmod::instr_fam(),
             ldst::instr_fam(),
             dspldst::instr_fam(),
+            dagmodim::instr_fam(),
+            dagmodik::instr_fam(),
             ldstii::instr_fam(),
             ldstiifp::instr_fam(),
         ];
 
+        // Second 16-bit slot: I-register loads/stores only
+        let ifams16b = [nop16::instr_fam(), dspldst::instr_fam()];
+
         let mut instrs = Vec::new();
 
-        for ifam32 in ifams32.iter() {
-            for instr32 in ifam32.instrs() {
-                println!("Doing: {}", instr32.get_name());
-                for ifam16a in ifams16.iter() {
-                    for instr16a in ifam16a.instrs() {
-                        for ifam16b in ifams16.iter() {
-                            for instr16b in ifam16b.instrs() {
-                                instrs.push(Self::multi_instr(
-                                    ifam, ifam32, instr32, ifam16a, instr16a, ifam16b, instr16b,
-                                ));
-                            }
+        for ifam16a in ifams16a.iter() {
+            for instr16a in ifam16a.instrs() {
+                for ifam16b in ifams16b.iter() {
+                    for instr16b in ifam16b.instrs() {
+                        // Only one of the 16-bit slots can store
+                        if instr16a.get_pcodes().stores() && instr16b.get_pcodes().stores() {
+                            continue;
                         }
+
+                        instrs.push(self.multi_instr(ifam, ifam16a, instr16a, ifam16b, instr16b));
                     }
                 }
             }
@@ -242,3 +244,21 @@ impl InstrFactory for MultiFactory {
         instrs
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_register_reads_in_slot_actions() {
+        let ifam = nop16::instr_fam();
+        let nop = InstrBuilder::new(&ifam).name("Nop");
+        let reads = nop.clone().add_action(e_copy(b_var("off"), b_reg("P0")));
+
+        match MultiFactory::multi_action(&ifam, &reads, &ifam, &nop) {
+            Err(Error::SlotRead { regs }) => assert_eq!(regs, ["P0"]),
+            res => panic!("{:?}", res.map(|_| ())),
+        }
+        assert!(MultiFactory::multi_action(&ifam, &nop, &ifam, &nop).is_ok());
+    }
+}
@@ -1,11 +1,17 @@
 mod common;
-mod expr;
 mod expr_util;
-mod format;
-mod pattern;
+mod flags;
 mod util;
 
 pub mod core;
+pub mod decl;
 pub mod instr16;
 pub mod instr32;
 pub mod instr64;
+
+pub(crate) mod expr;
+pub(crate) mod format;
+pub(crate) mod pattern;
+pub(crate) mod simplify;
+pub(crate) mod sizes;
+pub(crate) mod subtable;
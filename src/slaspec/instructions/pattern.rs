@@ -1,6 +1,9 @@
 use std::{fmt, hash::Hash};
 
 use super::util::capitalize;
+use crate::slaspec::error::Error;
+use crate::slaspec::registers::{Alias, RegGroup, register_file};
+use crate::slaspec::sleigh::AttachKind;
 
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq, PartialOrd, Ord)]
 pub enum RegisterSet {
@@ -39,97 +42,119 @@ impl fmt::Display for RegisterSet {
 }
 
 impl RegisterSet {
-    fn build_regs(id: &str, len: usize, suffix: Option<&str>) -> Vec<String> {
-        let mut regs = Vec::new();
-
-        for i in 0..len {
-            regs.push(format!(
-                "{}{}{}",
-                id,
-                i,
-                if let Some(s) = suffix {
-                    format!(".{}", s)
+    fn every_other(regs: Vec<String>, odd: bool) -> Vec<String> {
+        (0..regs.len())
+            .map(|i| {
+                if i % 2 == 0 {
+                    regs[i + odd as usize].clone()
                 } else {
-                    "".to_string()
+                    "_".to_string()
                 }
-            ));
-        }
-
-        regs
+            })
+            .collect()
     }
 
-    fn build_regs_from(regs: Vec<&str>) -> Vec<String> {
-        regs.into_iter().map(|v| v.to_string()).collect()
+    fn build_names_from(regs: Vec<String>) -> Vec<String> {
+        regs.into_iter().map(|v| format!("\"{v}\"")).collect()
     }
 
-    fn build_names_from(regs: Vec<&str>) -> Vec<String> {
-        regs.into_iter().map(|v| format!("\"{v}\"")).collect()
+    pub fn size(&self) -> usize {
+        match self {
+            Self::CBIT => 1,
+            _ => self
+                .regs()
+                .iter()
+                .find(|reg| *reg != "_")
+                .map_or(0, |reg| register_file().get(reg).size()),
+        }
     }
 
-    pub fn attach_type(&self) -> String {
+    pub fn attach_type(&self) -> AttachKind {
         match self {
-            Self::CBIT => "names",
-            _ => "variables",
+            Self::CBIT => AttachKind::Names,
+            _ => AttachKind::Variables,
         }
-        .to_string()
+    }
+
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::DReg,
+            Self::DRegL,
+            Self::DRegH,
+            Self::DRegB,
+            Self::DRegE,
+            Self::DRegO,
+            Self::DRegPair,
+            Self::PReg,
+            Self::PRegL,
+            Self::PRegH,
+            Self::IReg,
+            Self::IRegL,
+            Self::IRegH,
+            Self::MReg,
+            Self::MRegL,
+            Self::MRegH,
+            Self::BReg,
+            Self::BRegL,
+            Self::BRegH,
+            Self::LReg,
+            Self::LRegL,
+            Self::LRegH,
+            Self::SyRg2,
+            Self::SyRg3,
+            Self::LC,
+            Self::CBIT,
+        ]
+    }
+
+    /// The set displayed as `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().into_iter().find(|set| set.to_string() == name)
     }
 
     pub fn regs(&self) -> Vec<String> {
+        let rf = register_file();
+
         match self {
-            Self::DReg => Self::build_regs("R", 8, None),
-            Self::DRegL => Self::build_regs("R", 8, Some("L")),
-            Self::DRegH => Self::build_regs("R", 8, Some("H")),
-            Self::DRegB => Self::build_regs("R", 8, Some("B")),
-            Self::DRegE => Self::build_regs_from(vec!["R0", "_", "R2", "_", "R4", "_", "R6", "_"]),
-            Self::DRegO => Self::build_regs_from(vec!["R1", "_", "R3", "_", "R5", "_", "R7", "_"]),
-            Self::DRegPair => {
-                Self::build_regs_from(vec!["R10", "R10", "R32", "R32", "R54", "R54", "R76", "R76"])
-            }
-            Self::PReg => {
-                let mut regs = Self::build_regs("P", 6, None);
-                regs.append(&mut Self::build_regs_from(vec!["SP", "FP"]));
-                regs
-            }
-            Self::PRegL => {
-                let mut regs = Self::build_regs("P", 6, Some("L"));
-                regs.append(&mut Self::build_regs_from(vec!["SP.L", "FP.L"]));
-                regs
-            }
-            Self::PRegH => {
-                let mut regs = Self::build_regs("P", 6, Some("H"));
-                regs.append(&mut Self::build_regs_from(vec!["SP.H", "FP.H"]));
-                regs
-            }
-            Self::IReg => Self::build_regs("I", 4, None),
-            Self::IRegL => Self::build_regs("I", 4, Some("L")),
-            Self::IRegH => Self::build_regs("I", 4, Some("H")),
-            Self::MReg => Self::build_regs("M", 4, None),
-            Self::MRegL => Self::build_regs("M", 4, Some("L")),
-            Self::MRegH => Self::build_regs("M", 4, Some("H")),
-            Self::BReg => Self::build_regs("B", 4, None),
-            Self::BRegL => Self::build_regs("B", 4, Some("L")),
-            Self::BRegH => Self::build_regs("B", 4, Some("H")),
-            Self::LReg => Self::build_regs("L", 4, None),
-            Self::LRegL => Self::build_regs("L", 4, Some("L")),
-            Self::LRegH => Self::build_regs("L", 4, Some("H")),
-            Self::SyRg2 => Self::build_regs_from(vec![
+            Self::DReg => rf.group(RegGroup::Data),
+            Self::DRegL => rf.aliases(RegGroup::Data, Alias::Low),
+            Self::DRegH => rf.aliases(RegGroup::Data, Alias::High),
+            Self::DRegB => rf.aliases(RegGroup::Data, Alias::Byte),
+            Self::DRegE => Self::every_other(rf.group(RegGroup::Data), false),
+            Self::DRegO => Self::every_other(rf.group(RegGroup::Data), true),
+            Self::DRegPair => rf
+                .group(RegGroup::DataPair)
+                .into_iter()
+                .flat_map(|pair| [pair.clone(), pair])
+                .collect(),
+            Self::PReg => rf.group(RegGroup::Pointer),
+            Self::PRegL => rf.aliases(RegGroup::Pointer, Alias::Low),
+            Self::PRegH => rf.aliases(RegGroup::Pointer, Alias::High),
+            Self::IReg => rf.group(RegGroup::DagI),
+            Self::IRegL => rf.aliases(RegGroup::DagI, Alias::Low),
+            Self::IRegH => rf.aliases(RegGroup::DagI, Alias::High),
+            Self::MReg => rf.group(RegGroup::DagM),
+            Self::MRegL => rf.aliases(RegGroup::DagM, Alias::Low),
+            Self::MRegH => rf.aliases(RegGroup::DagM, Alias::High),
+            Self::BReg => rf.group(RegGroup::DagB),
+            Self::BRegL => rf.aliases(RegGroup::DagB, Alias::Low),
+            Self::BRegH => rf.aliases(RegGroup::DagB, Alias::High),
+            Self::LReg => rf.group(RegGroup::DagL),
+            Self::LRegL => rf.aliases(RegGroup::DagL, Alias::Low),
+            Self::LRegH => rf.aliases(RegGroup::DagL, Alias::High),
+            Self::SyRg2 => rf.names(&[
                 "LC0", "LT0", "LB0", "LC1", "LT1", "LB1", "CYCLES", "CYCLES2",
             ]),
-            Self::SyRg3 => Self::build_regs_from(vec![
+            Self::SyRg3 => rf.names(&[
                 "USP", "SEQSTAT", "SYSCFG", "RETI", "RETX", "RETN", "RETE", "EMUDAT",
             ]),
-            Self::LC => Self::build_regs_from(vec!["LC0", "LC1"]),
-            Self::CBIT => Self::build_names_from(vec![
-                "AZ", "AN", "AC0COPY", "VCOPY", "_0x04", "CC", "AQ", "_0x07", "RND_MOD", "_0x09",
-                "_0x0a", "_0x0b", "AC0", "AC1", "_0x0e", "_0x0f", "AV0", "AV0S", "AV1", "AV1S",
-                "_0x14", "_0x15", "_0x16", "_0x17", "V", "VS", "_0x1a", "_0x1b", "_0x1c", "_0x1d",
-                "_0x1e", "_0x1f",
-            ]),
+            Self::LC => rf.names(&["LC0", "LC1"]),
+            Self::CBIT => Self::build_names_from(rf.bit_names("ASTAT")),
         }
     }
 }
 
-#[derive(Debug, Default, Clone, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, Clone, Eq)]
 pub enum FieldType {
     #[default]
     Blank,
@@ -138,12 +163,15 @@ pub enum FieldType {
     SImmVal,
     Any,
     Variable(RegisterSet),
+    Table(usize),
 }
 
 impl Hash for FieldType {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
-        if let Self::Variable(v) = self {
-            v.hash(state);
+        match self {
+            Self::Variable(v) => v.hash(state),
+            Self::Table(t) => t.hash(state),
+            _ => {}
         }
         core::mem::discriminant(self).hash(state);
     }
@@ -153,12 +181,46 @@ impl PartialEq for FieldType {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (Self::Variable(l0), Self::Variable(r0)) => l0 == r0,
+            (Self::Table(l0), Self::Table(r0)) => l0 == r0,
             _ => core::mem::discriminant(self) == core::mem::discriminant(other),
         }
     }
 }
 
-#[derive(Debug, Default, Clone, Hash, PartialEq, Eq, Ord)]
+impl FieldType {
+    fn rank(&self) -> usize {
+        match self {
+            Self::Blank => 0,
+            Self::Mask(_) => 1,
+            Self::UImmVal => 2,
+            Self::SImmVal => 3,
+            Self::Any => 4,
+            Self::Variable(_) => 5,
+            Self::Table(_) => 6,
+        }
+    }
+}
+
+// Equality, hashing and ordering all ignore the value of a mask: a field is a
+// token field whatever value an instruction gives it, so sets of fields, hashed
+// or ordered, keep a single field per mask
+impl Ord for FieldType {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        match (self, other) {
+            (Self::Variable(l0), Self::Variable(r0)) => l0.cmp(r0),
+            (Self::Table(l0), Self::Table(r0)) => l0.cmp(r0),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for FieldType {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[derive(Debug, Default, Clone, Hash, PartialEq, Eq)]
 pub struct BitRange {
     start: usize,
     end: usize,
@@ -174,13 +236,17 @@ impl BitRange {
     }
 }
 
+impl Ord for BitRange {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.end
+            .cmp(&other.end)
+            .then_with(|| self.len().cmp(&other.len()))
+    }
+}
+
 impl PartialOrd for BitRange {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self.end.partial_cmp(&other.end) {
-            Some(core::cmp::Ordering::Equal) => {}
-            ord => return ord,
-        }
-        self.len().partial_cmp(&other.len())
+        Some(self.cmp(other))
     }
 }
 
@@ -236,7 +302,7 @@ impl ProtoPattern {
     }
 }
 
-#[derive(Debug, Default, Clone, Hash, PartialEq, Eq, Ord)]
+#[derive(Debug, Default, Clone, Hash, PartialEq, Eq)]
 pub struct Field {
     id: String,
     ftype: FieldType,
@@ -279,11 +345,16 @@ impl Field {
         self.ftype == FieldType::Blank
     }
 
+    pub fn is_table(&self) -> bool {
+        matches!(self.ftype, FieldType::Table(_))
+    }
+
     pub fn name(&self) -> String {
         let suffix = match &self.ftype {
             FieldType::Variable(regset) => format!("{regset}"),
             FieldType::UImmVal => String::from("UImm"),
             FieldType::SImmVal => String::from("SImm"),
+            FieldType::Table(index) => format!("Tbl{index}"),
             _ => String::new(),
         };
 
@@ -295,21 +366,18 @@ impl Field {
     }
 }
 
+impl Ord for Field {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.bit_range
+            .cmp(&other.bit_range)
+            .then_with(|| other.id.cmp(&self.id))
+            .then_with(|| other.ftype.cmp(&self.ftype))
+    }
+}
+
 impl PartialOrd for Field {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self.bit_range.partial_cmp(&other.bit_range) {
-            Some(core::cmp::Ordering::Equal) => {}
-            ord => return ord,
-        }
-        match self.id.partial_cmp(&other.id) {
-            Some(core::cmp::Ordering::Equal) => {}
-            Some(o) => return Some(o.reverse()),
-            None => return None,
-        }
-        match self.ftype.partial_cmp(&other.ftype) {
-            Some(o) => Some(o.reverse()),
-            None => None,
-        }
+        Some(self.cmp(other))
     }
 }
 
@@ -359,6 +427,38 @@ impl Pattern {
         None
     }
 
+    /// ASCII boxes of the bit layout, with the bits of the masks kept by
+    /// `fill` written out and the other fields named.
+    pub fn layout(&self, fill: impl Fn(&Field) -> bool) -> Vec<String> {
+        let sep = "+---+---+---+---|---+---+---+---|---+---+---+---|---+---+---+---+";
+        let mut lines = Vec::new();
+
+        for word in self.fields.iter().filter(|word| !word.is_empty()) {
+            lines.push(sep.to_string());
+            let mut word_str = String::new();
+
+            for field in word {
+                match field.ftype() {
+                    FieldType::Mask(mask) if fill(field) => {
+                        let bin_str = format!("{mask:0len$b}", len = field.len());
+                        for bit in bin_str.chars() {
+                            word_str += &format!("| {bit} ");
+                        }
+                    }
+                    _ => {
+                        word_str += &format!("|{:.^len$}", field.id(), len = (4 * field.len() - 1))
+                    }
+                }
+            }
+
+            word_str += "|";
+            lines.push(word_str);
+        }
+
+        lines.push(sep.to_string());
+        lines
+    }
+
     pub fn set_field_type(mut self, field_id: &str, ftype: FieldType) -> Self {
         if let Some((wi, fi)) = self.get_field_index(field_id) {
             self.fields[wi][fi].ftype = ftype;
@@ -367,51 +467,61 @@ impl Pattern {
         self
     }
 
-    pub fn split_field(mut self, field_id: &str, split: ProtoPattern) -> Self {
-        if let Some((wi, mut fi)) = self.get_field_index(field_id) {
-            let field = &self.fields[wi][fi];
-
-            if field.len() != split.len() {
-                println!("WARNING: Lengths are not matching for field splitting");
-                return self;
-            }
+    pub fn split_field(mut self, field_id: &str, split: ProtoPattern) -> Result<Self, Error> {
+        let Some((wi, mut fi)) = self.get_field_index(field_id) else {
+            return Err(Error::UnknownField {
+                field: field_id.to_string(),
+            });
+        };
+        let field = &self.fields[wi][fi];
+
+        if field.len() != split.len() {
+            return Err(Error::SplitLength {
+                field: field_id.to_string(),
+                len: field.len(),
+                split_len: split.len(),
+            });
+        }
 
-            let mut end: isize = self.fields[wi].remove(fi).bit_range.end as isize;
+        let mut end: isize = self.fields[wi].remove(fi).bit_range.end as isize;
 
-            for proto in split.fields.iter() {
-                let f = proto.to_field_end(end as usize);
-                end = f.bit_range.start as isize - 1;
+        for proto in split.fields.iter() {
+            let f = proto.to_field_end(end as usize);
+            end = f.bit_range.start as isize - 1;
 
-                self.fields[wi].insert(fi, f);
-                fi += 1;
-            }
+            self.fields[wi].insert(fi, f);
+            fi += 1;
         }
 
-        self
+        Ok(self)
     }
 
-    pub fn divide_field(mut self, field_id: &str, div: ProtoPattern) -> Self {
-        if let Some((wi, mut fi)) = self.get_field_index(field_id) {
-            let field = &self.fields[wi][fi];
-
-            for dfield in div.fields.iter() {
-                if field.len() != dfield.size {
-                    println!("WARNING: Lengths are not matching for field division");
-                    return self;
-                }
-            }
+    pub fn divide_field(mut self, field_id: &str, div: ProtoPattern) -> Result<Self, Error> {
+        let Some((wi, mut fi)) = self.get_field_index(field_id) else {
+            return Err(Error::UnknownField {
+                field: field_id.to_string(),
+            });
+        };
+        let field = &self.fields[wi][fi];
+
+        if let Some(dfield) = div.fields.iter().find(|dfield| dfield.size != field.len()) {
+            return Err(Error::DivideLength {
+                field: field_id.to_string(),
+                len: field.len(),
+                part_len: dfield.size,
+            });
+        }
 
-            let start = self.fields[wi].remove(fi).bit_range.start;
+        let start = self.fields[wi].remove(fi).bit_range.start;
 
-            for proto in div.fields.iter() {
-                let f = proto.to_field(start);
+        for proto in div.fields.iter() {
+            let f = proto.to_field(start);
 
-                self.fields[wi].insert(fi, f);
-                fi += 1;
-            }
+            self.fields[wi].insert(fi, f);
+            fi += 1;
         }
 
-        self
+        Ok(self)
     }
 }
 
@@ -470,3 +580,29 @@ impl From<[ProtoPattern; 4]> for Pattern {
         Pattern { fields }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashSet};
+    use std::hash::{BuildHasher, RandomState};
+
+    use super::*;
+
+    #[test]
+    fn keeps_one_field_per_mask() {
+        let proto = |mask| ProtoField::new("opc", FieldType::Mask(mask), 4);
+        let (lhs, rhs) = (proto(0x3).to_field(0), proto(0xc).to_field(0));
+        let hasher = RandomState::new();
+
+        assert_eq!(lhs, rhs);
+        assert_eq!(hasher.hash_one(&lhs), hasher.hash_one(&rhs));
+        assert_eq!(lhs.cmp(&rhs), std::cmp::Ordering::Equal);
+        assert_eq!(BTreeSet::from([lhs.clone(), rhs.clone()]).len(), 1);
+        assert_eq!(HashSet::from([lhs.clone(), rhs]).len(), 1);
+
+        // Any other difference still makes two fields
+        let other = ProtoField::new("opc", FieldType::UImmVal, 4).to_field(0);
+        let moved = proto(0x3).to_field(4);
+        assert_eq!(BTreeSet::from([lhs, other, moved]).len(), 3);
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use super::{
+    expr::{Code, Expr, Op},
+    expr_util::*,
+};
+
+// Macros without side effects, a local they compute can be dropped
+const PURE_MACROS: [&str; 7] = [
+    "zext", "sext", "carry", "scarry", "sborrow", "popcount", "lzcount",
+];
+
+/// Rewrites the p-code of a constructor before it's emitted: folds constant
+/// operations, drops identity operations and redundant parentheses, and
+/// removes the locals nothing reads.
+///
+/// Expressions are printed without parentheses of their own, so the text, not
+/// the tree, decides the precedence. Operations are only rewritten where their
+/// text stands alone (a statement, a side of a copy, a group, a macro argument
+/// or a condition), where removing or folding them can't regroup the operands.
+pub fn simplify(code: Code) -> Code {
+    let mut stmts = vec![];
+
+    for expr in code.exprs() {
+        flatten(expr.clone(), &mut stmts);
+    }
+
+    let mut stmts: Vec<Expr> = stmts.into_iter().map(|stmt| expr(stmt, true)).collect();
+    while let Some(dead) = dead_local(&stmts) {
+        stmts.remove(dead);
+    }
+
+    let mut simple = Code::new();
+    for stmt in stmts {
+        simple.add_expr(stmt);
+    }
+    simple
+}
+
+fn flatten(expr: Expr, stmts: &mut Vec<Expr>) {
+    match expr {
+        Expr::Line { current, next } => {
+            flatten(*current, stmts);
+            if let Some(line) = next {
+                flatten(*line, stmts);
+            }
+        }
+        stmt => stmts.push(stmt),
+    }
+}
+
+fn is_atom(expr: &Expr) -> bool {
+    matches!(
+        expr,
+        Expr::Field { .. }
+            | Expr::Var { .. }
+            | Expr::Reg { .. }
+            | Expr::Number { .. }
+            | Expr::Macro { .. }
+            | Expr::Group { .. }
+    )
+}
+
+/// Simplifies `expr`, `closed` when its text stands alone
+fn expr(expr_in: Expr, closed: bool) -> Expr {
+    match expr_in {
+        Expr::Line { current, next } => {
+            b_line(expr(*current, true), next.map(|line| expr(*line, true)))
+        }
+        Expr::Group { expr: inner } => {
+            let inner = expr(*inner, true);
+            if closed || is_atom(&inner) {
+                inner
+            } else {
+                b_grp(inner)
+            }
+        }
+        Expr::Binary {
+            lhs,
+            op: Op::Copy,
+            rhs,
+        } => b_bin(expr(*lhs, true), Op::Copy, expr(*rhs, true)),
+        Expr::Binary { lhs, op, rhs } => {
+            let bin = b_bin(expr(*lhs, false), op, expr(*rhs, false));
+            match closed {
+                true => binary(bin),
+                false => bin,
+            }
+        }
+        Expr::Macro { id, params } => b_mac(
+            &id,
+            params.into_iter().map(|param| expr(*param, true)).collect(),
+        ),
+        Expr::MacroCall { mac, args, id } => Expr::MacroCall {
+            mac,
+            args: args.into_iter().map(|arg| expr(arg, true)).collect(),
+            id,
+        },
+        Expr::Indirect { val } => b_indirect(expr(*val, true)),
+        Expr::IfGoto { cond, goto } => b_ifgoto(expr(*cond, true), expr(*goto, false)),
+        Expr::Unary { op, expr: val } => b_un(op, expr(*val, false)),
+        Expr::Size { var, size } => b_size(expr(*var, false), size),
+        Expr::Trunc { var, size } => b_trunc(expr(*var, false), size),
+        Expr::Ptr { space, addr, size } => b_ptr(&space, expr(*addr, false), size),
+        Expr::Ref { var } => b_ref(expr(*var, false)),
+        Expr::Return { addr } => b_ret(expr(*addr, false)),
+        Expr::Goto { dest } => b_goto(expr(*dest, false)),
+        Expr::Call { addr } => b_call(expr(*addr, false)),
+        other => other,
+    }
+}
+
+/// Folds or removes a binary operation standing alone
+fn binary(bin: Expr) -> Expr {
+    let Expr::Binary { lhs, op, rhs } = bin else {
+        return bin;
+    };
+
+    if let (Expr::Number { val: a }, Expr::Number { val: b }) = (&*lhs, &*rhs)
+        && let Some(val) = fold(*a, &op, *b)
+    {
+        return b_num(val);
+    }
+
+    match (&*lhs, &op, &*rhs) {
+        (
+            _,
+            Op::Plus | Op::Minus | Op::BitOr | Op::BitXor | Op::LShft | Op::RShft | Op::ARShft,
+            Expr::Number { val: 0 },
+        )
+        | (_, Op::Mult, Expr::Number { val: 1 }) => expr(*lhs, true),
+        (Expr::Number { val: 0 }, Op::Plus | Op::BitOr | Op::BitXor, _)
+        | (Expr::Number { val: 1 }, Op::Mult, _) => expr(*rhs, true),
+        _ => Expr::Binary { lhs, op, rhs },
+    }
+}
+
+/// Value of `a op b`, when it's a non-negative constant on any size
+fn fold(a: i128, op: &Op, b: i128) -> Option<i128> {
+    if a < 0 || b < 0 {
+        return None;
+    }
+
+    let val = match op {
+        Op::Plus => a.checked_add(b)?,
+        Op::Minus => a.checked_sub(b)?,
+        Op::Mult => a.checked_mul(b)?,
+        Op::BitOr => a | b,
+        Op::BitAnd => a & b,
+        Op::BitXor => a ^ b,
+        Op::LShft if b < 127 && a >> (127 - b) == 0 => a << b,
+        _ => return None,
+    };
+
+    (val >= 0).then_some(val)
+}
+
+/// Whether `expr` can be dropped without losing an effect
+fn is_pure(expr: &Expr) -> bool {
+    match expr {
+        Expr::Macro { id, params } => {
+            PURE_MACROS.contains(&id.as_str()) && params.iter().all(|param| is_pure(param))
+        }
+        Expr::Ptr { .. } | Expr::Line { .. } | Expr::Build { .. } => false,
+        Expr::Binary { op: Op::Copy, .. } => false,
+        Expr::Binary { lhs, rhs, .. } => is_pure(lhs) && is_pure(rhs),
+        Expr::Unary { expr, .. }
+        | Expr::Group { expr }
+        | Expr::Size { var: expr, .. }
+        | Expr::Trunc { var: expr, .. }
+        | Expr::Ref { var: expr }
+        | Expr::Indirect { val: expr } => is_pure(expr),
+        Expr::Field { .. } | Expr::Var { .. } | Expr::Reg { .. } | Expr::Number { .. } => true,
+        _ => false,
+    }
+}
+
+fn count_vars(expr: &Expr, uses: &mut HashMap<String, usize>) {
+    let mut count = |expr: &Expr| count_vars(expr, uses);
+
+    match expr {
+        Expr::Var { id } => *uses.entry(id.clone()).or_default() += 1,
+        Expr::Line { current, next } => {
+            count(current);
+            if let Some(line) = next {
+                count(line);
+            }
+        }
+        Expr::Macro { params, .. } => params.iter().for_each(|param| count(param)),
+        Expr::MacroCall { args, .. } => args.iter().for_each(count),
+        Expr::Binary { lhs, rhs, .. } => {
+            count(lhs);
+            count(rhs);
+        }
+        Expr::IfGoto { cond, goto } => {
+            count(cond);
+            count(goto);
+        }
+        Expr::Indirect { val: expr }
+        | Expr::Size { var: expr, .. }
+        | Expr::Trunc { var: expr, .. }
+        | Expr::Local { var: expr, .. }
+        | Expr::Unary { expr, .. }
+        | Expr::Ptr { addr: expr, .. }
+        | Expr::Ref { var: expr }
+        | Expr::Return { addr: expr }
+        | Expr::Goto { dest: expr }
+        | Expr::Call { addr: expr }
+        | Expr::Group { expr }
+        | Expr::Build { table: expr } => count(expr),
+        Expr::Field { .. } | Expr::Reg { .. } | Expr::Number { .. } | Expr::Label { .. } => {}
+    }
+}
+
+/// Index of a local declaration whose variable appears nowhere else
+fn dead_local(stmts: &[Expr]) -> Option<usize> {
+    let mut uses = HashMap::new();
+    for stmt in stmts {
+        count_vars(stmt, &mut uses);
+    }
+
+    stmts.iter().position(|stmt| {
+        let (var, val) = match stmt {
+            Expr::Local { var, .. } => (var, None),
+            Expr::Binary {
+                lhs,
+                op: Op::Copy,
+                rhs,
+            } => match &**lhs {
+                Expr::Local { var, .. } => (var, Some(rhs)),
+                _ => return false,
+            },
+            _ => return false,
+        };
+
+        match &**var {
+            Expr::Var { id } => uses.get(id) == Some(&1) && val.is_none_or(|val| is_pure(val)),
+            _ => false,
+        }
+    })
+}
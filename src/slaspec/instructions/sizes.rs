@@ -0,0 +1,352 @@
+use std::collections::HashMap;
+
+use crate::slaspec::error::Error;
+use crate::slaspec::registers::register_file;
+use crate::slaspec::sleigh::AttachKind;
+
+use super::{
+    expr::{Code, Expr, Op},
+    pattern::{FieldType, Pattern},
+};
+
+/// Infers the byte size of the p-code expressions of a constructor, the way
+/// the SLEIGH compiler does, and records the operands that don't agree.
+///
+/// Numbers and immediate fields take the size of their context, they only have
+/// to fit in it. Anything whose size can't be known statically (sub-tables,
+/// disassembly-time variables, user operations) is trusted.
+pub struct SizeCheck<'a> {
+    pattern: &'a Pattern,
+    prefix: &'a str,
+    locals: HashMap<String, usize>,
+    mismatches: Vec<String>,
+}
+
+impl<'a> SizeCheck<'a> {
+    pub fn new(pattern: &'a Pattern, prefix: &'a str) -> Self {
+        SizeCheck {
+            pattern,
+            prefix,
+            locals: HashMap::new(),
+            mismatches: Vec::new(),
+        }
+    }
+
+    /// Checks every statement of `code`, locals are declared in order.
+    pub fn check(pattern: &Pattern, prefix: &str, code: &Code) -> Result<(), Error> {
+        let mut check = SizeCheck::new(pattern, prefix);
+
+        for expr in code.exprs() {
+            check.stmt(expr);
+        }
+
+        match check.mismatches.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Size {
+                mismatches: check.mismatches,
+            }),
+        }
+    }
+
+    fn mismatch(&mut self, expr: &Expr, reason: String) {
+        let text = expr.build(self.pattern, self.prefix);
+        self.mismatches.push(format!("`{}`: {reason}", text.trim()));
+    }
+
+    /// Size of `expr` when it has one of its own, `None` when it adapts
+    fn size(&self, expr: &Expr) -> Option<usize> {
+        match expr {
+            Expr::Field { id, .. } => match self.pattern.get_field(id)?.ftype() {
+                FieldType::Variable(regset) if regset.attach_type() == AttachKind::Variables => {
+                    Some(regset.size()).filter(|size| *size > 0)
+                }
+                _ => None,
+            },
+            Expr::Var { id } => self
+                .locals
+                .get(id)
+                .copied()
+                .or_else(|| register_file().size_of(id)),
+            Expr::Reg { id } => register_file().size_of(id),
+            Expr::Local { size, .. } | Expr::Size { size, .. } | Expr::Ptr { size, .. } => {
+                Some(*size)
+            }
+            Expr::Trunc { var, size } => self
+                .size(var)
+                .and_then(|var_size| var_size.checked_sub(*size))
+                .filter(|size| *size > 0),
+            Expr::Group { expr } | Expr::Indirect { val: expr } => self.size(expr),
+            Expr::Unary { op: Op::Bang, .. } => Some(1),
+            Expr::Unary { expr, .. } => self.size(expr),
+            Expr::Binary { lhs, op, rhs } => match op {
+                Op::Copy => None,
+                Op::LShft | Op::RShft | Op::ARShft => self.size(lhs),
+                Op::Plus | Op::Minus | Op::Mult | Op::Rem | Op::BitOr | Op::BitAnd | Op::BitXor => {
+                    self.size(lhs).or_else(|| self.size(rhs))
+                }
+                _ => Some(1),
+            },
+            Expr::Macro { id, .. } => {
+                matches!(id.as_str(), "carry" | "scarry" | "sborrow").then_some(1)
+            }
+            _ => None,
+        }
+    }
+
+    fn stmt(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Line { current, next } => {
+                self.stmt(current);
+                if let Some(line) = next {
+                    self.stmt(line);
+                }
+            }
+            _ => self.visit(expr),
+        }
+    }
+
+    /// Checks `expr` and its operands, declaring the locals it introduces
+    fn visit(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Line { .. } => self.stmt(expr),
+            // Macros are checked as expanded at the call site
+            Expr::MacroCall { .. } => self.stmt(&expr.clone().inline_macros()),
+            Expr::Local { var, size } => {
+                if let Expr::Var { id } = &**var {
+                    self.locals.insert(id.clone(), *size);
+                }
+            }
+            Expr::Size { var, size } => {
+                self.visit(var);
+                if let Some(var_size) = self.size(var)
+                    && var_size < *size
+                {
+                    self.mismatch(expr, format!("{var_size} byte(s) read as {size}"));
+                }
+            }
+            Expr::Trunc { var, size } => {
+                self.visit(var);
+                if let Some(var_size) = self.size(var)
+                    && var_size <= *size
+                {
+                    self.mismatch(
+                        expr,
+                        format!("truncates {size} byte(s) of a {var_size}-byte value"),
+                    );
+                }
+            }
+            Expr::Ptr { addr: val, .. }
+            | Expr::Ref { var: val }
+            | Expr::Group { expr: val }
+            | Expr::Indirect { val }
+            | Expr::Return { addr: val }
+            | Expr::Call { addr: val }
+            | Expr::Goto { dest: val } => self.visit(val),
+            Expr::Unary {
+                op: Op::Bang,
+                expr: val,
+            } => self.expect(expr, val, 1),
+            Expr::Unary { expr: val, .. } => self.visit(val),
+            Expr::IfGoto { cond, goto } => {
+                self.expect(expr, cond, 1);
+                self.visit(goto);
+            }
+            Expr::Binary { lhs, op, rhs } => match op {
+                Op::Copy => {
+                    self.visit(lhs);
+                    match self.size(lhs) {
+                        Some(size) => self.expect(expr, rhs, size),
+                        None => self.visit(rhs),
+                    }
+                }
+                // The shift amount has a size of its own
+                Op::LShft | Op::RShft | Op::ARShft => {
+                    self.visit(lhs);
+                    self.visit(rhs);
+                }
+                Op::And | Op::Or | Op::Xor => {
+                    self.expect(expr, lhs, 1);
+                    self.expect(expr, rhs, 1);
+                }
+                _ => self.same(expr, lhs, rhs),
+            },
+            Expr::Macro { id, params } => match (id.as_str(), params.as_slice()) {
+                ("carry" | "scarry" | "sborrow", [lhs, rhs]) => self.same(expr, lhs, rhs),
+                _ => params.iter().for_each(|param| self.visit(param)),
+            },
+            Expr::Field { .. }
+            | Expr::Var { .. }
+            | Expr::Reg { .. }
+            | Expr::Number { .. }
+            | Expr::Label { .. }
+            | Expr::Build { .. } => {}
+        }
+    }
+
+    /// Both operands of `expr` must have the same size
+    fn same(&mut self, expr: &Expr, lhs: &Expr, rhs: &Expr) {
+        self.visit(lhs);
+        self.visit(rhs);
+
+        match (self.size(lhs), self.size(rhs)) {
+            (Some(lsize), Some(rsize)) if lsize != rsize => {
+                self.mismatch(expr, format!("operands of {lsize} and {rsize} byte(s)"))
+            }
+            (Some(size), None) => self.fits(expr, rhs, size),
+            (None, Some(size)) => self.fits(expr, lhs, size),
+            _ => {}
+        }
+    }
+
+    /// `val` is used where `size` bytes are expected
+    fn expect(&mut self, expr: &Expr, val: &Expr, size: usize) {
+        self.visit(val);
+
+        match self.size(val) {
+            Some(val_size) if val_size != size => self.mismatch(
+                expr,
+                format!("{val_size} byte(s) where {size} are expected"),
+            ),
+            Some(_) => {}
+            None => self.fits(expr, val, size),
+        }
+    }
+
+    /// The constants of `val` must fit in the `size` bytes of their context,
+    /// or be silently truncated.
+    fn fits(&mut self, expr: &Expr, val: &Expr, size: usize) {
+        let bits = 8 * size;
+
+        match val {
+            Expr::Number { val }
+                if bits < 128 && (*val < -(1 << (bits - 1)) || *val >= 1 << bits) =>
+            {
+                self.mismatch(expr, format!("{val:#x} doesn't fit in {size} byte(s)"));
+            }
+            Expr::Field { id, .. } => {
+                if let Some(field) = self.pattern.get_field(id)
+                    && !matches!(field.ftype(), FieldType::Variable(_) | FieldType::Table(_))
+                    && field.len() > bits
+                {
+                    self.mismatch(
+                        expr,
+                        format!(
+                            "field {id} has {} bits, more than {size} byte(s)",
+                            field.len()
+                        ),
+                    );
+                }
+            }
+            Expr::Group { expr: val } | Expr::Unary { expr: val, .. } => self.fits(expr, val, size),
+            Expr::Binary { lhs, op, rhs } => match op {
+                Op::LShft | Op::RShft | Op::ARShft => self.fits(expr, lhs, size),
+                _ => {
+                    self.fits(expr, lhs, size);
+                    self.fits(expr, rhs, size);
+                }
+            },
+            Expr::Macro { id, params } => {
+                if let ("zext" | "sext", [param]) = (id.as_str(), params.as_slice())
+                    && let Some(param_size) = self.size(param)
+                    && param_size > size
+                {
+                    self.mismatch(expr, format!("extends {param_size} byte(s) into {size}"));
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slaspec::builder::SLASpecBuilder;
+    use crate::slaspec::instructions::core::{InstrBuilder, InstrFamilyBuilder, Prefixed};
+    use crate::slaspec::instructions::expr_util::*;
+    use crate::slaspec::instructions::pattern::{ProtoField, ProtoPattern, RegisterSet};
+    use crate::slaspec::instructions::simplify::simplify;
+
+    fn ifam() -> InstrFamilyBuilder {
+        InstrFamilyBuilder::new_16(
+            "Test",
+            "Test family",
+            "tst",
+            ProtoPattern::new(vec![
+                ProtoField::new("sig", FieldType::Mask(0x7f), 7),
+                ProtoField::new("imm", FieldType::UImmVal, 3),
+                ProtoField::new("src", FieldType::Variable(RegisterSet::DReg), 3),
+                ProtoField::new("dst", FieldType::Variable(RegisterSet::DRegL), 3),
+            ]),
+        )
+    }
+
+    /// The mismatches reported for `pcode`, with the instruction they belong to
+    fn mismatches(pcode: Expr) -> (String, Vec<String>) {
+        let instr = InstrBuilder::new(&ifam())
+            .name("Move")
+            .display("{dst} = {src}".to_string())
+            .add_pcode(pcode);
+
+        match instr.build("instruction", String::new()) {
+            Err(Error::Instr { name, source, .. }) => match *source {
+                Error::Size { mismatches } => (name, mismatches),
+                err => panic!("{err}"),
+            },
+            res => panic!("{:?}", res.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn reports_copies_between_sizes() {
+        let (name, found) = mismatches(e_copy(e_rfield("dst"), e_rfield("src")));
+        assert_eq!(name, "Move");
+        assert_eq!(
+            found,
+            ["`tstDstDRegL = tstSrcDReg`: 4 byte(s) where 2 are expected"]
+        );
+    }
+
+    #[test]
+    fn reports_operands_of_different_sizes() {
+        let (name, found) = mismatches(e_copy(
+            e_rfield("dst"),
+            b_bin(e_rfield("src"), Op::Plus, e_rfield("dst")),
+        ));
+        assert_eq!(name, "Move");
+        assert!(
+            found
+                .iter()
+                .any(|mismatch| mismatch.ends_with("operands of 4 and 2 byte(s)")),
+            "{found:?}"
+        );
+    }
+
+    #[test]
+    fn reports_varnode_sizes() {
+        let (name, found) = mismatches(e_copy(e_rfield("src"), b_size(e_rfield("dst"), 4)));
+        assert_eq!(name, "Move");
+        assert_eq!(found.len(), 1);
+        assert!(found[0].ends_with("2 byte(s) read as 4"), "{found:?}");
+
+        let (_, found) = mismatches(e_copy(e_rfield("dst"), b_num(0x12345)));
+        assert!(
+            found[0].ends_with("0x12345 doesn't fit in 2 byte(s)"),
+            "{found:?}"
+        );
+    }
+
+    #[test]
+    fn passes_the_full_spec() {
+        let slab = SLASpecBuilder::new();
+
+        for ifam in slab.families() {
+            for instr in ifam.instrs() {
+                let pcodes = simplify(instr.get_pcodes());
+                if let Err(err) = SizeCheck::check(instr.pattern(), &instr.prefix(), &pcodes) {
+                    panic!("{} {}: {err}", ifam.name(), instr.get_name());
+                }
+            }
+        }
+    }
+}
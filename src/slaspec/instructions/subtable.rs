@@ -0,0 +1,549 @@
+use std::collections::{BTreeSet, HashMap};
+
+use itertools::Itertools;
+
+use crate::slaspec::sleigh::{
+    AttachKind, Constraint, Constructor, DisplaySection, Document, Item, Layout, Stmt,
+};
+
+use super::{
+    core::InstrBuilder,
+    format::display_format,
+    pattern::{Field, FieldType, Pattern, RegisterSet},
+    util::mask_hex,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum SubTableRow {
+    Export {
+        selectors: Vec<(String, u16, usize)>,
+        export: String,
+    },
+    Build {
+        guard: (String, u16, usize),
+        table: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubTable {
+    name: String,
+    field_id: String,
+    rows: Vec<SubTableRow>,
+}
+
+impl SubTable {
+    /// A table matching any of `tables` when their `guard` field holds `val`.
+    pub fn builds(
+        name: &str,
+        field_id: &str,
+        guard_val: u16,
+        tables: Vec<(String, String)>,
+    ) -> Self {
+        SubTable {
+            name: name.to_string(),
+            field_id: field_id.to_string(),
+            rows: tables
+                .into_iter()
+                .map(|(guard, table)| SubTableRow::Build {
+                    guard: (guard, guard_val, 1),
+                    table,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn rows_len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Token exported by the row whose selectors hold their values, `value`
+    /// gives the value of a selector token.
+    pub fn exported(&self, value: impl Fn(&str) -> Option<u16>) -> Option<String> {
+        self.rows.iter().find_map(|row| match row {
+            SubTableRow::Export { selectors, export } => selectors
+                .iter()
+                .all(|(token, val, _)| value(token) == Some(*val))
+                .then(|| export.clone()),
+            _ => None,
+        })
+    }
+
+    /// Guard token, guard value and built table of each row building a table.
+    pub fn built_tables(&self) -> Vec<(String, u16, String)> {
+        self.rows
+            .iter()
+            .filter_map(|row| match row {
+                SubTableRow::Build {
+                    guard: (token, val, _),
+                    table,
+                } => Some((token.clone(), *val, table.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn items(&self) -> Vec<Item> {
+        self.rows
+            .iter()
+            .map(|row| {
+                let (constraints, operand, stmt) = match row {
+                    SubTableRow::Export { selectors, export } => (
+                        selectors
+                            .iter()
+                            .map(|(token, val, len)| Constraint::Eq {
+                                field: token.clone(),
+                                value: mask_hex(*val, *len),
+                            })
+                            .collect::<Vec<Constraint>>(),
+                        export,
+                        format!("export {export}"),
+                    ),
+                    SubTableRow::Build {
+                        guard: (token, val, len),
+                        table,
+                    } => (
+                        vec![Constraint::Prefix(Box::new(Constraint::Eq {
+                            field: token.clone(),
+                            value: mask_hex(*val, *len),
+                        }))],
+                        table,
+                        format!("build {table}"),
+                    ),
+                };
+
+                Item::Constructor(Constructor {
+                    table: self.name.clone(),
+                    display: DisplaySection::Operands(format!(" {operand}")),
+                    pattern: vec![
+                        constraints
+                            .into_iter()
+                            .chain([Constraint::Operand(operand.clone())])
+                            .collect(),
+                    ],
+                    actions: vec![],
+                    semantics: vec![Stmt::Op(stmt)],
+                    layout: Layout::Inline,
+                })
+            })
+            .collect()
+    }
+
+    pub fn build(&self) -> String {
+        Document {
+            items: self.items(),
+        }
+        .build()
+    }
+}
+
+type FieldPos = (usize, usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum FieldVal {
+    Mask(u16),
+    Reg(RegisterSet),
+}
+
+fn neutral_pattern(pattern: &Pattern) -> Pattern {
+    let mut neutral = pattern.clone();
+
+    for field in pattern.fields().iter().flatten() {
+        if field.is_var() {
+            neutral = neutral.set_field_type(&field.id(), FieldType::Any);
+        }
+    }
+
+    neutral
+}
+
+fn field_kind(field: &Field) -> String {
+    match field.ftype() {
+        FieldType::Mask(_) => "mask".to_string(),
+        FieldType::Variable(_) => "var".to_string(),
+        ftype => format!("{ftype:?}"),
+    }
+}
+
+fn template_key(instr: &InstrBuilder, prefix: &str) -> String {
+    let neutral = neutral_pattern(instr.pattern());
+    let layout = instr
+        .pattern()
+        .fields()
+        .iter()
+        .enumerate()
+        .flat_map(|(wi, word)| {
+            word.iter().map(move |f| {
+                format!(
+                    "{wi}:{}:{}:{}:{}",
+                    f.id(),
+                    f.start(),
+                    f.end(),
+                    field_kind(f)
+                )
+            })
+        })
+        .join(",");
+
+    format!(
+        "{}\n{}\n{}\n{}\n{layout}",
+        instr.get_name(),
+        display_format(&instr.get_display(), &neutral, prefix)
+            .map_or_else(|_| instr.get_display(), |(display, _)| display),
+        instr.get_actions().build(&neutral, prefix),
+        instr.get_pcodes().build(&neutral, prefix),
+    )
+}
+
+fn field_val(field: &Field) -> Option<FieldVal> {
+    match field.ftype() {
+        FieldType::Mask(val) => Some(FieldVal::Mask(val)),
+        FieldType::Variable(regset) => Some(FieldVal::Reg(regset)),
+        _ => None,
+    }
+}
+
+fn field_at(instr: &InstrBuilder, pos: FieldPos) -> Field {
+    instr.pattern().fields()[pos.0][pos.1].clone()
+}
+
+fn register_table(
+    tables: &mut Vec<SubTable>,
+    field: &Field,
+    prefix: &str,
+    rows: Vec<SubTableRow>,
+) -> usize {
+    if let Some(index) = tables
+        .iter()
+        .position(|t| t.field_id == field.id() && t.rows == rows)
+    {
+        return index;
+    }
+
+    let index = tables.len();
+    let mut table_field = Pattern::new([vec![field.clone()], vec![], vec![], vec![]]);
+    table_field = table_field.set_field_type(&field.id(), FieldType::Table(index));
+
+    tables.push(SubTable {
+        name: table_field.fields()[0][0].token_name(prefix),
+        field_id: field.id(),
+        rows,
+    });
+
+    index
+}
+
+/// Finds the smallest set of selector fields whose mask values decide
+/// which register set a variable field uses across a group.
+fn find_selectors(
+    members: &[&InstrBuilder],
+    var: FieldPos,
+    candidates: &[FieldPos],
+) -> Option<Vec<FieldPos>> {
+    for size in 1..=candidates.len() {
+        for sel in candidates.iter().copied().combinations(size) {
+            let mut mapping: HashMap<Vec<FieldVal>, FieldVal> = HashMap::new();
+            let functional = members.iter().all(|instr| {
+                let key: Vec<FieldVal> = sel
+                    .iter()
+                    .filter_map(|pos| field_val(&field_at(instr, *pos)))
+                    .collect();
+                let val = field_val(&field_at(instr, var)).unwrap();
+                *mapping.entry(key).or_insert(val) == val
+            });
+
+            if functional {
+                return Some(sel);
+            }
+        }
+    }
+
+    None
+}
+
+/// Replaces a group of constructors rendering the same template by one parent
+/// per combination of their other masks, each register field becoming a
+/// sub-table indexed by the masks that select its register set.
+///
+/// Only register-set fields are factored. Constructors that differ in a mode
+/// (`Mmode`, `AccOp`), an operand size or an extension render different
+/// templates and stay constructors of their own: the registers alone take
+/// Dsp32Mac from 7029 constructors to 854 and the whole spec from about 27 MiB
+/// to 5 MiB of .sla, `sawfish stats -s` reports it per family.
+fn factor_group(
+    members: &[&InstrBuilder],
+    prefix: &str,
+    tables: &mut Vec<SubTable>,
+) -> Option<Vec<InstrBuilder>> {
+    let base = members[0].pattern().fields();
+    let mut var_mask = Vec::new();
+    let mut var_regs = Vec::new();
+
+    for (wi, word) in base.iter().enumerate() {
+        for (fi, field) in word.iter().enumerate() {
+            let vals: BTreeSet<FieldVal> = members
+                .iter()
+                .filter_map(|instr| field_val(&field_at(instr, (wi, fi))))
+                .collect();
+
+            if vals.len() <= 1 {
+                continue;
+            }
+
+            match field.ftype() {
+                FieldType::Mask(_) => var_mask.push((wi, fi)),
+                FieldType::Variable(_) => var_regs.push((wi, fi)),
+                _ => {}
+            }
+        }
+    }
+
+    if var_regs.is_empty() {
+        return None;
+    }
+
+    let mut selectors: Vec<(FieldPos, Vec<FieldPos>)> = Vec::new();
+
+    for var in var_regs {
+        let regsets: BTreeSet<RegisterSet> = members
+            .iter()
+            .filter_map(|instr| match field_at(instr, var).ftype() {
+                FieldType::Variable(regset) => Some(regset),
+                _ => None,
+            })
+            .collect();
+
+        // A sub-table can only export varnodes of one size
+        let sizes: BTreeSet<usize> = regsets.iter().map(|r| r.size()).collect();
+        if sizes.len() != 1
+            || regsets
+                .iter()
+                .any(|r| r.attach_type() != AttachKind::Variables)
+        {
+            return None;
+        }
+
+        let candidates: Vec<FieldPos> = var_mask
+            .iter()
+            .copied()
+            .filter(|pos| pos.0 == var.0 && !selectors.iter().any(|(_, s)| s.contains(pos)))
+            .collect();
+
+        selectors.push((var, find_selectors(members, var, &candidates)?));
+    }
+
+    let free: Vec<FieldPos> = var_mask
+        .iter()
+        .copied()
+        .filter(|pos| !selectors.iter().any(|(_, s)| s.contains(pos)))
+        .collect();
+
+    let mut partitions: Vec<(Vec<FieldVal>, Vec<&InstrBuilder>)> = Vec::new();
+    for instr in members {
+        let key: Vec<FieldVal> = free
+            .iter()
+            .filter_map(|pos| field_val(&field_at(instr, *pos)))
+            .collect();
+
+        match partitions.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, part)) => part.push(instr),
+            None => partitions.push((key, vec![instr])),
+        }
+    }
+
+    let mut parents = Vec::new();
+
+    for (_, part) in partitions {
+        let all_vals: BTreeSet<Vec<FieldVal>> = part
+            .iter()
+            .map(|instr| {
+                selectors
+                    .iter()
+                    .flat_map(|(var, sel)| sel.iter().chain([var]))
+                    .filter_map(|pos| field_val(&field_at(instr, *pos)))
+                    .collect()
+            })
+            .collect();
+
+        let mut product = 1;
+        let mut pattern = part[0].pattern().clone();
+
+        for (var, sel) in &selectors {
+            let rows: BTreeSet<SubTableRow> = part
+                .iter()
+                .map(|instr| SubTableRow::Export {
+                    selectors: sel
+                        .iter()
+                        .map(|pos| {
+                            let field = field_at(instr, *pos);
+                            let val = match field.ftype() {
+                                FieldType::Mask(val) => val,
+                                _ => 0,
+                            };
+                            (field.token_name(prefix), val, field.len())
+                        })
+                        .collect(),
+                    export: field_at(instr, *var).token_name(prefix),
+                })
+                .collect();
+
+            product *= rows.len();
+
+            let var_field = field_at(part[0], *var);
+            let index = register_table(tables, &var_field, prefix, rows.into_iter().collect());
+
+            for pos in sel {
+                pattern = pattern.set_field_type(&field_at(part[0], *pos).id(), FieldType::Blank);
+            }
+            pattern = pattern.set_field_type(&var_field.id(), FieldType::Table(index));
+        }
+
+        // The parent and its sub-tables must accept exactly the encodings of the group
+        if product != all_vals.len() {
+            return None;
+        }
+
+        parents.push(part[0].clone().set_pattern(pattern));
+    }
+
+    Some(parents)
+}
+
+pub fn factor_instrs(
+    instrs: &[InstrBuilder],
+    prefix: &str,
+    tables: &mut Vec<SubTable>,
+) -> Vec<InstrBuilder> {
+    let mut groups: Vec<Vec<&InstrBuilder>> = Vec::new();
+    let mut group_index: HashMap<String, usize> = HashMap::new();
+
+    for instr in instrs {
+        let key = template_key(instr, prefix);
+
+        match group_index.get(&key) {
+            Some(index) => groups[*index].push(instr),
+            None => {
+                group_index.insert(key, groups.len());
+                groups.push(vec![instr]);
+            }
+        }
+    }
+
+    let mut factored = Vec::new();
+
+    for members in groups {
+        if members.len() > 1 {
+            // Work on a copy so a failed attempt leaves no orphan tables behind
+            let mut group_tables = tables.clone();
+            if let Some(mut parents) = factor_group(&members, prefix, &mut group_tables) {
+                *tables = group_tables;
+                factored.append(&mut parents);
+                continue;
+            }
+        }
+
+        factored.extend(members.into_iter().cloned());
+    }
+
+    factored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slaspec::instructions::core::{InstrFactory, InstrFamilyBuilder};
+    use crate::slaspec::instructions::expr_util::*;
+    use crate::slaspec::instructions::pattern::{ProtoField, ProtoPattern};
+
+    /// `Mv`, its destination register set chosen by `s`
+    struct MoveFactory([RegisterSet; 2]);
+
+    impl InstrFactory for MoveFactory {
+        fn build_instrs(&self, ifam: &InstrFamilyBuilder) -> Vec<InstrBuilder> {
+            self.0
+                .iter()
+                .enumerate()
+                .map(|(s, regset)| {
+                    InstrBuilder::new(ifam)
+                        .name("Mv")
+                        .display("{dst} = {src}".to_string())
+                        .set_field_type("s", FieldType::Mask(s as u16))
+                        .set_field_type("dst", FieldType::Variable(*regset))
+                        .add_pcode(e_copy(e_rfield("dst"), e_rfield("src")))
+                })
+                .collect()
+        }
+    }
+
+    fn instrs(regsets: [RegisterSet; 2]) -> Vec<InstrBuilder> {
+        let mut ifam = InstrFamilyBuilder::new_16(
+            "Test",
+            "Test family",
+            "tst",
+            ProtoPattern::new(vec![
+                ProtoField::new("sig", FieldType::Mask(0x1ff), 9),
+                ProtoField::new("s", FieldType::Blank, 1),
+                ProtoField::new("dst", FieldType::Blank, 3),
+                ProtoField::new("src", FieldType::Variable(RegisterSet::DReg), 3),
+            ]),
+        );
+        ifam.add_instrs(&MoveFactory(regsets));
+
+        ifam.instrs().into_iter().cloned().collect()
+    }
+
+    fn pos(instr: &InstrBuilder, id: &str) -> FieldPos {
+        (
+            0,
+            instr.pattern().fields()[0]
+                .iter()
+                .position(|field| field.id() == id)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn finds_the_masks_selecting_a_register_set() {
+        let instrs = instrs([RegisterSet::DReg, RegisterSet::PReg]);
+        let members: Vec<&InstrBuilder> = instrs.iter().collect();
+        let (s, dst) = (pos(members[0], "s"), pos(members[0], "dst"));
+
+        assert_eq!(find_selectors(&members, dst, &[s]), Some(vec![s]));
+        assert_eq!(find_selectors(&members, dst, &[]), None);
+    }
+
+    #[test]
+    fn factors_register_sets_into_a_sub_table() {
+        let instrs = instrs([RegisterSet::DReg, RegisterSet::PReg]);
+        let mut tables = Vec::new();
+        let factored = factor_instrs(&instrs, "tst", &mut tables);
+
+        assert_eq!(factored.len(), 1);
+        assert_eq!(tables.len(), 1);
+        assert_eq!(
+            tables[0].build(),
+            "tstDstTbl0: tstDstDReg is tstS=0x0 & tstDstDReg { export tstDstDReg; }\n\
+             tstDstTbl0: tstDstPReg is tstS=0x1 & tstDstPReg { export tstDstPReg; }\n"
+        );
+
+        let fields = &factored[0].pattern().fields()[0];
+        let ftype = |id: &str| fields.iter().find(|f| f.id() == id).unwrap().ftype();
+        assert_eq!(ftype("s"), FieldType::Blank);
+        assert_eq!(ftype("dst"), FieldType::Table(0));
+    }
+
+    #[test]
+    fn keeps_register_sets_of_different_sizes() {
+        let instrs = instrs([RegisterSet::DReg, RegisterSet::DRegL]);
+        let members: Vec<&InstrBuilder> = instrs.iter().collect();
+        let mut tables = Vec::new();
+
+        assert!(factor_group(&members, "tst", &mut tables).is_none());
+        assert_eq!(factor_instrs(&instrs, "tst", &mut tables).len(), 2);
+        assert!(tables.is_empty());
+    }
+}
@@ -0,0 +1,800 @@
+use std::collections::HashMap;
+
+use super::disasm::{Disassembler, Instruction, eval, field_num};
+use super::globals::{PROGRAM_COUNTER, REGISTER_SPACE};
+use super::instructions::core::{InstrBuilder, InstrFamilyBuilder};
+use super::instructions::expr::{Expr, Op};
+use super::instructions::pattern::{Field, FieldType};
+use super::registers::register_file;
+use super::sleigh::AttachKind;
+
+fn mask(size: usize) -> u128 {
+    if size >= 16 {
+        u128::MAX
+    } else {
+        (1 << (8 * size)) - 1
+    }
+}
+
+/// A varnode value, kept zero-extended to its size in bytes.
+#[derive(Debug, Clone, Copy)]
+struct Val {
+    bits: u128,
+    size: usize,
+}
+
+impl Val {
+    fn new(bits: u128, size: usize) -> Self {
+        Val {
+            bits: bits & mask(size),
+            size,
+        }
+    }
+
+    fn num(val: i128, size: usize) -> Self {
+        Self::new(val as u128, size)
+    }
+
+    fn flag(cond: bool) -> Self {
+        Self::new(cond as u128, 1)
+    }
+
+    fn signed(&self) -> i128 {
+        let shift = 128 - 8 * self.size.min(16);
+        ((self.bits << shift) as i128) >> shift
+    }
+
+    fn is_set(&self) -> bool {
+        self.bits != 0
+    }
+}
+
+/// Storage written by a copy.
+#[derive(Debug, Clone)]
+enum Place {
+    Reg {
+        offset: usize,
+        size: usize,
+    },
+    Bits {
+        offset: usize,
+        size: usize,
+        start: usize,
+        len: usize,
+    },
+    Local {
+        id: String,
+        size: usize,
+    },
+    Ram {
+        addr: u32,
+        size: usize,
+    },
+}
+
+impl Place {
+    fn size(&self) -> usize {
+        match self {
+            Place::Reg { size, .. } | Place::Local { size, .. } | Place::Ram { size, .. } => *size,
+            Place::Bits { len, .. } => len.div_ceil(8),
+        }
+    }
+
+    /// The `size` least significant bytes of the place.
+    fn low(self, size: usize) -> Place {
+        match self {
+            Place::Reg { offset, .. } => Place::Reg { offset, size },
+            Place::Local { id, .. } => Place::Local { id, size },
+            Place::Ram { addr, .. } => Place::Ram { addr, size },
+            bits => bits,
+        }
+    }
+}
+
+enum Flow {
+    Next,
+    Label(String),
+    Branch(u32),
+}
+
+/// Register file and sparse RAM of a Blackfin+ core.
+#[derive(Debug, Clone)]
+pub struct Machine {
+    regs: Vec<u8>,
+    ram: HashMap<u32, u8>,
+    ops: Vec<String>,
+}
+
+impl Machine {
+    pub fn new() -> Self {
+        let size = register_file()
+            .regs()
+            .map(|reg| reg.offset() + reg.size())
+            .max()
+            .unwrap_or_default();
+
+        Machine {
+            regs: vec![0; size],
+            ram: HashMap::new(),
+            ops: Vec::new(),
+        }
+    }
+
+    fn reg_place(name: &str) -> Option<Place> {
+        let rf = register_file();
+
+        if let Some(reg) = rf.regs().find(|reg| reg.name() == name) {
+            return Some(Place::Reg {
+                offset: reg.offset(),
+                size: reg.size(),
+            });
+        }
+
+        rf.bit_range(name).map(|(reg, start, len)| Place::Bits {
+            offset: reg.offset(),
+            size: reg.size(),
+            start,
+            len,
+        })
+    }
+
+    fn read_space(bytes: impl Iterator<Item = u8>) -> u128 {
+        bytes
+            .enumerate()
+            .fold(0, |val, (i, byte)| val | (byte as u128) << (8 * i))
+    }
+
+    fn read_place(&self, place: &Place, locals: &HashMap<String, Val>) -> Result<Val, String> {
+        Ok(match place {
+            Place::Reg { offset, size } => Val::new(
+                Self::read_space(self.regs[*offset..offset + size].iter().copied()),
+                *size,
+            ),
+            Place::Bits {
+                offset,
+                size,
+                start,
+                len,
+            } => {
+                let reg = Self::read_space(self.regs[*offset..offset + size].iter().copied());
+                Val::new((reg >> start) & ((1 << len) - 1), len.div_ceil(8))
+            }
+            Place::Local { id, size } => match locals.get(id) {
+                Some(val) => Val::new(val.bits, *size),
+                None => return Err(format!("local {id} is read before being written")),
+            },
+            Place::Ram { addr, size } => Val::new(self.load(*addr, *size), *size),
+        })
+    }
+
+    fn write_place(&mut self, place: &Place, val: Val, locals: &mut HashMap<String, Val>) {
+        match place {
+            Place::Reg { offset, size } => {
+                for (i, byte) in self.regs[*offset..offset + size].iter_mut().enumerate() {
+                    *byte = (val.bits >> (8 * i)) as u8;
+                }
+            }
+            Place::Bits {
+                offset,
+                size,
+                start,
+                len,
+            } => {
+                let bits = ((1u128 << len) - 1) << start;
+                let reg = Self::read_space(self.regs[*offset..offset + size].iter().copied());
+                let reg = (reg & !bits) | ((val.bits << start) & bits);
+                self.write_place(
+                    &Place::Reg {
+                        offset: *offset,
+                        size: *size,
+                    },
+                    Val::new(reg, *size),
+                    locals,
+                );
+            }
+            Place::Local { id, size } => {
+                let local = locals.entry(id.clone()).or_insert(Val::new(0, *size));
+                local.bits = (local.bits & !mask(*size)) | (val.bits & mask(*size));
+            }
+            Place::Ram { addr, size } => self.store(*addr, *size, val.bits),
+        }
+    }
+
+    /// Value of the register or flag `name`.
+    pub fn reg(&self, name: &str) -> u64 {
+        match Self::reg_place(name) {
+            Some(place) => self
+                .read_place(&place, &HashMap::new())
+                .map_or(0, |val| val.bits as u64),
+            None => panic!("Register {name} is not defined in the register file"),
+        }
+    }
+
+    pub fn set_reg(&mut self, name: &str, val: u64) {
+        match Self::reg_place(name) {
+            Some(place) => {
+                let size = place.size();
+                self.write_place(&place, Val::new(val as u128, size), &mut HashMap::new());
+            }
+            None => panic!("Register {name} is not defined in the register file"),
+        }
+    }
+
+    /// Little-endian value of the `size` bytes at `addr`, unwritten bytes
+    /// read as zero.
+    pub fn load(&self, addr: u32, size: usize) -> u128 {
+        Self::read_space(
+            (0..size as u32).map(|i| self.ram.get(&addr.wrapping_add(i)).copied().unwrap_or(0)),
+        )
+    }
+
+    pub fn store(&mut self, addr: u32, size: usize, val: u128) {
+        for i in 0..size {
+            self.ram
+                .insert(addr.wrapping_add(i as u32), (val >> (8 * i)) as u8);
+        }
+    }
+
+    pub fn write_bytes(&mut self, addr: u32, bytes: &[u8]) {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.ram.insert(addr.wrapping_add(i as u32), *byte);
+        }
+    }
+
+    /// User-defined p-code operations called so far, in order.
+    pub fn ops(&self) -> &[String] {
+        &self.ops
+    }
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One constructor being executed, with its operands and temporaries.
+struct Frame<'f> {
+    ifam: &'f InstrFamilyBuilder,
+    instr: &'f InstrBuilder,
+    words: &'f [u16],
+    fields: HashMap<String, (usize, Field)>,
+    vars: HashMap<String, i128>,
+    locals: HashMap<String, Val>,
+    addr: u32,
+    next: u32,
+}
+
+impl<'f> Frame<'f> {
+    fn new(
+        ifam: &'f InstrFamilyBuilder,
+        instr: &'f InstrBuilder,
+        words: &'f [u16],
+        addr: u32,
+        next: u32,
+    ) -> Self {
+        let mut fields = HashMap::new();
+        let mut nums = HashMap::new();
+
+        for (wi, word) in instr.pattern().fields().into_iter().enumerate() {
+            for field in word {
+                if let Some(w) = words.get(wi) {
+                    nums.insert(field.id(), field_num(&field, *w));
+                    fields.insert(field.id(), (wi, field));
+                }
+            }
+        }
+
+        // Values computed at disassembly time are constants for the p-code
+        let mut vars: HashMap<String, i128> = HashMap::new();
+        vars.insert(String::from("inst_start"), addr as i128);
+        vars.insert(String::from("inst_next"), next as i128);
+        for action in instr.get_actions().exprs() {
+            eval(action, &nums, &mut vars);
+        }
+
+        Frame {
+            ifam,
+            instr,
+            words,
+            fields,
+            vars,
+            locals: HashMap::new(),
+            addr,
+            next,
+        }
+    }
+
+    fn field(&self, id: &str) -> Result<&(usize, Field), String> {
+        self.fields
+            .get(id)
+            .ok_or_else(|| format!("{} has no field {id}", self.instr.get_name()))
+    }
+
+    /// Register selected by the field `id`, if it is attached to registers.
+    fn field_reg(&self, id: &str) -> Result<Option<String>, String> {
+        let (wi, field) = self.field(id)?;
+
+        match field.ftype() {
+            FieldType::Variable(regset) if regset.attach_type() == AttachKind::Variables => {
+                let index = field_num(field, self.words[*wi]) as usize;
+                match regset.regs().get(index) {
+                    Some(reg) if reg != "_" => Ok(Some(reg.clone())),
+                    _ => Err(format!("field {id} selects no register")),
+                }
+            }
+            FieldType::Table(_) => Err(format!("field {id} is a sub-table")),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Executes the actions and p-code of the instruction model on a [`Machine`].
+/// Hardware loops and delay slots are not modelled.
+pub struct Interpreter<'a> {
+    disasm: Disassembler<'a>,
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new(families: Vec<(usize, &'a [InstrFamilyBuilder])>) -> Self {
+        Interpreter {
+            disasm: Disassembler::new(families),
+        }
+    }
+
+    /// Size in bytes `expr` has regardless of its context, if any.
+    fn nat_size(&self, frame: &Frame, expr: &Expr) -> Option<usize> {
+        match expr {
+            Expr::Field { id, .. } => match frame.field_reg(id) {
+                Ok(Some(reg)) => register_file().size_of(&reg),
+                _ => None,
+            },
+            Expr::Reg { id } => register_file().size_of(id),
+            Expr::Var { id } => frame
+                .locals
+                .get(id)
+                .map(|val| val.size)
+                .or_else(|| register_file().size_of(id)),
+            Expr::Size { size, .. } | Expr::Local { size, .. } | Expr::Ptr { size, .. } => {
+                Some(*size)
+            }
+            Expr::Trunc { var, size } => self
+                .nat_size(frame, var)
+                .map(|var_size| var_size.saturating_sub(*size)),
+            Expr::Group { expr } => self.nat_size(frame, expr),
+            Expr::Indirect { val } => self.nat_size(frame, val),
+            Expr::Unary { op: Op::Bang, .. } => Some(1),
+            Expr::Unary { expr, .. } => self.nat_size(frame, expr),
+            Expr::Binary { lhs, op, rhs } => match op {
+                Op::LShft | Op::RShft | Op::ARShft => self.nat_size(frame, lhs),
+                Op::Plus
+                | Op::Minus
+                | Op::Mult
+                | Op::Rem
+                | Op::BitOr
+                | Op::BitAnd
+                | Op::BitXor
+                | Op::Copy => self
+                    .nat_size(frame, lhs)
+                    .or_else(|| self.nat_size(frame, rhs)),
+                _ => Some(1),
+            },
+            Expr::Macro { id, .. } => {
+                matches!(id.as_str(), "carry" | "scarry" | "sborrow").then_some(1)
+            }
+            _ => None,
+        }
+    }
+
+    fn place(&self, machine: &Machine, frame: &mut Frame, expr: &Expr) -> Result<Place, String> {
+        match expr {
+            Expr::Field { id, .. } => match frame.field_reg(id)? {
+                Some(reg) => Machine::reg_place(&reg).ok_or(format!("unknown register {reg}")),
+                None => Err(format!("field {id} is not a register")),
+            },
+            Expr::Reg { id } => Machine::reg_place(id).ok_or(format!("unknown register {id}")),
+            Expr::Var { id } => match frame.locals.get(id) {
+                Some(val) => Ok(Place::Local {
+                    id: id.clone(),
+                    size: val.size,
+                }),
+                // Names are global in SLEIGH, a variable can name a register
+                None => Machine::reg_place(id).ok_or(format!("local {id} is not declared")),
+            },
+            Expr::Local { var, size } => match &**var {
+                Expr::Var { id } => {
+                    frame.locals.insert(id.clone(), Val::new(0, *size));
+                    Ok(Place::Local {
+                        id: id.clone(),
+                        size: *size,
+                    })
+                }
+                _ => Err(String::from("only variables can be declared local")),
+            },
+            Expr::Size { var, size } => Ok(self.place(machine, frame, var)?.low(*size)),
+            Expr::Ptr { space, addr, size } => {
+                let addr = self.eval(machine, frame, addr, None)?.bits;
+                if space == REGISTER_SPACE {
+                    Ok(Place::Reg {
+                        offset: addr as usize,
+                        size: *size,
+                    })
+                } else {
+                    Ok(Place::Ram {
+                        addr: addr as u32,
+                        size: *size,
+                    })
+                }
+            }
+            Expr::Group { expr } => self.place(machine, frame, expr),
+            _ => Err(format!("can't write to {expr:?}")),
+        }
+    }
+
+    fn eval(
+        &self,
+        machine: &Machine,
+        frame: &mut Frame,
+        expr: &Expr,
+        hint: Option<usize>,
+    ) -> Result<Val, String> {
+        let size = hint.or_else(|| self.nat_size(frame, expr)).unwrap_or(4);
+
+        Ok(match expr {
+            Expr::Number { val } => Val::num(*val, size),
+            Expr::Field { id, .. } => match frame.field_reg(id)? {
+                Some(_) => {
+                    let place = self.place(machine, frame, expr)?;
+                    machine.read_place(&place, &frame.locals)?
+                }
+                None => {
+                    let (wi, field) = frame.field(id)?;
+                    Val::num(field_num(field, frame.words[*wi]), size)
+                }
+            },
+            Expr::Var { id } => match (frame.locals.get(id), frame.vars.get(id)) {
+                (Some(val), _) => *val,
+                (None, Some(val)) => Val::num(*val, size),
+                (None, None) => match id.strip_prefix("0x") {
+                    Some(hex) => Val::new(
+                        u128::from_str_radix(hex, 16)
+                            .map_err(|_| format!("variable {id} has no value"))?,
+                        size,
+                    ),
+                    None => {
+                        let place = self.place(machine, frame, expr)?;
+                        machine.read_place(&place, &frame.locals)?
+                    }
+                },
+            },
+            Expr::Reg { .. } => {
+                let place = self.place(machine, frame, expr)?;
+                machine.read_place(&place, &frame.locals)?
+            }
+            Expr::Ptr { .. } => {
+                let place = self.place(machine, frame, expr)?;
+                machine.read_place(&place, &frame.locals)?
+            }
+            Expr::Size { var, size } => Val::new(self.eval(machine, frame, var, None)?.bits, *size),
+            Expr::Trunc { var, size: cut } => {
+                let val = self.eval(machine, frame, var, None)?;
+                Val::new(
+                    val.bits >> (8 * cut),
+                    hint.unwrap_or(val.size.saturating_sub(*cut)),
+                )
+            }
+            Expr::Ref { var } => match self.place(machine, frame, var)? {
+                Place::Reg { offset, .. } | Place::Bits { offset, .. } => {
+                    Val::new(offset as u128, size)
+                }
+                _ => return Err(String::from("only registers have an address")),
+            },
+            Expr::Group { expr } => self.eval(machine, frame, expr, hint)?,
+            Expr::Indirect { val } => self.eval(machine, frame, val, hint)?,
+            Expr::Unary { op, expr } => match op {
+                Op::Bang => Val::flag(!self.eval(machine, frame, expr, None)?.is_set()),
+                Op::Minus => {
+                    let val = self.eval(machine, frame, expr, Some(size))?;
+                    Val::new(val.bits.wrapping_neg(), size)
+                }
+                Op::BitNot => {
+                    let val = self.eval(machine, frame, expr, Some(size))?;
+                    Val::new(!val.bits, size)
+                }
+                _ => return Err(format!("unsupported unary operator {}", op.to_string())),
+            },
+            Expr::Binary { lhs, op, rhs } => {
+                self.eval_binary(machine, frame, lhs, op, rhs, size)?
+            }
+            Expr::Macro { id, params } => self.eval_macro(machine, frame, id, params, hint)?,
+            _ => return Err(format!("can't evaluate {expr:?}")),
+        })
+    }
+
+    fn eval_binary(
+        &self,
+        machine: &Machine,
+        frame: &mut Frame,
+        lhs: &Expr,
+        op: &Op,
+        rhs: &Expr,
+        size: usize,
+    ) -> Result<Val, String> {
+        match op {
+            Op::And | Op::Or | Op::Xor => {
+                let l = self.eval(machine, frame, lhs, None)?.is_set();
+                let r = self.eval(machine, frame, rhs, None)?.is_set();
+                return Ok(Val::flag(match op {
+                    Op::And => l && r,
+                    Op::Or => l || r,
+                    _ => l ^ r,
+                }));
+            }
+            Op::LShft | Op::RShft | Op::ARShft => {
+                let val = self.eval(machine, frame, lhs, Some(size))?;
+                let shift = self.eval(machine, frame, rhs, None)?.bits;
+                let bits = 8 * size as u128;
+                return Ok(match op {
+                    Op::LShft if shift < bits => Val::new(val.bits << shift, size),
+                    Op::RShft if shift < bits => Val::new(val.bits >> shift, size),
+                    Op::ARShft => Val::num(val.signed() >> shift.min(bits - 1), size),
+                    _ => Val::new(0, size),
+                });
+            }
+            Op::Plus | Op::Minus | Op::Mult | Op::Rem | Op::BitOr | Op::BitAnd | Op::BitXor => {
+                let l = self.eval(machine, frame, lhs, Some(size))?.bits;
+                let r = self.eval(machine, frame, rhs, Some(size))?.bits;
+                return Ok(Val::new(
+                    match op {
+                        Op::Plus => l.wrapping_add(r),
+                        Op::Minus => l.wrapping_sub(r),
+                        Op::Mult => l.wrapping_mul(r),
+                        Op::Rem if r == 0 => return Err(String::from("remainder by zero")),
+                        Op::Rem => l % r,
+                        Op::BitOr => l | r,
+                        Op::BitAnd => l & r,
+                        _ => l ^ r,
+                    },
+                    size,
+                ));
+            }
+            Op::Copy | Op::BitNot | Op::Bang => {
+                return Err(format!("{} is not a binary operator", op.to_string()));
+            }
+            _ => {}
+        }
+
+        // Both sides of a comparison have the size of the sized one
+        let size = self
+            .nat_size(frame, lhs)
+            .or_else(|| self.nat_size(frame, rhs))
+            .unwrap_or(4);
+        let l = self.eval(machine, frame, lhs, Some(size))?;
+        let r = self.eval(machine, frame, rhs, Some(size))?;
+
+        Ok(Val::flag(match op {
+            Op::EQ => l.bits == r.bits,
+            Op::NE => l.bits != r.bits,
+            Op::LT => l.bits < r.bits,
+            Op::LE => l.bits <= r.bits,
+            Op::GT => l.bits > r.bits,
+            Op::GE => l.bits >= r.bits,
+            Op::LTS => l.signed() < r.signed(),
+            Op::LES => l.signed() <= r.signed(),
+            Op::GTS => l.signed() > r.signed(),
+            _ => l.signed() >= r.signed(),
+        }))
+    }
+
+    fn eval_macro(
+        &self,
+        machine: &Machine,
+        frame: &mut Frame,
+        id: &str,
+        params: &[Box<Expr>],
+        hint: Option<usize>,
+    ) -> Result<Val, String> {
+        match (id, params) {
+            ("zext" | "sext", [param]) => {
+                let val = self.eval(machine, frame, param, None)?;
+                let size = hint.unwrap_or(val.size);
+                Ok(if id == "zext" {
+                    Val::new(val.bits, size)
+                } else {
+                    Val::num(val.signed(), size)
+                })
+            }
+            ("carry" | "scarry" | "sborrow", [a, b]) => {
+                let size = self
+                    .nat_size(frame, a)
+                    .or_else(|| self.nat_size(frame, b))
+                    .unwrap_or(4);
+                let a = self.eval(machine, frame, a, Some(size))?;
+                let b = self.eval(machine, frame, b, Some(size))?;
+                let sum = Val::new(a.bits.wrapping_add(b.bits), size);
+                let diff = Val::new(a.bits.wrapping_sub(b.bits), size);
+
+                Ok(Val::flag(match id {
+                    "carry" => a.bits + b.bits > mask(size),
+                    "scarry" => {
+                        (a.signed() < 0) == (b.signed() < 0)
+                            && (sum.signed() < 0) != (a.signed() < 0)
+                    }
+                    _ => {
+                        (a.signed() < 0) != (b.signed() < 0)
+                            && (diff.signed() < 0) != (a.signed() < 0)
+                    }
+                }))
+            }
+            ("lzcount" | "popcount", [param]) => {
+                let val = self.eval(machine, frame, param, None)?;
+                let count = if id == "popcount" {
+                    val.bits.count_ones()
+                } else {
+                    val.bits.leading_zeros() - (128 - 8 * val.size as u32)
+                };
+                Ok(Val::new(count as u128, hint.unwrap_or(4)))
+            }
+            _ => Err(format!("{id} has no value")),
+        }
+    }
+
+    fn flatten<'e>(exprs: &'e [Expr], stmts: &mut Vec<&'e Expr>) {
+        for expr in exprs {
+            let mut line = Some(expr);
+
+            while let Some(expr) = line {
+                match expr {
+                    Expr::Line { current, next } => {
+                        Self::flatten(std::slice::from_ref(current), stmts);
+                        line = next.as_deref();
+                    }
+                    _ => {
+                        stmts.push(expr);
+                        line = None;
+                    }
+                }
+            }
+        }
+    }
+
+    fn exec_stmt(
+        &self,
+        machine: &mut Machine,
+        frame: &mut Frame,
+        stmt: &Expr,
+    ) -> Result<Flow, String> {
+        match stmt {
+            Expr::Binary {
+                lhs,
+                op: Op::Copy,
+                rhs,
+            } => {
+                // Assigning an undeclared variable declares it
+                let lhs = match &**lhs {
+                    Expr::Var { id }
+                        if !frame.locals.contains_key(id) && Machine::reg_place(id).is_none() =>
+                    {
+                        let size = self.nat_size(frame, rhs).unwrap_or(4);
+                        frame.locals.insert(id.clone(), Val::new(0, size));
+                        lhs
+                    }
+                    _ => lhs,
+                };
+                let place = self.place(machine, frame, lhs)?;
+                let val = self.eval(machine, frame, rhs, Some(place.size()))?;
+                machine.write_place(&place, val, &mut frame.locals);
+            }
+            Expr::Local { .. } => {
+                self.place(machine, frame, stmt)?;
+            }
+            Expr::Label { .. } => {}
+            Expr::IfGoto { cond, goto } => {
+                if self.eval(machine, frame, cond, None)?.is_set() {
+                    return self.jump(machine, frame, goto);
+                }
+            }
+            Expr::Goto { dest: addr } | Expr::Call { addr } | Expr::Return { addr } => {
+                return self.jump(machine, frame, addr);
+            }
+            Expr::Macro { id, params } => match self.eval_macro(machine, frame, id, params, None) {
+                Ok(_) => {}
+                Err(_) => machine.ops.push(id.clone()),
+            },
+            Expr::Build { table } => {
+                let Expr::Field { id, .. } = &**table else {
+                    return Err(format!("can't build {table:?}"));
+                };
+                let (wi, field) = frame.field(id)?.clone();
+                let FieldType::Table(index) = field.ftype() else {
+                    return Err(format!("field {id} is not a sub-table"));
+                };
+                let (ifam, instr, words, _) = self
+                    .disasm
+                    .resolve_table_instr(
+                        frame.ifam,
+                        index,
+                        &frame.words[wi..],
+                        frame.addr,
+                        frame.next,
+                    )
+                    .ok_or(format!("no constructor of sub-table {id} matches"))?;
+                let mut sub = Frame::new(
+                    ifam,
+                    instr,
+                    &frame.words[wi..wi + words],
+                    frame.addr,
+                    frame.next,
+                );
+
+                if let Some(target) = self.run(machine, &mut sub)? {
+                    return Ok(Flow::Branch(target));
+                }
+            }
+            _ => return Err(format!("can't execute {stmt:?}")),
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn jump(&self, machine: &Machine, frame: &mut Frame, dest: &Expr) -> Result<Flow, String> {
+        Ok(match dest {
+            Expr::Label { id } => Flow::Label(id.clone()),
+            dest => Flow::Branch(self.eval(machine, frame, dest, Some(4))?.bits as u32),
+        })
+    }
+
+    /// Runs the p-code of the constructor of `frame`, returning the branch
+    /// target if it leaves the instruction.
+    fn run(&self, machine: &mut Machine, frame: &mut Frame) -> Result<Option<u32>, String> {
+        let pcodes = frame.instr.get_pcodes().inline_macros();
+        let mut stmts = Vec::new();
+        Self::flatten(pcodes.exprs(), &mut stmts);
+
+        let mut pc = 0;
+        while let Some(stmt) = stmts.get(pc) {
+            pc += 1;
+
+            match self.exec_stmt(machine, frame, stmt)? {
+                Flow::Next => {}
+                Flow::Branch(target) => return Ok(Some(target)),
+                Flow::Label(label) => {
+                    pc = stmts
+                        .iter()
+                        .position(|stmt| matches!(stmt, Expr::Label { id } if *id == label))
+                        .ok_or(format!("label {label} is not defined"))?;
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Executes the instruction at PC, then moves PC to the next instruction
+    /// or to the branch target.
+    pub fn step(&self, machine: &mut Machine) -> Result<Instruction, String> {
+        let addr = machine.reg(PROGRAM_COUNTER) as u32;
+        let bytes: Vec<u8> = (0..8)
+            .map(|i| machine.load(addr.wrapping_add(i), 1) as u8)
+            .collect();
+        let (ifam, instr, decoded) = self
+            .disasm
+            .lookup(&bytes, addr)
+            .ok_or(format!("no instruction at {addr:#010x}"))?;
+
+        let words: Vec<u16> = decoded
+            .bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        let next = addr.wrapping_add(decoded.len() as u32);
+        let mut frame = Frame::new(ifam, instr, &words, addr, next);
+
+        let target = self
+            .run(machine, &mut frame)
+            .map_err(|err| format!("{:#010x} {}: {err}", addr, decoded.text))?;
+        machine.set_reg(PROGRAM_COUNTER, target.unwrap_or(next) as u64);
+
+        Ok(decoded)
+    }
+}
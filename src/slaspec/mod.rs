@@ -1,4 +1,17 @@
 pub mod globals;
 pub mod instructions;
 
+pub mod asm;
+pub mod assets;
 pub mod builder;
+pub mod coverage;
+pub mod cspec;
+pub mod disasm;
+pub mod doc;
+pub mod error;
+pub mod export;
+pub mod interp;
+pub mod pspec;
+pub mod registers;
+pub mod sleigh;
+pub mod stats;
@@ -0,0 +1,111 @@
+use super::globals::{CONTEXT_REGISTER, DEFAULT_MEM, MEMORY_BLOCKS, PROGRAM_COUNTER};
+use super::instructions::pattern::RegisterSet;
+use super::registers::{RegGroup, register_file};
+
+pub struct PSpecBuilder {
+    context_fields: Vec<String>,
+}
+
+impl PSpecBuilder {
+    pub fn new() -> Self {
+        PSpecBuilder {
+            context_fields: register_file()
+                .context_fields()
+                .iter()
+                .map(|f| f.name())
+                .collect(),
+        }
+    }
+
+    fn register_groups() -> Vec<(&'static str, Vec<String>)> {
+        let mut dag = Vec::new();
+        for regset in [
+            RegisterSet::IReg,
+            RegisterSet::MReg,
+            RegisterSet::BReg,
+            RegisterSet::LReg,
+        ] {
+            dag.append(&mut regset.regs());
+        }
+
+        let mut seq = RegisterSet::SyRg2.regs();
+        seq.append(&mut RegisterSet::SyRg3.regs());
+        seq.append(&mut register_file().names(&["RETS"]));
+
+        vec![
+            ("DATA", RegisterSet::DReg.regs()),
+            ("POINTER", RegisterSet::PReg.regs()),
+            ("DAG", dag),
+            ("ACCUMULATOR", register_file().group(RegGroup::Accumulator)),
+            ("STATUS", register_file().group(RegGroup::Status)),
+            ("SEQUENCER", seq),
+        ]
+    }
+
+    fn build_context_data(&self) -> String {
+        let mut context_str = String::new();
+
+        context_str += "  <context_data>\n";
+        context_str += &format!("    <context_set space=\"{}\">\n", DEFAULT_MEM);
+        for field in &self.context_fields {
+            context_str += &format!("      <set name=\"{}\" val=\"0\"/>\n", field);
+        }
+        context_str += "    </context_set>\n";
+        context_str += "  </context_data>\n";
+
+        context_str
+    }
+
+    fn build_register_data() -> String {
+        let mut reg_str = String::new();
+
+        reg_str += "  <register_data>\n";
+        reg_str += &format!(
+            "    <register name=\"{}\" hidden=\"true\"/>\n",
+            CONTEXT_REGISTER
+        );
+        for (group, regs) in Self::register_groups() {
+            for reg in regs {
+                reg_str += &format!("    <register name=\"{}\" group=\"{}\"/>\n", reg, group);
+            }
+        }
+        reg_str += "  </register_data>\n";
+
+        reg_str
+    }
+
+    fn build_memory_blocks() -> String {
+        let mut mem_str = String::new();
+
+        mem_str += "  <default_memory_blocks>\n";
+        for (name, start, len, mode) in MEMORY_BLOCKS {
+            mem_str += &format!(
+                "    <memory_block name=\"{}\" start_address=\"{}:{:#010x}\" length=\"{:#x}\" mode=\"{}\" initialized=\"false\"/>\n",
+                name, DEFAULT_MEM, start, len, mode
+            );
+        }
+        mem_str += "  </default_memory_blocks>\n";
+
+        mem_str
+    }
+
+    pub fn build(&self) -> String {
+        let mut pspec = String::new();
+
+        pspec += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\n";
+        pspec += "<processor_spec>\n";
+        pspec += &format!("  <programcounter register=\"{}\"/>\n", PROGRAM_COUNTER);
+        pspec += &self.build_context_data();
+        pspec += &Self::build_register_data();
+        pspec += &Self::build_memory_blocks();
+        pspec += "</processor_spec>\n";
+
+        pspec
+    }
+}
+
+impl Default for PSpecBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
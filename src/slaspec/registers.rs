@@ -0,0 +1,533 @@
+use std::sync::OnceLock;
+
+use itertools::Itertools;
+
+use super::globals::CONTEXT_REGISTER;
+use super::sleigh::{self, BitRange, ContextDef, Document, Item, RegisterDef};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegGroup {
+    Data,
+    DataPair,
+    Pointer,
+    DagI,
+    DagL,
+    DagB,
+    DagM,
+    Accumulator,
+    Status,
+    System,
+    Context,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alias {
+    Low,
+    High,
+    Byte,
+    Word,
+    Ext,
+}
+
+impl Alias {
+    fn suffix(&self) -> &'static str {
+        match self {
+            Self::Low => "L",
+            Self::High => "H",
+            Self::Byte => "B",
+            Self::Word => "W",
+            Self::Ext => "X",
+        }
+    }
+
+    // Offset and size of the alias inside a register of the given size
+    fn slice(&self, size: usize) -> (usize, usize) {
+        match self {
+            Self::Low => (0, 2),
+            Self::High => (2, 2),
+            Self::Byte => (0, 1),
+            Self::Word => (0, 4),
+            Self::Ext => (size - 1, 1),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Register {
+    name: String,
+    offset: usize,
+    size: usize,
+    group: RegGroup,
+    parent: Option<(String, Alias)>,
+}
+
+impl Register {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Section {
+    comment: &'static str,
+    regs: Vec<Register>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BitRangeDef {
+    name: String,
+    reg: String,
+    start: usize,
+    len: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContextField {
+    name: String,
+    start: usize,
+    end: usize,
+    noflow: bool,
+}
+
+impl ContextField {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RegisterFile {
+    sections: Vec<Section>,
+    bitranges: Vec<BitRangeDef>,
+    context: Vec<ContextField>,
+}
+
+impl RegisterFile {
+    fn root(name: &str, offset: usize, size: usize, group: RegGroup) -> Register {
+        Register {
+            name: name.to_string(),
+            offset,
+            size,
+            group,
+            parent: None,
+        }
+    }
+
+    fn with_aliases(regs: Vec<Register>, aliases: &[Alias]) -> Vec<Register> {
+        let mut all = regs.clone();
+
+        for alias in aliases {
+            for reg in &regs {
+                let (offset, size) = alias.slice(reg.size);
+                all.push(Register {
+                    name: format!("{}.{}", reg.name, alias.suffix()),
+                    offset: reg.offset + offset,
+                    size,
+                    group: reg.group,
+                    parent: Some((reg.name.clone(), *alias)),
+                });
+            }
+        }
+
+        all
+    }
+
+    fn array(
+        names: &[String],
+        offset: usize,
+        stride: usize,
+        size: usize,
+        group: RegGroup,
+    ) -> Vec<Register> {
+        names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| Self::root(name, offset + i * stride, size, group))
+            .collect()
+    }
+
+    fn indexed(id: &str, count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("{id}{i}")).collect()
+    }
+
+    fn named(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    fn data_section() -> Section {
+        let pairs: Vec<String> = (0..4).map(|i| format!("R{}{}", 2 * i + 1, 2 * i)).collect();
+        let mut regs = Self::array(&pairs, 0x0000, 8, 8, RegGroup::DataPair);
+
+        regs.append(&mut Self::with_aliases(
+            Self::array(&Self::indexed("R", 8), 0x0000, 4, 4, RegGroup::Data),
+            &[Alias::Low, Alias::High, Alias::Byte],
+        ));
+
+        Section {
+            comment: "Data registers",
+            regs,
+        }
+    }
+
+    fn pointer_section() -> Section {
+        let mut names = Self::indexed("P", 6);
+        names.append(&mut Self::named(&["SP", "FP"]));
+
+        Section {
+            comment: "Pointer registers",
+            regs: Self::with_aliases(
+                Self::array(&names, 0x0020, 4, 4, RegGroup::Pointer),
+                &[Alias::Low, Alias::High],
+            ),
+        }
+    }
+
+    fn dag_section() -> Section {
+        let mut regs = Vec::new();
+
+        // The I, L, B and M registers of one index are interleaved
+        for (i, (id, group)) in [
+            ("I", RegGroup::DagI),
+            ("L", RegGroup::DagL),
+            ("B", RegGroup::DagB),
+            ("M", RegGroup::DagM),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            regs.append(&mut Self::array(
+                &Self::indexed(id, 4),
+                0x0040 + 4 * i,
+                16,
+                4,
+                group,
+            ));
+        }
+
+        Section {
+            comment: "Data address registers",
+            regs: Self::with_aliases(regs, &[Alias::Low, Alias::High]),
+        }
+    }
+
+    fn accumulator_section() -> Section {
+        Section {
+            comment: "Accumulators (40 bit)\n# A0.X and A1.X only contain 8 bit",
+            regs: Self::with_aliases(
+                Self::array(&Self::indexed("A", 2), 0x0080, 5, 5, RegGroup::Accumulator),
+                &[Alias::Word, Alias::Ext, Alias::Low, Alias::High],
+            ),
+        }
+    }
+
+    fn status_section() -> Section {
+        Section {
+            comment: "Status register and flags",
+            regs: vec![Self::root("ASTAT", 0x008a, 4, RegGroup::Status)],
+        }
+    }
+
+    fn system_sections() -> Vec<Section> {
+        vec![
+            Section {
+                comment: "User Stack Pointer and EMUDAT",
+                regs: Self::array(
+                    &Self::named(&["USP", "EMUDAT"]),
+                    0x0090,
+                    4,
+                    4,
+                    RegGroup::System,
+                ),
+            },
+            Section {
+                comment: "Program counter",
+                regs: vec![Self::root("PC", 0x0098, 4, RegGroup::System)],
+            },
+            Section {
+                comment: "Sequencer registers",
+                regs: Self::array(
+                    &Self::named(&[
+                        "SEQSTAT", "RETX", "RETN", "RETI", "RETE", "RETS", "LC0", "LC1", "LT0",
+                        "LT1", "LB0", "LB1", "SYSCFG", "CYCLES", "CYCLES2",
+                    ]),
+                    0x00a0,
+                    4,
+                    4,
+                    RegGroup::System,
+                ),
+            },
+            Section {
+                comment: "Context registers",
+                regs: vec![Self::root(CONTEXT_REGISTER, 0x0100, 4, RegGroup::Context)],
+            },
+        ]
+    }
+
+    fn astat_bits() -> Vec<BitRangeDef> {
+        [
+            ("AZ", 0),
+            ("AN", 1),
+            ("AC0COPY", 2),
+            ("VCOPY", 3),
+            ("CC", 5),
+            ("AQ", 6),
+            ("RND_MOD", 8),
+            ("AC0", 12),
+            ("AC1", 13),
+            ("AV0", 16),
+            ("AV0S", 17),
+            ("AV1", 18),
+            ("AV1S", 19),
+            ("V", 24),
+            ("VS", 25),
+        ]
+        .into_iter()
+        .map(|(name, start)| BitRangeDef {
+            name: name.to_string(),
+            reg: "ASTAT".to_string(),
+            start,
+            len: 1,
+        })
+        .collect()
+    }
+
+    fn context_defs() -> Vec<ContextField> {
+        [
+            ("phase", 0, 0, false),
+            ("loop0active", 1, 1, true),
+            ("loop1active", 2, 2, true),
+            ("zloop", 3, 4, true),
+        ]
+        .into_iter()
+        .map(|(name, start, end, noflow)| ContextField {
+            name: name.to_string(),
+            start,
+            end,
+            noflow,
+        })
+        .collect()
+    }
+
+    pub fn new() -> Self {
+        let mut sections = vec![
+            Self::data_section(),
+            Self::pointer_section(),
+            Self::dag_section(),
+            Self::accumulator_section(),
+            Self::status_section(),
+        ];
+        sections.append(&mut Self::system_sections());
+
+        RegisterFile {
+            sections,
+            bitranges: Self::astat_bits(),
+            context: Self::context_defs(),
+        }
+    }
+
+    pub fn regs(&self) -> impl Iterator<Item = &Register> {
+        self.sections.iter().flat_map(|s| s.regs.iter())
+    }
+
+    pub fn get(&self, name: &str) -> &Register {
+        match self.regs().find(|r| r.name == name) {
+            Some(reg) => reg,
+            None => panic!("Register {name} is not defined in the register file"),
+        }
+    }
+
+    pub fn size_of(&self, name: &str) -> Option<usize> {
+        if let Some(reg) = self.regs().find(|r| r.name == name) {
+            return Some(reg.size);
+        }
+
+        self.bitranges
+            .iter()
+            .find(|b| b.name == name)
+            .map(|b| b.len.div_ceil(8))
+    }
+
+    /// Register, first bit and length of the bitrange `name`.
+    pub fn bit_range(&self, name: &str) -> Option<(&Register, usize, usize)> {
+        self.bitranges
+            .iter()
+            .find(|b| b.name == name)
+            .map(|b| (self.get(&b.reg), b.start, b.len))
+    }
+
+    pub fn names(&self, names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| self.get(name).name()).collect()
+    }
+
+    pub fn group(&self, group: RegGroup) -> Vec<String> {
+        self.regs()
+            .filter(|r| r.group == group && r.parent.is_none())
+            .map(|r| r.name())
+            .collect()
+    }
+
+    pub fn aliases(&self, group: RegGroup, alias: Alias) -> Vec<String> {
+        self.group(group)
+            .iter()
+            .map(|name| {
+                match self
+                    .regs()
+                    .find(|r| r.parent == Some((name.clone(), alias)))
+                {
+                    Some(reg) => reg.name(),
+                    None => panic!("Register {name} has no {alias:?} alias"),
+                }
+            })
+            .collect()
+    }
+
+    pub fn bit_names(&self, reg: &str) -> Vec<String> {
+        let size = self.get(reg).size;
+
+        (0..size * 8)
+            .map(|bit| {
+                match self
+                    .bitranges
+                    .iter()
+                    .find(|b| b.reg == reg && b.start == bit && b.len == 1)
+                {
+                    Some(b) => b.name.clone(),
+                    None => format!("_{bit:#04x}"),
+                }
+            })
+            .collect()
+    }
+
+    pub fn context_fields(&self) -> &[ContextField] {
+        &self.context
+    }
+
+    fn build_section(section: &Section) -> Vec<Item> {
+        let mut items = vec![Item::Comment(format!(" {}", section.comment))];
+        let by_size = section
+            .regs
+            .iter()
+            .sorted_by_key(|r| (std::cmp::Reverse(r.size), r.offset))
+            .chunk_by(|r| r.size);
+
+        for (size, regs) in &by_size {
+            let mut start = None;
+            let mut next = 0;
+            let mut names: Vec<String> = Vec::new();
+
+            for reg in regs {
+                // Registers that can't be reached by padding start a new definition
+                if let Some(offset) = start
+                    && (reg.offset < next || (reg.offset - next) % size != 0)
+                {
+                    items.push(Item::Register(RegisterDef {
+                        offset,
+                        size,
+                        names: std::mem::take(&mut names),
+                    }));
+                    start = None;
+                }
+                if start.is_none() {
+                    start = Some(reg.offset);
+                    next = reg.offset;
+                }
+                while next < reg.offset {
+                    names.push("_".to_string());
+                    next += size;
+                }
+                names.push(reg.name());
+                next += size;
+            }
+
+            if let Some(offset) = start {
+                items.push(Item::Register(RegisterDef {
+                    offset,
+                    size,
+                    names,
+                }));
+            }
+        }
+
+        items
+    }
+
+    fn build_bitranges(&self) -> Item {
+        Item::BitRanges(
+            self.bitranges
+                .iter()
+                .map(|bits| BitRange {
+                    name: bits.name.clone(),
+                    reg: bits.reg.clone(),
+                    start: bits.start,
+                    len: bits.len,
+                })
+                .collect(),
+        )
+    }
+
+    fn build_context(&self) -> Item {
+        Item::Context(ContextDef {
+            register: CONTEXT_REGISTER.to_string(),
+            fields: self
+                .context
+                .iter()
+                .map(|field| sleigh::ContextField {
+                    name: field.name.clone(),
+                    start: field.start,
+                    end: field.end,
+                    noflow: field.noflow,
+                })
+                .collect(),
+        })
+    }
+
+    pub fn document(&self) -> Document {
+        let banner = "#".repeat(69);
+        let mut doc = Document::new();
+
+        doc.items.extend([
+            Item::Comment(banner.clone()),
+            Item::Comment(format!(
+                "#######>--------{:^36}--------<########",
+                "Registers"
+            )),
+            Item::Comment(banner),
+            Item::Blank,
+        ]);
+
+        for section in &self.sections {
+            doc.items.extend(Self::build_section(section));
+            if section.regs.iter().any(|r| r.name == "ASTAT") {
+                doc.push(self.build_bitranges());
+            }
+            doc.push(Item::Blank);
+        }
+
+        doc.push(self.build_context());
+
+        doc
+    }
+
+    pub fn build(&self) -> String {
+        self.document().build()
+    }
+}
+
+impl Default for RegisterFile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn register_file() -> &'static RegisterFile {
+    static REGISTER_FILE: OnceLock<RegisterFile> = OnceLock::new();
+    REGISTER_FILE.get_or_init(RegisterFile::new)
+}
@@ -0,0 +1,431 @@
+//! In-memory SLEIGH document. The emitters describe what they generate with
+//! these items, [`Document::build`] is the only place deciding how it's written.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenField {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    pub signed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenDef {
+    pub name: String,
+    pub size: usize,
+    pub fields: Vec<TokenField>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachKind {
+    Variables,
+    Names,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attach {
+    pub kind: AttachKind,
+    pub fields: Vec<String>,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    /// `field=value`, the value already formatted
+    Eq { field: String, value: String },
+    /// A field or sub-table used as an operand
+    Operand(String),
+    /// A constraint on the start of a longer instruction (`... `)
+    Prefix(Box<Constraint>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+    Op(String),
+    Label(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Pattern and semantics on their own lines
+    Block,
+    /// Everything on one line, for the glue constructors
+    Inline,
+}
+
+/// Everything between the `:` and `is` of a constructor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplaySection {
+    /// The `^"mnemonic"` and operands, already formatted
+    Operands(String),
+    /// A text displayed as-is, quoted when written
+    Literal(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constructor {
+    pub table: String,
+    pub display: DisplaySection,
+    /// One list of constraints per instruction word
+    pub pattern: Vec<Vec<Constraint>>,
+    pub actions: Vec<Stmt>,
+    pub semantics: Vec<Stmt>,
+    pub layout: Layout,
+}
+
+/// A p-code snippet shared between constructors
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Macro {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpaceKind {
+    Ram,
+    Register,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpaceDef {
+    pub name: String,
+    pub kind: SpaceKind,
+    pub size: usize,
+    pub default: bool,
+}
+
+/// Registers of the same size laid out from `offset`, `_` skips one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisterDef {
+    pub offset: usize,
+    pub size: usize,
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BitRange {
+    pub name: String,
+    pub reg: String,
+    pub start: usize,
+    pub len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextField {
+    pub name: String,
+    pub start: usize,
+    pub end: usize,
+    pub noflow: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextDef {
+    pub register: String,
+    pub fields: Vec<ContextField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Item {
+    /// A comment line, without its leading `#`
+    Comment(String),
+    Blank,
+    /// `define key=value;`, for the endianness and alignment
+    Define {
+        key: String,
+        value: String,
+    },
+    Space(SpaceDef),
+    Register(RegisterDef),
+    BitRanges(Vec<BitRange>),
+    Context(ContextDef),
+    Token(TokenDef),
+    Attach(Attach),
+    PcodeOp(String),
+    Macro(Macro),
+    Constructor(Constructor),
+    Include(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub items: Vec<Item>,
+}
+
+impl Stmt {
+    pub fn build(&self) -> String {
+        match self {
+            Stmt::Op(op) => format!("\n\t{op};"),
+            Stmt::Label(label) => format!("\n{label}"),
+        }
+    }
+}
+
+fn build_stmts(stmts: &[Stmt]) -> String {
+    stmts.iter().map(|stmt| stmt.build()).collect()
+}
+
+impl Constraint {
+    fn build(&self) -> String {
+        match self {
+            Constraint::Eq { field, value } => format!("{field}={value}"),
+            Constraint::Operand(name) => name.clone(),
+            Constraint::Prefix(constraint) => format!("{} ...", constraint.build()),
+        }
+    }
+}
+
+impl DisplaySection {
+    fn build(&self) -> String {
+        match self {
+            DisplaySection::Operands(text) => text.clone(),
+            DisplaySection::Literal(text) => {
+                format!(" \"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+        }
+    }
+}
+
+impl SpaceDef {
+    fn build(&self) -> String {
+        let kind = match self.kind {
+            SpaceKind::Ram => "ram_space",
+            SpaceKind::Register => "register_space",
+        };
+
+        format!(
+            "define space {} type={kind} size={}{};",
+            self.name,
+            self.size,
+            if self.default { " default" } else { "" }
+        )
+    }
+}
+
+impl RegisterDef {
+    fn build(&self) -> String {
+        let names = if self.names.len() <= 8 {
+            format!("[{}]", self.names.join(" "))
+        } else {
+            format!(
+                "[\n{}\n]",
+                self.names
+                    .chunks(8)
+                    .map(|chunk| format!("    {}", chunk.join(" ")))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            )
+        };
+
+        format!(
+            "define register offset={:#06x} size={} {names};",
+            self.offset, self.size
+        )
+    }
+}
+
+fn build_bitranges(bitranges: &[BitRange]) -> String {
+    let mut bits_str = String::from("define bitrange\n");
+
+    for bits in bitranges {
+        bits_str += &format!(
+            "    {}={}[{},{}]\n",
+            bits.name, bits.reg, bits.start, bits.len
+        );
+    }
+
+    bits_str + ";"
+}
+
+impl ContextDef {
+    fn build(&self) -> String {
+        let mut ctx_str = format!("define context {}\n", self.register);
+
+        for field in &self.fields {
+            ctx_str += &format!(
+                "    {:11} = ({},{}){}\n",
+                field.name,
+                field.start,
+                field.end,
+                if field.noflow { " noflow" } else { "" }
+            );
+        }
+
+        ctx_str + ";"
+    }
+}
+
+impl TokenDef {
+    fn build(&self) -> String {
+        let mut token_str = format!("define token {} ({})\n", self.name, self.size);
+
+        for field in &self.fields {
+            token_str += &format!(
+                "\t{:16} = ({:2},{:2}) {}\n",
+                field.name,
+                field.start,
+                field.end,
+                if field.signed { "signed" } else { "" }
+            );
+        }
+
+        token_str + ";"
+    }
+}
+
+impl Attach {
+    fn build(&self) -> String {
+        let kind = match self.kind {
+            AttachKind::Variables => "variables",
+            AttachKind::Names => "names",
+        };
+        let fields = match self.fields.as_slice() {
+            [field] => field.clone(),
+            fields => format!("[ {} ]", fields.join(" ")),
+        };
+        let values = if self.values.len() <= 8 {
+            self.values.join(" ")
+        } else {
+            format!(
+                "\n{}\n",
+                self.values
+                    .chunks(8)
+                    .map(|chunk| format!("\t{}", chunk.join(" ")))
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            )
+        };
+
+        format!("attach {kind} {fields} [{values}];")
+    }
+}
+
+impl Macro {
+    fn build(&self) -> String {
+        format!(
+            "macro {}({}) {{{}\n}}",
+            self.name,
+            self.params.join(", "),
+            build_stmts(&self.body)
+        )
+    }
+}
+
+impl Constructor {
+    fn build_block(&self) -> String {
+        let mut cons_str = format!("{}:{}\n\tis ", self.table, self.display.build());
+
+        cons_str += &self
+            .pattern
+            .iter()
+            .map(|word| {
+                word.iter()
+                    .map(|constraint| format!("{} ", constraint.build()))
+                    .collect::<Vec<String>>()
+                    .join("& ")
+            })
+            .collect::<Vec<String>>()
+            .join("\n\t ; ");
+
+        if !self.actions.is_empty() {
+            cons_str += &format!("\n[{}\n]", build_stmts(&self.actions));
+        }
+
+        if self.semantics.is_empty() {
+            cons_str += "{}";
+        } else {
+            cons_str += if self.actions.is_empty() { "\n" } else { " " };
+            cons_str += &format!("{{{}\n}}", build_stmts(&self.semantics));
+        }
+
+        cons_str
+    }
+
+    fn build_inline(&self) -> String {
+        let pattern = self
+            .pattern
+            .iter()
+            .map(|word| {
+                word.iter()
+                    .map(|constraint| constraint.build())
+                    .collect::<Vec<String>>()
+                    .join(" & ")
+            })
+            .collect::<Vec<String>>()
+            .join(" ; ");
+        let semantics = match self.semantics.as_slice() {
+            [] => String::from("{}"),
+            stmts => format!(
+                "{{{} }}",
+                stmts
+                    .iter()
+                    .map(|stmt| match stmt {
+                        Stmt::Op(op) => format!(" {op};"),
+                        Stmt::Label(label) => format!(" {label}"),
+                    })
+                    .collect::<String>()
+            ),
+        };
+
+        format!(
+            "{}:{} is {pattern} {semantics}",
+            self.table,
+            self.display.build()
+        )
+    }
+
+    fn build(&self) -> String {
+        match self.layout {
+            Layout::Block => self.build_block(),
+            Layout::Inline => self.build_inline(),
+        }
+    }
+}
+
+impl Item {
+    pub fn build(&self) -> String {
+        match self {
+            Item::Comment(text) => format!("#{text}"),
+            Item::Blank => String::new(),
+            Item::Define { key, value } => format!("define {key}={value};"),
+            Item::Space(space) => space.build(),
+            Item::Register(reg) => reg.build(),
+            Item::BitRanges(bitranges) => build_bitranges(bitranges),
+            Item::Context(ctx) => ctx.build(),
+            Item::Token(token) => token.build(),
+            Item::Attach(attach) => attach.build(),
+            Item::PcodeOp(op) => format!("define pcodeop {op};"),
+            Item::Macro(mac) => mac.build(),
+            Item::Constructor(cons) => cons.build(),
+            Item::Include(path) => format!("@include \"{path}\""),
+        }
+    }
+}
+
+impl Document {
+    pub fn new() -> Self {
+        Document { items: Vec::new() }
+    }
+
+    pub fn push(&mut self, item: Item) {
+        self.items.push(item);
+    }
+
+    pub fn append(&mut self, mut doc: Document) {
+        self.items.append(&mut doc.items);
+    }
+
+    pub fn constructors(&self) -> impl Iterator<Item = &Constructor> {
+        self.items.iter().filter_map(|item| match item {
+            Item::Constructor(cons) => Some(cons),
+            _ => None,
+        })
+    }
+
+    pub fn build(&self) -> String {
+        self.items
+            .iter()
+            .map(|item| format!("{}\n", item.build()))
+            .collect()
+    }
+}
@@ -0,0 +1,284 @@
+use super::globals::SLA_MAX_FILE_SIZE;
+use super::instructions::core::{InstrBuilder, InstrFamilyBuilder};
+use super::instructions::pattern::FieldType;
+
+// Uncompressed .sla bytes per element. The constructor overhead was fitted so
+// the spec without sub-tables landed on the 24.8 MB the README measured in
+// Ghidra. The p-code added to the families since then isn't in that
+// measurement, which is why the estimate of the current spec is higher.
+const CONSTRUCTOR_BYTES: usize = 250;
+const WORD_BYTES: usize = 60;
+const OPERAND_BYTES: usize = 80;
+const DISPLAY_BYTES: usize = 4;
+const OP_BYTES: usize = 40;
+const TOKEN_BYTES: usize = 100;
+const ATTACH_BYTES: usize = 20;
+
+fn instr_bytes(instr: &InstrBuilder) -> usize {
+    let fields = instr.pattern().fields();
+    let words = fields.iter().filter(|word| !word.is_empty()).count();
+    let operands = fields
+        .iter()
+        .flatten()
+        .filter(|field| !matches!(field.ftype(), FieldType::Mask(_) | FieldType::Blank))
+        .count();
+    let ops = instr.get_actions().op_count() + instr.get_pcodes().op_count();
+
+    CONSTRUCTOR_BYTES
+        + words * WORD_BYTES
+        + operands * OPERAND_BYTES
+        + instr.get_display().len() * DISPLAY_BYTES
+        + ops * OP_BYTES
+}
+
+#[derive(Debug, Clone)]
+pub struct TableStats {
+    pub name: String,
+    pub constructors: usize,
+    pub pcode_ops: usize,
+    pub sla_bytes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct FamilyStats {
+    pub name: String,
+    pub tokens: usize,
+    pub attaches: usize,
+    pub attached_names: usize,
+    pub tables: Vec<TableStats>,
+}
+
+impl FamilyStats {
+    pub fn new(ifam: &InstrFamilyBuilder) -> Self {
+        let mut tables: Vec<TableStats> = ifam
+            .tables()
+            .into_iter()
+            .map(|table| {
+                let instrs = ifam.table_instrs(&table).map_or(&[][..], |v| v.as_slice());
+
+                TableStats {
+                    constructors: instrs.len(),
+                    pcode_ops: instrs
+                        .iter()
+                        .map(|instr| instr.get_actions().op_count() + instr.get_pcodes().op_count())
+                        .sum(),
+                    sla_bytes: instrs.iter().map(instr_bytes).sum(),
+                    name: table,
+                }
+            })
+            .collect();
+
+        tables.extend(
+            (0..ifam.subtables_len())
+                .filter_map(|index| ifam.subtable(index))
+                .map(|table| TableStats {
+                    name: table.name(),
+                    constructors: table.rows_len(),
+                    pcode_ops: 0,
+                    sla_bytes: table.rows_len() * (CONSTRUCTOR_BYTES + OPERAND_BYTES),
+                }),
+        );
+
+        let attached_names = ifam
+            .variables()
+            .filter_map(|var| match var.ftype() {
+                FieldType::Variable(regset) => Some(regset.regs().len()),
+                _ => None,
+            })
+            .sum();
+
+        FamilyStats {
+            name: ifam.name(),
+            tokens: ifam.tokens_len(),
+            attaches: ifam.variables().count(),
+            attached_names,
+            tables,
+        }
+    }
+
+    pub fn constructors(&self) -> usize {
+        self.tables.iter().map(|table| table.constructors).sum()
+    }
+
+    pub fn pcode_ops(&self) -> usize {
+        self.tables.iter().map(|table| table.pcode_ops).sum()
+    }
+
+    /// Heuristic size of the family in the uncompressed .sla file
+    pub fn sla_bytes(&self) -> usize {
+        self.tokens * TOKEN_BYTES
+            + self.attached_names * ATTACH_BYTES
+            + self
+                .tables
+                .iter()
+                .map(|table| table.sla_bytes)
+                .sum::<usize>()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SpecStats {
+    pub families: Vec<FamilyStats>,
+}
+
+impl SpecStats {
+    pub fn new(ifams: &[&InstrFamilyBuilder]) -> Self {
+        SpecStats {
+            families: ifams.iter().map(|ifam| FamilyStats::new(ifam)).collect(),
+        }
+    }
+
+    pub fn sla_bytes(&self) -> usize {
+        self.families.iter().map(|ifam| ifam.sla_bytes()).sum()
+    }
+
+    pub fn fits(&self) -> bool {
+        self.sla_bytes() <= SLA_MAX_FILE_SIZE
+    }
+
+    fn size(bytes: usize) -> String {
+        format!(
+            "{:8.2} MiB {:5.1}%",
+            bytes as f64 / (1024.0 * 1024.0),
+            100.0 * bytes as f64 / SLA_MAX_FILE_SIZE as f64
+        )
+    }
+
+    pub fn build(&self) -> String {
+        let mut report = String::new();
+
+        report += &format!(
+            "{:26} {:>8} {:>7} {:>7} {:>9} {:>19}\n",
+            "Family/Table", "Constr.", "Tokens", "Attach", "P-code", ".sla estimate"
+        );
+
+        for ifam in &self.families {
+            // A family over a sixteenth of the limit is worth a look
+            let mark = if ifam.sla_bytes() > SLA_MAX_FILE_SIZE / 16 {
+                " !"
+            } else {
+                ""
+            };
+
+            report += &format!(
+                "{:26} {:8} {:7} {:7} {:9} {}{mark}\n",
+                ifam.name,
+                ifam.constructors(),
+                ifam.tokens,
+                ifam.attaches,
+                ifam.pcode_ops(),
+                Self::size(ifam.sla_bytes())
+            );
+
+            if ifam.tables.len() > 1 {
+                for table in &ifam.tables {
+                    report += &format!(
+                        "  {:24} {:8} {:7} {:7} {:9} {}\n",
+                        table.name,
+                        table.constructors,
+                        "",
+                        "",
+                        table.pcode_ops,
+                        Self::size(table.sla_bytes)
+                    );
+                }
+            }
+        }
+
+        report += &format!(
+            "{:26} {:8} {:7} {:7} {:9} {}\n",
+            "Total",
+            self.families
+                .iter()
+                .map(|ifam| ifam.constructors())
+                .sum::<usize>(),
+            self.families.iter().map(|ifam| ifam.tokens).sum::<usize>(),
+            self.families
+                .iter()
+                .map(|ifam| ifam.attaches)
+                .sum::<usize>(),
+            self.families
+                .iter()
+                .map(|ifam| ifam.pcode_ops())
+                .sum::<usize>(),
+            Self::size(self.sla_bytes())
+        );
+
+        if !self.fits() {
+            report += &format!(
+                "\nThe .sla estimate is over the {} MiB Ghidra can load\n",
+                SLA_MAX_FILE_SIZE / (1024 * 1024)
+            );
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::slaspec::instructions::core::InstrFactory;
+    use crate::slaspec::instructions::expr::{Expr, Op};
+    use crate::slaspec::instructions::pattern::{ProtoField, ProtoPattern, RegisterSet};
+
+    struct MoveFactory();
+
+    fn reg(id: &str) -> Box<Expr> {
+        Box::new(Expr::Field {
+            id: id.to_string(),
+            is_reg: true,
+        })
+    }
+
+    impl InstrFactory for MoveFactory {
+        fn build_instrs(&self, ifam: &InstrFamilyBuilder) -> Vec<InstrBuilder> {
+            vec![
+                InstrBuilder::new(ifam)
+                    .name("Move")
+                    .display("{dst} = {src}".to_string())
+                    .add_pcode(Expr::Binary {
+                        lhs: reg("dst"),
+                        op: Op::Copy,
+                        rhs: reg("src"),
+                    }),
+            ]
+        }
+    }
+
+    #[test]
+    fn estimates_a_known_family() {
+        let mut ifam = InstrFamilyBuilder::new_16(
+            "Test",
+            "Test family",
+            "tst",
+            ProtoPattern::new(vec![
+                ProtoField::new("sig", FieldType::Mask(0x3ff), 10),
+                ProtoField::new("src", FieldType::Variable(RegisterSet::DReg), 3),
+                ProtoField::new("dst", FieldType::Variable(RegisterSet::DReg), 3),
+            ]),
+        );
+        ifam.add_instrs(&MoveFactory());
+        ifam.init_tokens_and_vars();
+
+        let stats = FamilyStats::new(&ifam);
+
+        assert_eq!(stats.constructors(), 1);
+        assert_eq!(stats.pcode_ops(), 1);
+        assert_eq!(
+            (stats.tokens, stats.attaches, stats.attached_names),
+            (3, 2, 16)
+        );
+        // 3 tokens, 16 attached names and one constructor of one word, two
+        // operands, 13 characters of display and one op
+        assert_eq!(
+            stats.sla_bytes(),
+            3 * 100 + 16 * 20 + 250 + 60 + 2 * 80 + 13 * 4 + 40
+        );
+
+        let spec = SpecStats::new(&[&ifam]);
+        assert_eq!(spec.sla_bytes(), stats.sla_bytes());
+        assert!(spec.fits());
+        assert!(!spec.build().contains("Ghidra can load"));
+    }
+}
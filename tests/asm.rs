@@ -0,0 +1,55 @@
+use sawfish::slaspec::builder::SLASpecBuilder;
+
+#[test]
+fn assembles_known_instructions() {
+    let slab = SLASpecBuilder::new();
+    let asm = slab.assembler();
+
+    for (text, addr, bytes) in [
+        ("nop", 0, vec![0x00, 0x00]),
+        ("R1 = [I1++]", 0, vec![0x09, 0x9c]),
+        ("P0.L = 0x1234", 0, vec![0x08, 0xe1, 0x34, 0x12]),
+        ("JUMP.S 0x100c", 0x1010, vec![0xfe, 0x2f]),
+        ("if CC JUMP 0x101a", 0x1014, vec![0x03, 0x18]),
+    ] {
+        let instr = asm.assemble(text, addr).expect(text);
+        assert_eq!(instr.bytes, bytes, "{text}");
+    }
+
+    assert!(asm.assemble("R0 = [I4++]", 0).is_none());
+    assert!(asm.assemble("JUMP.S 0x100d", 0x1010).is_none());
+}
+
+#[test]
+fn round_trips_every_constructor() {
+    let slab = SLASpecBuilder::new();
+    let asm = slab.assembler();
+    let disasm = slab.disassembler();
+    let addr = 0xffa0_1000;
+    let mut failures = Vec::new();
+    let samples = disasm.samples();
+
+    let constructors: usize = slab.families().iter().map(|ifam| ifam.len()).sum();
+    assert_eq!(samples.len(), constructors);
+
+    for sample in samples {
+        let bytes = match sample {
+            Ok(bytes) => bytes,
+            Err(instr) => {
+                failures.push(format!("{instr} => no encoding"));
+                continue;
+            }
+        };
+        let Some(instr) = disasm.decode(&bytes, addr) else {
+            failures.push(format!("{bytes:02x?} => not decoded"));
+            continue;
+        };
+
+        match asm.assemble(&instr.text, addr) {
+            Some(asm_instr) if asm_instr.text == instr.text => {}
+            res => failures.push(format!("{} => {res:?}", instr.build())),
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
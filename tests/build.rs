@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use sawfish::slaspec::assets::Assets;
+use sawfish::slaspec::builder::{FamilyFilter, SLASpecBuilder};
+use sawfish::slaspec::error::Error;
+
+#[test]
+fn builds_every_family() {
+    let outdir = env::temp_dir().join(format!("sawfish-build-{}", std::process::id()));
+    let result = SLASpecBuilder::new().build(&outdir);
+    let built = outdir.join("includes/instructions.sinc").is_file();
+    fs::remove_dir_all(&outdir).ok();
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    assert!(built);
+}
+
+fn included_families(filter: FamilyFilter, name: &str) -> Vec<String> {
+    let outdir = env::temp_dir().join(format!("sawfish-{name}-{}", std::process::id()));
+    let result = SLASpecBuilder::with_filter(&filter).build(&outdir);
+    let includes = fs::read_to_string(outdir.join("includes/instructions.sinc"));
+    fs::remove_dir_all(&outdir).ok();
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    includes
+        .unwrap()
+        .lines()
+        .filter_map(|line| line.strip_prefix("@include \""))
+        .map(|path| path.trim_end_matches(".sinc\"").to_string())
+        .collect()
+}
+
+#[test]
+fn builds_the_selected_families() {
+    let only = vec![String::from("dsp32a*"), String::from("LoopSetup*")];
+    let exclude = vec![String::from("LoopSetupImm")];
+
+    assert_eq!(
+        included_families(FamilyFilter::new(only, exclude), "only"),
+        ["instr32/Dsp32Alu", "instr32/LoopSetup"]
+    );
+}
+
+#[test]
+fn matches_whole_family_names() {
+    let filter = FamilyFilter::new(
+        vec![String::from("loopsetup"), String::from("Dsp32")],
+        vec![],
+    );
+
+    assert!(filter.keeps("LoopSetup"));
+    assert!(!filter.keeps("LoopSetupImm"));
+    assert!(!filter.keeps("Dsp32Alu"));
+    assert_eq!(
+        filter.unmatched(&[String::from("LoopSetup"), String::from("Dsp32Alu")]),
+        ["Dsp32"]
+    );
+}
+
+#[test]
+fn multi_keeps_its_parallel_families() {
+    let filter = FamilyFilter::new(vec![String::from("Multi")], vec![]);
+    let families = included_families(filter, "multi");
+
+    assert!(families.contains(&String::from("instr64/Multi")));
+    assert!(families.contains(&String::from("instr32/Dsp32Mac")));
+    assert!(!families.contains(&String::from("instr32/LoopSetup")));
+}
+
+#[test]
+fn notes_the_excluded_families_multi_keeps() {
+    let messages = Rc::new(RefCell::new(Vec::new()));
+    let sink = Rc::clone(&messages);
+    let filter = FamilyFilter::new(
+        vec![String::from("Multi")],
+        vec![String::from("Dsp32Mac"), String::from("LoopSetup")],
+    );
+    let slab = SLASpecBuilder::with_progress(
+        &filter,
+        Assets::embedded(),
+        Box::new(move |msg| sink.borrow_mut().push(msg.to_string())),
+    )
+    .unwrap();
+    let names: Vec<String> = slab.families().iter().map(|ifam| ifam.name()).collect();
+
+    assert!(names.contains(&String::from("Dsp32Mac")));
+    assert_eq!(
+        messages
+            .borrow()
+            .iter()
+            .filter(|msg| msg.starts_with("NOTE"))
+            .collect::<Vec<_>>(),
+        ["NOTE: Dsp32Mac is kept for the parallel slot of Multi"]
+    );
+}
+
+#[test]
+fn reports_the_failing_path() {
+    let outdir = Path::new("/dev/null/sawfish");
+
+    match SLASpecBuilder::new().build(outdir) {
+        Err(Error::Io { path, .. }) => assert_eq!(path, outdir),
+        other => panic!("expected an I/O error, got {other:?}"),
+    }
+}
+
+#[test]
+fn reports_size_mismatches_per_instruction() {
+    let err = Error::Size {
+        mismatches: vec![
+            String::from("`R0 = R0.L`: 2 byte(s) where 4 are expected"),
+            String::from("`A0.X = 0x100`: 0x100 doesn't fit in 1 byte(s)"),
+        ],
+    }
+    .in_instr("Mv", "{dst} = {src}")
+    .in_family("RegMv");
+
+    assert_eq!(
+        err.to_string(),
+        "family RegMv: instruction Mv \"{dst} = {src}\": size mismatch in \
+         `R0 = R0.L`: 2 byte(s) where 4 are expected, \
+         `A0.X = 0x100`: 0x100 doesn't fit in 1 byte(s)"
+    );
+}
+
+#[test]
+fn shares_the_macros_of_the_selected_families() {
+    let outdir = env::temp_dir().join(format!("sawfish-macros-{}", std::process::id()));
+    let filter = FamilyFilter::new(vec![String::from("dsp32alu")], vec![]);
+    let result = SLASpecBuilder::with_filter(&filter).build(&outdir);
+    let macros = fs::read_to_string(outdir.join("includes/macros.sinc"));
+    let slaspec = fs::read_to_string(outdir.join("blackfinplus.slaspec"));
+    fs::remove_dir_all(&outdir).ok();
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    let macros = macros.unwrap();
+    assert!(macros.contains("macro sadd_sat2(dst, src0, src1) {"));
+    assert!(macros.contains("macro strunc_sat2(dst, src) {"));
+    assert!(macros.contains("macro round2_4(dst, src) {"));
+    assert!(
+        slaspec
+            .unwrap()
+            .contains("@include \"includes/macros.sinc\"")
+    );
+}
+
+#[test]
+fn reads_the_assets_of_the_data_dir() {
+    let datadir = env::temp_dir().join(format!("sawfish-data-{}", std::process::id()));
+    let outdir = datadir.join("out");
+    fs::create_dir_all(datadir.join("families")).unwrap();
+    fs::write(datadir.join("loop.sinc.part"), "# Custom loops\n").unwrap();
+
+    let filter = FamilyFilter::new(vec![String::from("Nop16")], vec![]);
+    let result = SLASpecBuilder::with_assets(&filter, Assets::with_dir(&datadir))
+        .unwrap()
+        .build(&outdir);
+    let slaspec = fs::read_to_string(outdir.join("blackfinplus.slaspec"));
+    fs::remove_dir_all(&datadir).ok();
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    assert!(slaspec.unwrap().contains("# Custom loops\n"));
+}
+
+#[test]
+fn generates_the_files_in_memory() {
+    let messages = Rc::new(RefCell::new(Vec::new()));
+    let sink = Rc::clone(&messages);
+    let filter = FamilyFilter::new(vec![String::from("CacheCtrl")], vec![]);
+    let slab = SLASpecBuilder::with_progress(
+        &filter,
+        Assets::embedded(),
+        Box::new(move |msg| sink.borrow_mut().push(msg.to_string())),
+    )
+    .unwrap();
+    let files = slab.generate().unwrap();
+
+    assert_eq!(
+        files
+            .keys()
+            .map(|path| path.to_str().unwrap())
+            .collect::<Vec<_>>(),
+        [
+            "blackfinplus.cspec",
+            "blackfinplus.pspec",
+            "blackfinplus.slaspec",
+            "includes/instr16/CacheCtrl.sinc",
+            "includes/instructions.sinc",
+            "includes/macros.sinc",
+            "includes/registers.sinc",
+        ]
+    );
+    assert!(files[Path::new("includes/instructions.sinc")].contains("instr16/CacheCtrl.sinc"));
+    assert!(
+        messages
+            .borrow()
+            .iter()
+            .any(|msg| msg.starts_with("\tCacheCtrl"))
+    );
+}
+
+#[test]
+fn generates_the_same_files_twice() {
+    let generate = |subtables: bool| {
+        let mut slab = SLASpecBuilder::new();
+        if subtables {
+            slab.factor_subtables();
+        }
+        slab.generate().unwrap()
+    };
+
+    for subtables in [false, true] {
+        let (first, second) = (generate(subtables), generate(subtables));
+
+        assert_eq!(
+            first.keys().collect::<Vec<_>>(),
+            second.keys().collect::<Vec<_>>()
+        );
+        for (path, text) in &first {
+            assert!(
+                second[path] == *text,
+                "{} differs between two runs",
+                path.display()
+            );
+        }
+    }
+}
+
+#[test]
+fn fails_on_an_invalid_family_definition() {
+    let datadir = env::temp_dir().join(format!("sawfish-bad-data-{}", std::process::id()));
+    fs::create_dir_all(datadir.join("families")).unwrap();
+    fs::write(datadir.join("families/bad.toml"), "name = \"Bad\n").unwrap();
+
+    let result = SLASpecBuilder::with_assets(&FamilyFilter::default(), Assets::with_dir(&datadir));
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_sawfish"))
+        .args(["--data-dir", datadir.to_str().unwrap(), "stats"])
+        .output()
+        .unwrap()
+        .status;
+    fs::remove_dir_all(&datadir).ok();
+
+    match result {
+        Err(Error::File { path, source }) => {
+            assert!(path.ends_with("families/bad.toml"));
+            assert_eq!(
+                source.to_string(),
+                "line 1: invalid basic string, expected `\"`"
+            );
+        }
+        other => panic!("expected a definition error, got {:?}", other.err()),
+    }
+    assert!(!status.success());
+}
+
+#[test]
+fn keeps_the_slot_of_declarative_families() {
+    let slab = SLASpecBuilder::new();
+    let names: Vec<String> = slab.families().iter().map(|ifam| ifam.name()).collect();
+    let index = names.iter().position(|name| name == "CacheCtrl").unwrap();
+
+    assert_eq!(names[index - 1], "CC2Dreg");
+    assert_eq!(names[index + 1], "CC2Stat");
+}
+
+#[test]
+fn fails_on_an_unknown_preceding_family() {
+    let datadir = env::temp_dir().join(format!("sawfish-after-{}", std::process::id()));
+    fs::create_dir_all(datadir.join("families")).unwrap();
+    let def = fs::read_to_string("data/families/cachectrl.toml").unwrap();
+    fs::write(
+        datadir.join("families/cachectrl.toml"),
+        def.replace("after = \"CC2Dreg\"", "after = \"Nope\""),
+    )
+    .unwrap();
+
+    let result = SLASpecBuilder::with_assets(&FamilyFilter::default(), Assets::with_dir(&datadir));
+    fs::remove_dir_all(&datadir).ok();
+
+    assert_eq!(
+        result.err().map(|err| err.to_string()),
+        Some(String::from("family CacheCtrl: no family Nope"))
+    );
+}
@@ -0,0 +1,205 @@
+use std::path::Path;
+
+use sawfish::slaspec::assets::Assets;
+use sawfish::slaspec::builder::SLASpecBuilder;
+use sawfish::slaspec::disasm::Disassembler;
+use sawfish::slaspec::error::Error;
+use sawfish::slaspec::instructions::decl::{load_dir, parse_family};
+
+const FAMILY: &str = r#"
+name = "Test"
+desc = "Test family"
+prefix = "tst"
+size = 16
+pcodeops = ["touch"]
+
+[[field]]
+name = "sig"
+len = 10
+type = 0x3f0
+
+[[field]]
+name = "neg"
+len = 1
+
+[[field]]
+name = "src"
+len = 2
+type = "DReg"
+
+[[field]]
+name = "dst"
+len = 3
+type = "DReg"
+
+[[variant]]
+group = "sign"
+neg = 0
+op = ""
+
+[[variant]]
+group = "sign"
+neg = 1
+op = "-"
+
+[[instr]]
+name = "Move"
+display = "{dst} = {op}{src}"
+semantics = "{dst} = {op}{src};"
+"#;
+
+#[test]
+fn builds_families_from_definitions() {
+    let def = parse_family(FAMILY).unwrap();
+    let mut ifam = def.ifam;
+    ifam.init_tokens_and_vars();
+
+    assert_eq!(def.size, 16);
+    assert_eq!(def.after, None);
+    assert_eq!(ifam.name(), "Test");
+    assert_eq!(ifam.pcodeops(), ["touch"]);
+    assert_eq!(
+        ifam.instrs()
+            .iter()
+            .map(|instr| instr.get_display())
+            .collect::<Vec<_>>(),
+        ["{dst} = {src}", "{dst} = -{src}"]
+    );
+
+    let fams = [ifam];
+    let disasm = Disassembler::new(vec![(16, &fams[..])]);
+    // sig=0x3f0, neg=1, src=R2, dst=R5
+    let instr = disasm.decode(&0xfc35u16.to_le_bytes(), 0).unwrap();
+    assert_eq!(instr.text, "R5 = -R2");
+}
+
+#[test]
+fn reports_the_line_of_errors() {
+    let line_of = |text: &str| match parse_family(text) {
+        Err(Error::Definition { line, .. }) => line,
+        res => panic!("{:?}", res.map(|def| def.ifam.name())),
+    };
+
+    assert_eq!(line_of(&FAMILY.replacen("DReg", "Nope", 1)), 20);
+    assert_eq!(line_of(&FAMILY.replace("{op}{src};", "{op}{src}")), 40);
+    assert_eq!(line_of(&FAMILY.replace("len = 3", "len = 4")), 8);
+    assert_eq!(line_of(&FAMILY.replace("size = 16", "size = 24")), 5);
+    assert_eq!(
+        line_of(&FAMILY.replace("neg = 1", "neg = \"1\"\nneg = 1")),
+        35
+    );
+}
+
+#[test]
+fn loads_the_shipped_definitions() {
+    let fams = load_dir(Path::new("data/families")).unwrap();
+    assert!(fams.iter().any(|def| def.ifam.name() == "CacheCtrl"));
+    assert!(load_dir(Path::new("data/none")).unwrap().is_empty());
+
+    // Every definition file is embedded
+    let embedded = Assets::embedded().families().unwrap();
+    assert_eq!(
+        embedded
+            .iter()
+            .map(|def| def.ifam.name())
+            .collect::<Vec<_>>(),
+        fams.iter().map(|def| def.ifam.name()).collect::<Vec<_>>()
+    );
+
+    let slab = SLASpecBuilder::new();
+    let instr = slab.assembler().assemble("FLUSH [P0++]", 0).unwrap();
+    assert_eq!(instr.bytes, [0x70, 0x02]);
+}
+
+#[test]
+fn rejects_malformed_files() {
+    let head = "name = \"Bad\"\ndesc = \"Bad family\"\nprefix = \"bad\"\nsize = 16\n";
+
+    for (text, line, reason) in [
+        (
+            String::from("name = \"Bad\n"),
+            1,
+            "invalid basic string, expected `\"`",
+        ),
+        (
+            String::from("desc = \"\"\"\nsome\ntext\n"),
+            4,
+            "invalid multi-line basic string, expected `\"`",
+        ),
+        (
+            String::from("name = \"a\"\nname = \"b\"\n"),
+            2,
+            "duplicate key",
+        ),
+        (
+            String::from("[[field]\n"),
+            1,
+            "unclosed array table, expected `]`",
+        ),
+        (
+            String::from("size 16\n"),
+            1,
+            "key with no value, expected `=`",
+        ),
+        (
+            String::from("size = 0xZZ\n"),
+            1,
+            "invalid hexadecimal number",
+        ),
+        (
+            String::from("pcodeops = [\"a\" \"b\"]\n"),
+            1,
+            "missing comma between array elements, expected `,`",
+        ),
+        (
+            String::from("name = \"a\"\ndesc = \"b\"\nsize = 16\n"),
+            1,
+            "missing field `prefix`",
+        ),
+        (
+            head.replace("size = 16", "size = \"16\""),
+            4,
+            "invalid type: string \"16\", expected i64",
+        ),
+        (
+            format!("{head}color = 1\n"),
+            5,
+            "unknown field `color`, expected one of `name`, `desc`, `prefix`, `size`, \
+             `pcodeops`, `after`, `field`, `variant`, `instr`",
+        ),
+        // A field is an array of tables, not a table
+        (
+            format!("{head}\n[field]\nname = \"f\"\nlen = 16\n"),
+            6,
+            "invalid type: map, expected a sequence",
+        ),
+        (
+            format!("{head}\n[[fields]]\nname = \"f\"\n"),
+            6,
+            "unknown field `fields`, expected one of `name`, `desc`, `prefix`, `size`, \
+             `pcodeops`, `after`, `field`, `variant`, `instr`",
+        ),
+        (
+            format!("{head}\n[[field]]\nname = \"f\"\nlen = 16\nwidth = 1\n"),
+            9,
+            "unknown field `width`, expected one of `name`, `len`, `word`, `type`",
+        ),
+        (
+            format!("{head}\n[[field]]\nname = \"f\"\nlen = 16\n\n[[instr]]\ndisplay = \"x\"\n"),
+            10,
+            "missing field `name`",
+        ),
+    ] {
+        match parse_family(&text) {
+            Err(Error::Definition {
+                line: found_line,
+                reason: found_reason,
+            }) => assert_eq!(
+                (found_line, found_reason.as_str()),
+                (line, reason),
+                "{text:?}"
+            ),
+            res => panic!("{text:?}: {:?}", res.map(|def| def.ifam.name())),
+        }
+    }
+}
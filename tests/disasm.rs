@@ -0,0 +1,55 @@
+use sawfish::slaspec::builder::SLASpecBuilder;
+
+#[test]
+fn decodes_known_encodings() {
+    let slab = SLASpecBuilder::new();
+    let disasm = slab.disassembler();
+
+    for (bytes, addr, text) in [
+        (vec![0x00, 0x00], 0, "NOP"),
+        (vec![0x10, 0x00], 0, "RTS"),
+        (vec![0x09, 0x9c], 0, "R1 = [I1++]"),
+        (vec![0x08, 0xe1, 0x34, 0x12], 0, "P0.L = 0x1234"),
+        (vec![0xfe, 0x2f], 0x1010, "JUMP.S 0x100c"),
+        (vec![0x03, 0x18], 0x1014, "if CC JUMP 0x101a"),
+    ] {
+        let instr = disasm.decode(&bytes, addr).expect(text);
+        assert_eq!(instr.text, text);
+        assert_eq!(instr.len(), bytes.len());
+    }
+}
+
+#[test]
+fn decodes_parallel_bundles() {
+    let slab = SLASpecBuilder::new();
+    let bytes = [0x01, 0xc8, 0x00, 0x9c, 0x09, 0x9c, 0x00, 0x00, 0xff, 0xff];
+    let instrs = slab.disassembler().disassemble(&bytes, 0);
+
+    assert_eq!(instrs.len(), 2);
+    assert_eq!(instrs[0].len(), 8);
+    assert!(instrs[0].text.ends_with(" || R1 = [I1++]"));
+    assert_eq!(instrs[1].text, "ILLEGAL");
+}
+
+#[test]
+fn factored_spec_decodes_like_the_plain_one() {
+    let plain = SLASpecBuilder::new();
+    let mut factored = SLASpecBuilder::new();
+    factored.factor_subtables();
+    let (plain_disasm, factored_disasm) = (plain.disassembler(), factored.disassembler());
+    let mut failures = Vec::new();
+
+    for sample in plain_disasm.samples() {
+        let bytes = sample.unwrap();
+        let decode = |disasm: &sawfish::slaspec::disasm::Disassembler| {
+            disasm.decode(&bytes, 0).map(|instr| instr.text)
+        };
+        let (expected, found) = (decode(&plain_disasm), decode(&factored_disasm));
+
+        if expected != found {
+            failures.push(format!("{bytes:02x?}: {expected:?} became {found:?}"));
+        }
+    }
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,55 @@
+use std::env;
+use std::fs;
+
+use sawfish::slaspec::builder::{FamilyFilter, SLASpecBuilder};
+
+#[test]
+fn documents_each_encoding() {
+    let outdir = env::temp_dir().join(format!("sawfish-doc-{}", std::process::id()));
+    let filter = FamilyFilter::new(vec![String::from("DAGModIk")], vec![]);
+    let result = SLASpecBuilder::with_filter(&filter).build_docs(&outdir);
+    let index = fs::read_to_string(outdir.join("index.md"));
+    let page = fs::read_to_string(outdir.join("DAGModIk.md"));
+    fs::remove_dir_all(&outdir).ok();
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+    assert!(
+        index
+            .unwrap()
+            .contains("| [DAGModIk](DAGModIk.md) | DAG Arithmetic | 16 | 4 |")
+    );
+
+    let page = page.unwrap();
+    assert_eq!(page.matches("Constructor `DagAddImm`").count(), 4);
+    assert!(page.contains(
+        "### `{i} -= 4`\n\n\
+         Constructor `DagAddImm` of table `DAGModIk`.\n\n\
+         ```text\n\
+         +---+---+---+---|---+---+---+---|---+---+---+---|---+---+---+---+\n\
+         | 1 | 0 | 0 | 1 | 1 | 1 | 1 | 1 | 0 | 1 | 1 | 0 | 1 | 1 |...i...|\n"
+    ));
+    assert!(page.contains("| `i` | 0 | 1:0 | I0 I1 I2 I3 |\n"));
+    assert!(page.contains("\tdmkIIReg = dmkIIReg - 0x4;\n"));
+}
+
+#[test]
+fn splits_sub_families_into_pages() {
+    let outdir = env::temp_dir().join(format!("sawfish-doc-sub-{}", std::process::id()));
+    let filter = FamilyFilter::new(vec![String::from("Dsp32Mac")], vec![]);
+    let result = SLASpecBuilder::with_filter(&filter).build_docs(&outdir);
+    let page = fs::read_to_string(outdir.join("Dsp32Mac.md"));
+    let sub_page = fs::read_to_string(outdir.join("Dsp32Mac").join("dmc-Cplx.md"));
+    let sub_pages = fs::read_dir(outdir.join("Dsp32Mac")).map(|dir| dir.count());
+    fs::remove_dir_all(&outdir).ok();
+
+    assert!(result.is_ok(), "{}", result.unwrap_err());
+
+    let page = page.unwrap();
+    assert!(page.contains("| [Cplx](Dsp32Mac/dmc-Cplx.md) | `Dsp32MacCplx` | 78 |\n"));
+    assert!(!page.contains("### "));
+    assert_eq!(sub_pages.unwrap(), 20);
+
+    let sub_page = sub_page.unwrap();
+    assert!(sub_page.starts_with("# Dsp32Mac: Cplx\n\n"));
+    assert_eq!(sub_page.matches("of table `Dsp32MacCplx`.").count(), 78);
+}
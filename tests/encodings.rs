@@ -0,0 +1,10 @@
+use sawfish::slaspec::builder::SLASpecBuilder;
+
+#[test]
+fn encodings_are_sound() {
+    let slab = SLASpecBuilder::new();
+
+    for report in slab.check_encodings() {
+        assert!(report.is_sound(), "{}", report.build());
+    }
+}
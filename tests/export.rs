@@ -0,0 +1,33 @@
+use std::process::Command;
+
+use sawfish::slaspec::builder::{FamilyFilter, SLASpecBuilder};
+
+#[test]
+fn exports_patterns_and_semantics() {
+    let filter = FamilyFilter::new(vec![String::from("DAGModIk")], vec![]);
+    let json = SLASpecBuilder::with_filter(&filter).export();
+
+    assert!(json.starts_with("{\n  \"families\": [\n"));
+    assert!(json.contains("\"name\": \"DAGModIk\",\n      \"prefix\": \"dmk\","));
+    assert_eq!(json.matches("\"name\": \"DagAddImm\"").count(), 4);
+    assert!(json.contains("\"display\": \"{i} -= 4\""));
+    assert!(json.contains("\"kind\": \"mask\",\n                \"value\": 2550"));
+    assert!(json.contains("\"set\": \"IReg\""));
+    assert!(json.contains("\"pcode\": \"local circ_len_i:4 = *[register]:4 (&dmkIIReg + 0x4);\\n"));
+}
+
+#[test]
+fn prints_valid_json_on_the_standard_output() {
+    let output = Command::new(env!("CARGO_BIN_EXE_sawfish"))
+        .arg("export")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(
+        json["families"]
+            .as_array()
+            .is_some_and(|fams| !fams.is_empty())
+    );
+}
@@ -0,0 +1,143 @@
+use sawfish::slaspec::builder::SLASpecBuilder;
+use sawfish::slaspec::interp::Machine;
+
+const ADDR: u32 = 0xffa0_1000;
+
+/// Assembles `text` at `ADDR`, runs it on `machine` and returns the new PC.
+fn run(slab: &SLASpecBuilder, machine: &mut Machine, text: &str) -> u32 {
+    let instr = slab.assembler().assemble(text, ADDR).expect(text);
+    machine.write_bytes(ADDR, &instr.bytes);
+    machine.set_reg("PC", ADDR as u64);
+
+    slab.interpreter().step(machine).expect(text);
+    machine.reg("PC") as u32
+}
+
+#[test]
+fn saturates_32_bit_add() {
+    let slab = SLASpecBuilder::new();
+    let mut machine = Machine::new();
+    machine.set_reg("R1", 0x7fff_ffff);
+    machine.set_reg("R2", 0x1);
+
+    assert_eq!(run(&slab, &mut machine, "R0 = R1 + R2 (S)"), ADDR + 4);
+    assert_eq!(machine.reg("R0"), 0x7fff_ffff);
+
+    run(&slab, &mut machine, "R0 = R1 + R2 (NS)");
+    assert_eq!(machine.reg("R0"), 0x8000_0000);
+
+    machine.set_reg("R1", 0x8000_0000);
+    run(&slab, &mut machine, "R0 = R1 - R2 (S)");
+    assert_eq!(machine.reg("R0"), 0x8000_0000);
+}
+
+#[test]
+fn loads_and_post_increments() {
+    let slab = SLASpecBuilder::new();
+    let mut machine = Machine::new();
+    machine.set_reg("I1", 0xff80_0000);
+    machine.store(0xff80_0000, 4, 0x1234_5678);
+
+    run(&slab, &mut machine, "R1 = [I1++]");
+    assert_eq!(machine.reg("R1"), 0x1234_5678);
+    assert_eq!(machine.reg("I1"), 0xff80_0004);
+    assert_eq!(machine.reg("R1.H"), 0x1234);
+}
+
+#[test]
+fn returns_and_moves_on_cc() {
+    let slab = SLASpecBuilder::new();
+    let mut machine = Machine::new();
+    machine.set_reg("RETS", 0xffa0_2000);
+    machine.set_reg("R1", 0x42);
+
+    assert_eq!(run(&slab, &mut machine, "RTS"), 0xffa0_2000);
+
+    run(&slab, &mut machine, "IF CC R0 = R1");
+    assert_eq!(machine.reg("R0"), 0x0);
+
+    machine.set_reg("CC", 1);
+    assert_eq!(machine.reg("ASTAT"), 0x20);
+    run(&slab, &mut machine, "IF CC R0 = R1");
+    assert_eq!(machine.reg("R0"), 0x42);
+}
+
+#[test]
+fn bundle_slots_read_old_registers() {
+    let slab = SLASpecBuilder::new();
+    let mut machine = Machine::new();
+    machine.set_reg("R1", 0x10);
+    machine.set_reg("R2", 0x20);
+    machine.set_reg("I0", 0xff80_0000);
+
+    assert_eq!(
+        run(&slab, &mut machine, "R1 = R1 + R2 (NS) || [I0++] = R1"),
+        ADDR + 8
+    );
+    assert_eq!(machine.reg("R1"), 0x30);
+    assert_eq!(machine.reg("I0"), 0xff80_0004);
+    assert_eq!(machine.load(0xff80_0000, 4), 0x10);
+}
+
+#[test]
+fn post_modify_wraps_in_circular_buffers() {
+    let slab = SLASpecBuilder::new();
+    let mut machine = Machine::new();
+    machine.set_reg("B1", 0xff80_0000);
+    machine.set_reg("L1", 0x8);
+    machine.set_reg("I1", 0xff80_0004);
+    machine.store(0xff80_0004, 4, 0x1234_5678);
+
+    run(&slab, &mut machine, "R1 = [I1++]");
+    assert_eq!(machine.reg("R1"), 0x1234_5678);
+    assert_eq!(machine.reg("I1"), 0xff80_0000);
+
+    run(&slab, &mut machine, "I1 -= 2");
+    assert_eq!(machine.reg("I1"), 0xff80_0006);
+
+    machine.set_reg("M1", (-4i32) as u32 as u64);
+    run(&slab, &mut machine, "I1 += M1");
+    assert_eq!(machine.reg("I1"), 0xff80_0002);
+    run(&slab, &mut machine, "I1 += M1");
+    assert_eq!(machine.reg("I1"), 0xff80_0006);
+
+    machine.set_reg("L1", 0x0);
+    run(&slab, &mut machine, "I1 -= M1");
+    assert_eq!(machine.reg("I1"), 0xff80_000a);
+}
+
+#[test]
+fn writes_astat_flags() {
+    let slab = SLASpecBuilder::new();
+    let mut machine = Machine::new();
+    machine.set_reg("R1", 0x7fff_ffff);
+    machine.set_reg("R2", 0x1);
+
+    run(&slab, &mut machine, "R0 = R1 + R2 (NS)");
+    assert_eq!(machine.reg("R0"), 0x8000_0000);
+    assert_eq!(machine.reg("V"), 1);
+    assert_eq!(machine.reg("VS"), 1);
+    assert_eq!(machine.reg("VCOPY"), 1);
+    assert_eq!(machine.reg("AN"), 1);
+    assert_eq!(machine.reg("AZ"), 0);
+    assert_eq!(machine.reg("AC0"), 0);
+
+    // Logic operations clear V but the sticky flag stays set
+    run(&slab, &mut machine, "R0 = R1 & R2");
+    assert_eq!(machine.reg("V"), 0);
+    assert_eq!(machine.reg("VS"), 1);
+    assert_eq!(machine.reg("AN"), 0);
+
+    run(&slab, &mut machine, "R0 = R1 - R1 (NS)");
+    assert_eq!(machine.reg("AZ"), 1);
+    assert_eq!(machine.reg("AC0"), 1);
+    assert_eq!(machine.reg("AC0COPY"), 1);
+
+    machine.set_reg("VS", 0);
+    machine.set_reg("R1", 0x8000_0000);
+    machine.set_reg("R2", 0x8000_0000);
+    run(&slab, &mut machine, "R0 = R1 * R2");
+    assert_eq!(machine.reg("R0"), 0x7fff_ffff);
+    assert_eq!(machine.reg("V"), 1);
+    assert_eq!(machine.reg("VS"), 1);
+}
@@ -0,0 +1,18 @@
+use sawfish::slaspec::instructions::instr32::dsp32alu;
+
+#[test]
+fn folds_constants_before_emission() {
+    let sinc = dsp32alu::instr_fam().build().unwrap();
+
+    assert!(sinc.contains("\tresL = 0x7fff;\n"));
+    assert!(sinc.contains("zext(resH << 0x10) | zext(resL);"));
+    assert!(!sinc.contains("(0x1 << 0xf)"));
+    assert!(!sinc.contains(" + 0x0"));
+}
+
+#[test]
+fn calls_shared_macros_on_registers() {
+    let sinc = dsp32alu::instr_fam().build().unwrap();
+
+    assert!(sinc.contains("\tsadd_sat2(dauDst0DRegL, dauSrc0DRegL, dauSrc1DRegL);\n"));
+}
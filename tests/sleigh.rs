@@ -0,0 +1,174 @@
+use sawfish::slaspec::sleigh::{
+    Attach, AttachKind, BitRange, Constraint, Constructor, ContextDef, ContextField,
+    DisplaySection, Document, Item, Layout, RegisterDef, SpaceDef, SpaceKind, Stmt, TokenDef,
+    TokenField,
+};
+
+fn eq(field: &str, value: &str) -> Constraint {
+    Constraint::Eq {
+        field: field.to_string(),
+        value: value.to_string(),
+    }
+}
+
+#[test]
+fn prints_constructors() {
+    let block = Constructor {
+        table: String::from("Fam"),
+        display: DisplaySection::Operands(String::from("^\"Add\" {r} += 1")),
+        pattern: vec![
+            vec![
+                eq("famSig", "0x12"),
+                Constraint::Operand(String::from("famR")),
+            ],
+            vec![eq("famZero", "0x0")],
+        ],
+        actions: vec![],
+        semantics: vec![
+            Stmt::Op(String::from("famR = famR + 1")),
+            Stmt::Label(String::from("<end>")),
+        ],
+        layout: Layout::Block,
+    };
+    let inline = Constructor {
+        table: String::new(),
+        display: DisplaySection::Operands(String::from("^Fam")),
+        pattern: vec![vec![
+            Constraint::Prefix(Box::new(eq("famM", "0x1"))),
+            Constraint::Operand(String::from("Fam")),
+        ]],
+        actions: vec![],
+        semantics: vec![
+            Stmt::Op(String::from("build Fam")),
+            Stmt::Op(String::from("delayslot(4)")),
+        ],
+        layout: Layout::Inline,
+    };
+
+    assert_eq!(
+        Item::Constructor(block).build(),
+        "Fam:^\"Add\" {r} += 1\n\tis famSig=0x12 & famR \n\t ; famZero=0x0 \
+         \n{\n\tfamR = famR + 1;\n<end>\n}"
+    );
+    assert_eq!(
+        Item::Constructor(inline).build(),
+        ":^Fam is famM=0x1 ... & Fam { build Fam; delayslot(4); }"
+    );
+}
+
+#[test]
+fn prints_definitions() {
+    let mut doc = Document::new();
+
+    doc.push(Item::Comment(String::from("## Tokens ###")));
+    doc.push(Item::Token(TokenDef {
+        name: String::from("famInstr16"),
+        size: 16,
+        fields: vec![TokenField {
+            name: String::from("famImm"),
+            start: 0,
+            end: 6,
+            signed: true,
+        }],
+    }));
+    doc.push(Item::Attach(Attach {
+        kind: AttachKind::Variables,
+        fields: vec![String::from("famR")],
+        values: (0..10).map(|i| format!("R{i}")).collect(),
+    }));
+    doc.push(Item::PcodeOp(String::from("idle")));
+
+    assert_eq!(
+        doc.build(),
+        "### Tokens ###\n\
+         define token famInstr16 (16)\n\tfamImm           = ( 0, 6) signed\n;\n\
+         attach variables famR [\n\tR0 R1 R2 R3 R4 R5 R6 R7\n\tR8 R9\n];\n\
+         define pcodeop idle;\n"
+    );
+}
+
+#[test]
+fn escapes_literal_displays() {
+    let literal = Constructor {
+        table: String::from("FamDesc00"),
+        display: DisplaySection::Literal(String::from("say \"a\\b\"")),
+        pattern: vec![vec![Constraint::Operand(String::from("epsilon"))]],
+        actions: vec![],
+        semantics: vec![],
+        layout: Layout::Inline,
+    };
+
+    assert_eq!(
+        Item::Constructor(literal).build(),
+        "FamDesc00: \"say \\\"a\\\\b\\\"\" is epsilon {}"
+    );
+}
+
+#[test]
+fn prints_register_definitions() {
+    let mut doc = Document::new();
+
+    doc.push(Item::Define {
+        key: String::from("endian"),
+        value: String::from("little"),
+    });
+    doc.push(Item::Space(SpaceDef {
+        name: String::from("ram"),
+        kind: SpaceKind::Ram,
+        size: 4,
+        default: true,
+    }));
+    doc.push(Item::Space(SpaceDef {
+        name: String::from("register"),
+        kind: SpaceKind::Register,
+        size: 2,
+        default: false,
+    }));
+    doc.push(Item::Register(RegisterDef {
+        offset: 0x10,
+        size: 4,
+        names: (0..9)
+            .map(|i| {
+                if i == 1 {
+                    String::from("_")
+                } else {
+                    format!("R{i}")
+                }
+            })
+            .collect(),
+    }));
+    doc.push(Item::BitRanges(vec![BitRange {
+        name: String::from("CC"),
+        reg: String::from("ASTAT"),
+        start: 5,
+        len: 1,
+    }]));
+    doc.push(Item::Context(ContextDef {
+        register: String::from("contextreg"),
+        fields: vec![ContextField {
+            name: String::from("phase"),
+            start: 0,
+            end: 1,
+            noflow: true,
+        }],
+    }));
+
+    assert_eq!(
+        doc.build(),
+        "define endian=little;\n\
+         define space ram type=ram_space size=4 default;\n\
+         define space register type=register_space size=2;\n\
+         define register offset=0x0010 size=4 [\n    R0 _ R2 R3 R4 R5 R6 R7\n    R8\n];\n\
+         define bitrange\n    CC=ASTAT[5,1]\n;\n\
+         define context contextreg\n    phase       = (0,1) noflow\n;\n"
+    );
+}
+
+#[test]
+fn families_build_from_their_document() {
+    let ifam = sawfish::slaspec::instructions::instr16::nop16::instr_fam();
+    let doc = ifam.document().unwrap();
+
+    assert_eq!(doc.build(), ifam.build().unwrap());
+    assert!(doc.constructors().any(|cons| cons.table == "NOP16"));
+}
@@ -0,0 +1,111 @@
+//! Compares the SLEIGH of every family with the golden files of
+//! `tests/snapshots`. Run with `UPDATE_SNAPSHOTS=1` to rewrite them.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sawfish::slaspec::builder::SLASpecBuilder;
+use sawfish::slaspec::instructions::core::Prefixed;
+
+const SNAPSHOT_DIR: &str = "tests/snapshots";
+
+/// The SLEIGH of every family, laid out like the instruction files of a
+/// build: a family split into sub-families gets a file per sub-family.
+fn render(slab: &SLASpecBuilder) -> Vec<(PathBuf, String)> {
+    let mut files = vec![];
+
+    for ifam in slab.families() {
+        let name = ifam.name();
+
+        if ifam.sub_fam() == 1 {
+            files.push((PathBuf::from(format!("{name}.sinc")), ifam.build().unwrap()));
+            continue;
+        }
+
+        files.push((
+            PathBuf::from(format!("{name}.sinc")),
+            ifam.build_head().build(),
+        ));
+        for (id, doc) in ifam.build_id_instrs().unwrap() {
+            files.push((
+                Path::new(&name).join(format!("{}-{id}.sinc", ifam.prefix())),
+                doc.build(),
+            ));
+        }
+    }
+
+    files
+}
+
+fn first_difference(expected: &str, found: &str) -> String {
+    match expected
+        .lines()
+        .zip(found.lines())
+        .position(|(expected, found)| expected != found)
+    {
+        Some(line) => format!(
+            "line {}: expected `{}`, found `{}`",
+            line + 1,
+            expected.lines().nth(line).unwrap(),
+            found.lines().nth(line).unwrap()
+        ),
+        None => format!(
+            "expected {} line(s), found {}",
+            expected.lines().count(),
+            found.lines().count()
+        ),
+    }
+}
+
+#[test]
+fn matches_the_golden_files() {
+    let slab = SLASpecBuilder::new();
+    let dir = Path::new(SNAPSHOT_DIR);
+    let files = render(&slab);
+
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::remove_dir_all(dir).ok();
+        for (path, text) in &files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, text).unwrap();
+        }
+        return;
+    }
+
+    let mut failures = vec![];
+
+    for (path, text) in &files {
+        match fs::read_to_string(dir.join(path)) {
+            Ok(golden) if golden == *text => {}
+            Ok(golden) => failures.push(format!(
+                "{}: {}",
+                path.display(),
+                first_difference(&golden, text)
+            )),
+            Err(_) => failures.push(format!("{}: no golden file", path.display())),
+        }
+    }
+
+    let mut golden_files = vec![];
+    for entry in fs::read_dir(dir).unwrap().flatten() {
+        match entry.path().is_dir() {
+            true => golden_files.extend(fs::read_dir(entry.path()).unwrap().flatten()),
+            false => golden_files.push(entry),
+        }
+    }
+    for entry in golden_files {
+        let path = entry.path().strip_prefix(dir).unwrap().to_path_buf();
+        if !files.iter().any(|(known, _)| *known == path) {
+            failures.push(format!("{}: no family renders it", path.display()));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} snapshot(s) differ, run with UPDATE_SNAPSHOTS=1 to accept the changes:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
@@ -0,0 +1,31 @@
+use sawfish::slaspec::builder::{FamilyFilter, SLASpecBuilder};
+
+#[test]
+fn counts_constructors_and_ops() {
+    let filter = FamilyFilter::new(vec![String::from("DAGModIk")], vec![]);
+    let stats = SLASpecBuilder::with_filter(&filter).stats();
+    let ifam = &stats.families[0];
+
+    assert_eq!(stats.families.len(), 1);
+    assert_eq!(ifam.name, "DAGModIk");
+    assert_eq!(ifam.constructors(), 4);
+    assert_eq!((ifam.tokens, ifam.attaches, ifam.attached_names), (3, 1, 4));
+    assert!(ifam.pcode_ops() > 4 * 4);
+    assert!(stats.fits());
+}
+
+#[test]
+fn reports_every_family() {
+    let mut slab = SLASpecBuilder::new();
+    let stats = slab.stats();
+    let report = stats.build();
+
+    assert_eq!(stats.families.len(), slab.families().len());
+    assert!(report.contains("Dsp32Mac "));
+    assert!(report.contains("\nTotal "));
+
+    slab.factor_subtables();
+    let factored = slab.stats();
+    assert!(factored.sla_bytes() < stats.sla_bytes());
+    assert!(factored.fits());
+}